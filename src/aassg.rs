@@ -0,0 +1,320 @@
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+use crate::bartering::{self, Barters};
+
+// A 1.16 AASSG run split by split. Every split can end the run, and otherwise takes a normally
+// distributed number of seconds. The Bastion is played out with real barters instead: the run
+// needs Fire Resistance, Ender Pearls for the eyes and a respawn anchor's worth of Crying Obsidian
+// and Glowstone Dust for the explosives.
+const BASTION_GOLD: std::ops::RangeInclusive<u64> = 40..=200;
+const BASTION_PEARLS: u64 = 12;
+const BASTION_CRYING_OBSIDIAN: u64 = 6;
+const BASTION_GLOWSTONE_DUST: u64 = 12;
+// seconds each barter adds to the Bastion split
+const SECONDS_PER_GOLD: f64 = 0.75;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Split {
+    Temple,
+    NetherEntry,
+    Bastion,
+    Fortress,
+    Stronghold,
+    End,
+    Advancements,
+}
+
+impl Split {
+    pub const ALL: [Split; 7] = [
+        Split::Temple,
+        Split::NetherEntry,
+        Split::Bastion,
+        Split::Fortress,
+        Split::Stronghold,
+        Split::End,
+        Split::Advancements,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Split::Temple => "Temple",
+            Split::NetherEntry => "Nether",
+            Split::Bastion => "Bastion",
+            Split::Fortress => "Fortress",
+            Split::Stronghold => "Stronghold",
+            Split::End => "End",
+            Split::Advancements => "Advancements",
+        }
+    }
+}
+
+struct SplitModel {
+    split: Split,
+    // odds that the run dies during the split, and what kills it by weight
+    death_odds: f64,
+    deaths: &'static [(u32, &'static str)],
+    // seconds the split takes
+    mean: f64,
+    spread: f64,
+    shortest: f64,
+}
+
+const SPLITS: [SplitModel; 7] = [
+    SplitModel {
+        split: Split::Temple,
+        death_odds: 0.1,
+        deaths: &[
+            (10, "Your any% Temple blew up."),
+            (20, "You died to a Creeper in your any% Temple."),
+            (35, "You died to a Zombie in your any% Temple."),
+            (30, "You died to a Skeleton in your any% Temple."),
+            (5, "You died to a Witch in your any% Temple."),
+        ],
+        mean: 150.0,
+        spread: 40.0,
+        shortest: 60.0,
+    },
+    SplitModel {
+        split: Split::NetherEntry,
+        death_odds: 0.04,
+        deaths: &[
+            (40, "You mispalced obby when building your first nether portal."),
+            (135, "Your run died to slow pre-Nether any% crafting."),
+            (135, "You couldn't find your wood after blowing up trees in any%."),
+            (90, "You accidentally hit one of the Zombiefied Piglins in pre-Bastion any%, so they killed you."),
+        ],
+        mean: 240.0,
+        spread: 60.0,
+        shortest: 90.0,
+    },
+    // the barters decide how the Bastion goes
+    SplitModel {
+        split: Split::Bastion,
+        death_odds: 0.0,
+        deaths: &[],
+        mean: 120.0,
+        spread: 40.0,
+        shortest: 45.0,
+    },
+    SplitModel {
+        split: Split::Fortress,
+        death_odds: 0.08,
+        deaths: &[
+            (45, "You died to Blazes in the Fortress."),
+            (35, "A Ghast knocked you into lava on the way to the Fortress."),
+            (20, "You died to a Wither Skeleton while looking for Blazes."),
+        ],
+        mean: 240.0,
+        spread: 80.0,
+        shortest: 90.0,
+    },
+    SplitModel {
+        split: Split::Stronghold,
+        death_odds: 0.05,
+        deaths: &[
+            (50, "Your eyes broke and you ran out before finding the Stronghold."),
+            (30, "You got lost in the Stronghold."),
+            (20, "A Silverfish swarm killed you at the portal room."),
+        ],
+        mean: 420.0,
+        spread: 120.0,
+        shortest: 150.0,
+    },
+    SplitModel {
+        split: Split::End,
+        death_odds: 0.04,
+        deaths: &[
+            (60, "The Ender Dragon one-cycled you, just not the way you wanted."),
+            (40, "You fell into the void while fighting the Ender Dragon."),
+        ],
+        mean: 240.0,
+        spread: 60.0,
+        shortest: 90.0,
+    },
+    SplitModel {
+        split: Split::Advancements,
+        death_odds: 0.15,
+        deaths: &[
+            (35, "No thunder came for Very Very Frightening."),
+            (25, "You never found a Trident for the remaining advancements."),
+            (20, "Phantoms killed you while you chased the last biomes."),
+            (20, "You died to a Hoglin while getting the last advancements."),
+        ],
+        mean: 3600.0,
+        spread: 600.0,
+        shortest: 1800.0,
+    },
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    // the splits the run finished and the seconds into the run each one ended at
+    pub splits: Vec<(Split, u64)>,
+    // the split that killed the run, why and when
+    pub death: Option<(Split, &'static str, u64)>,
+    pub barters: Option<Barters>,
+}
+
+impl Run {
+    // seconds until the run finished or died
+    pub fn time(&self) -> u64 {
+        match self.death {
+            Some((_, _, time)) => time,
+            None => self.splits.last().map_or(0, |(_, time)| *time),
+        }
+    }
+}
+
+pub fn simulate_run<R: Rng>(rng: &mut R) -> Run {
+    let mut run: Run = Run { splits: Vec::new(), death: None, barters: None };
+    let mut time: f64 = 0.0;
+
+    for model in &SPLITS {
+        let mut duration: f64 = Normal::new(model.mean, model.spread)
+            .expect("split spreads are positive")
+            .sample(rng)
+            .max(model.shortest);
+        let mut death: Option<&'static str> = None;
+
+        if model.split == Split::Bastion {
+            let barters: Barters = bartering::barter(rng.gen_range(BASTION_GOLD), rng);
+            duration += barters.gold as f64 * SECONDS_PER_GOLD;
+            death = bastion_death(&barters);
+            run.barters = Some(barters);
+        } else if rng.gen::<f64>() < model.death_odds {
+            death = Some(pick_death(model.deaths, rng));
+        }
+
+        if let Some(reason) = death {
+            let died_at: f64 = time + rng.gen::<f64>() * duration;
+            run.death = Some((model.split, reason, died_at as u64));
+            return run;
+        }
+
+        time += duration;
+        run.splits.push((model.split, time as u64));
+    }
+
+    run
+}
+
+fn bastion_death(barters: &Barters) -> Option<&'static str> {
+    if barters.fire_resistance() == 0 {
+        Some("You died to Blazes during any% Nether, beacuse you didn't get Fire Resistance from barters.")
+    } else if barters.count("minecraft:ender_pearl") < BASTION_PEARLS {
+        Some("You didn't get enough Ender Pearls from barters in any% Nether.")
+    } else if barters.count("minecraft:crying_obsidian") < BASTION_CRYING_OBSIDIAN || barters.count("minecraft:glowstone_dust") < BASTION_GLOWSTONE_DUST {
+        Some("You didn't get enough materials for Explosives from barters in any% Nether")
+    } else {
+        None
+    }
+}
+
+fn pick_death<R: Rng>(deaths: &[(u32, &'static str)], rng: &mut R) -> &'static str {
+    let mut pick: u32 = rng.gen_range(0..deaths.iter().map(|(weight, _)| weight).sum::<u32>());
+
+    for (weight, reason) in deaths {
+        if pick < *weight {
+            return reason;
+        }
+        pick -= weight;
+    }
+
+    unreachable!("the pick is below the total weight")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunOdds {
+    pub runs: u64,
+    // seconds each finished run took
+    pub finish_times: Vec<u64>,
+    // how many runs died in each split, in split order
+    pub deaths: [u64; 7],
+}
+
+impl RunOdds {
+    pub fn completion_rate(&self) -> f64 {
+        self.finish_times.len() as f64 / self.runs as f64
+    }
+
+    // The split that ended the most runs, and how many it ended, or None when every run finished.
+    pub fn deadliest_split(&self) -> Option<(Split, u64)> {
+        Split::ALL.into_iter().zip(self.deaths).filter(|(_, deaths)| *deaths > 0).max_by_key(|(_, deaths)| *deaths)
+    }
+
+    // Finished runs counted in buckets of `bucket` seconds, from the fastest bucket to the slowest.
+    pub fn histogram(&self, bucket: u64) -> Vec<(u64, u64)> {
+        let mut histogram: Vec<(u64, u64)> = Vec::new();
+
+        for time in &self.finish_times {
+            let start: u64 = time / bucket * bucket;
+            match histogram.iter_mut().find(|(other, _)| *other == start) {
+                Some((_, count)) => *count += 1,
+                None => histogram.push((start, 1)),
+            }
+        }
+
+        histogram.sort();
+        histogram
+    }
+}
+
+pub fn simulate_runs<R: Rng>(runs: u64, rng: &mut R) -> RunOdds {
+    let mut odds: RunOdds = RunOdds { runs, finish_times: Vec::new(), deaths: [0; 7] };
+
+    for _ in 0..runs {
+        let run: Run = simulate_run(rng);
+        match run.death {
+            Some((split, _, _)) => odds.deaths[split as usize] += 1,
+            None => odds.finish_times.push(run.time()),
+        }
+    }
+
+    odds
+}
+
+// 5415 -> "1:30:15"
+pub fn format_run_time(seconds: u64) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    #[test]
+    fn runs_go_through_the_splits_in_order() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+
+        for _ in 0..200 {
+            let run: Run = simulate_run(&mut rng);
+            let reached: usize = run.splits.len() + run.death.iter().count();
+
+            assert!(run.splits.iter().zip(Split::ALL).all(|((split, _), expected)| *split == expected));
+            assert!(run.splits.windows(2).all(|pair| pair[0].1 < pair[1].1));
+            assert_eq!(run.barters.is_some(), reached > Split::Bastion as usize);
+            if let Some((split, _, time)) = run.death {
+                assert_eq!(split, Split::ALL[run.splits.len()]);
+                assert!(time >= run.splits.last().map_or(0, |(_, end)| *end));
+            }
+        }
+    }
+
+    #[test]
+    fn odds_add_up() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let odds: RunOdds = simulate_runs(1_000, &mut rng);
+
+        assert_eq!(odds.finish_times.len() as u64 + odds.deaths.iter().sum::<u64>(), 1_000);
+        assert_eq!(odds.histogram(600).iter().map(|(_, count)| count).sum::<u64>(), odds.finish_times.len() as u64);
+        // the Temple alone ends about a tenth of the runs
+        assert!((60..=140).contains(&odds.deaths[Split::Temple as usize]), "{:?}", odds.deaths);
+        assert!(odds.completion_rate() > 0.0 && odds.completion_rate() < 1.0);
+        assert_eq!(odds.deadliest_split().map(|(_, deaths)| deaths), odds.deaths.iter().max().copied());
+        assert_eq!(RunOdds { runs: 1, finish_times: vec![5415], deaths: [0; 7] }.deadliest_split(), None);
+        assert_eq!(format_run_time(5415), "1:30:15");
+    }
+}
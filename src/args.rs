@@ -0,0 +1,126 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::registry::CommandError;
+use crate::rng::MAX_SEED;
+use crate::weather::WeatherModel;
+
+// A numeric command argument and the values it's allowed to take. The limits keep every
+// command fast enough for chat and stop users from overflowing the simulations.
+pub struct NumberArg<T> {
+    pub name: &'static str,
+    pub min: T,
+    pub max: T,
+}
+
+impl<T: FromStr + PartialOrd + Display + Copy> NumberArg<T> {
+    pub const fn new(name: &'static str, min: T, max: T) -> Self {
+        NumberArg { name, min, max }
+    }
+
+    // Reads message_parts[index]. A missing argument is a syntax error, so the chatter gets
+    // the command's usage; anything else that isn't a number in range explains the limits.
+    pub fn parse(&self, message_parts: &[&str], index: usize) -> Result<T, CommandError> {
+        let arg: &str = match message_parts.get(index) {
+            Some(arg) => arg,
+            None => return Err(CommandError::InvalidSyntax),
+        };
+
+        match arg.parse::<T>() {
+            Ok(value) if self.min <= value && value <= self.max => Ok(value),
+            _ => Err(CommandError::Message(format!(
+                "Error: {} must be a number from {} to {}.",
+                self.name,
+                self.min,
+                self.max
+            ))),
+        }
+    }
+}
+
+const SEED: NumberArg<u64> = NumberArg::new("seed", 0, MAX_SEED);
+
+// Takes a "name=value" argument out of the message, wherever it is, so the other arguments keep
+// their positions.
+pub fn take_option<'a>(message_parts: &mut Vec<&'a str>, name: &str) -> Option<&'a str> {
+    let prefix: String = format!("{}=", name);
+    let position: usize = message_parts.iter().skip(1).position(|part| part.to_lowercase().starts_with(&prefix))?;
+
+    Some(&message_parts.remove(position + 1)[prefix.len()..])
+}
+
+// Options left after a command took the ones it knows are typos.
+pub fn reject_unknown_options(message_parts: &[&str]) -> Result<(), CommandError> {
+    match message_parts.iter().skip(1).find_map(|part| part.split_once('=')) {
+        Some((name, _)) => Err(CommandError::Message(format!("Error: there's no option called {}.", name))),
+        None => Ok(()),
+    }
+}
+
+pub fn take_seed(message_parts: &mut Vec<&str>) -> Result<Option<u64>, CommandError> {
+    match take_option(message_parts, "seed") {
+        None => Ok(None),
+        Some(seed) => SEED.parse(&[seed], 0).map(Some),
+    }
+}
+
+// Reads an optional game version like "1.16" at message_parts[index]. Without one the
+// commands use the default weather model.
+pub fn parse_weather_model(message_parts: &[&str], index: usize) -> Result<WeatherModel, CommandError> {
+    match message_parts.get(index) {
+        None => Ok(WeatherModel::default()),
+        Some(version) => WeatherModel::from_version(version).ok_or_else(|| {
+            CommandError::Message("Error: version must be 1.12 or later, like 1.16 or 1.20.5.".to_owned())
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOOTING_LEVEL: NumberArg<u32> = NumberArg::new("looting level", 0, 3);
+    const MINUTES: NumberArg<f64> = NumberArg::new("time in minutes", 0.0, 600.0);
+
+    #[test]
+    fn parses_values_in_range() {
+        assert_eq!(LOOTING_LEVEL.parse(&["!rollskulls", "5", "3"], 2), Ok(3));
+        assert_eq!(MINUTES.parse(&["!thunderodds", "12.5"], 1), Ok(12.5));
+    }
+
+    #[test]
+    fn missing_args_are_syntax_errors() {
+        assert_eq!(LOOTING_LEVEL.parse(&["!rollskulls", "5"], 2), Err(CommandError::InvalidSyntax));
+    }
+
+    #[test]
+    fn explains_the_limits() {
+        let error: Result<u32, CommandError> =
+            Err(CommandError::Message("Error: looting level must be a number from 0 to 3.".to_owned()));
+
+        assert_eq!(LOOTING_LEVEL.parse(&["!rollskulls", "5", "4"], 2), error);
+        assert_eq!(LOOTING_LEVEL.parse(&["!rollskulls", "5", "-1"], 2), error);
+        assert_eq!(LOOTING_LEVEL.parse(&["!rollskulls", "5", "three"], 2), error);
+        assert_eq!(LOOTING_LEVEL.parse(&["!rollskulls", "5", "99999999999999999999"], 2), error);
+        assert!(MINUTES.parse(&["!thunderodds", "NaN"], 1).is_err());
+        assert!(MINUTES.parse(&["!thunderodds", "inf"], 1).is_err());
+    }
+
+    #[test]
+    fn seeds_are_taken_out_of_the_args() {
+        let mut message_parts: Vec<&str> = vec!["!rolldrowned", "seed=1234", "100", "3"];
+
+        assert_eq!(take_seed(&mut message_parts), Ok(Some(1234)));
+        assert_eq!(message_parts, vec!["!rolldrowned", "100", "3"]);
+        assert_eq!(take_seed(&mut message_parts), Ok(None));
+        assert!(take_seed(&mut vec!["!rolltrident", "seed=-1"]).is_err());
+        assert!(take_seed(&mut vec!["!rolltrident", "seed=9223372036854775808"]).is_err());
+    }
+
+    #[test]
+    fn versions_are_optional() {
+        assert_eq!(parse_weather_model(&["!weather"], 1), Ok(WeatherModel::default()));
+        assert_eq!(parse_weather_model(&["!weather", "1.12.2"], 1), Ok(WeatherModel::Java1_16));
+        assert!(parse_weather_model(&["!weather", "1.8"], 1).is_err());
+    }
+}
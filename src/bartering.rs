@@ -0,0 +1,68 @@
+use rand::Rng;
+
+use crate::loot::{self, loot_tables, LootContext};
+use crate::mob_drops;
+
+// 1.16.1's piglin bartering table, which speedruns play on: 423 weight in total, with 20 for
+// 4 to 8 Ender Pearls and 10 each for a Fire Resistance potion and splash potion. Every potion it
+// gives is Fire Resistance and every book and pair of boots has Soul Speed.
+const BARTERING: &str = "minecraft:gameplay/piglin_bartering";
+const FIRE_RESISTANCE: [&str; 2] = ["minecraft:potion", "minecraft:splash_potion"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Barters {
+    pub gold: u64,
+    // how many of every item the bartering table can give, in the table's order
+    pub items: Vec<(String, u64)>,
+}
+
+impl Barters {
+    pub fn count(&self, item: &str) -> u64 {
+        self.items.iter().filter(|(other, _)| other == item).map(|(_, count)| count).sum()
+    }
+
+    pub fn fire_resistance(&self) -> u64 {
+        FIRE_RESISTANCE.iter().map(|potion| self.count(potion)).sum()
+    }
+}
+
+// Trades `gold` gold ingots, one at a time.
+pub fn barter<R: Rng>(gold: u64, rng: &mut R) -> Barters {
+    let context: LootContext = LootContext::default();
+
+    Barters { gold, items: mob_drops::roll_totals(loot_tables(), BARTERING, gold, &context, rng) }
+}
+
+// "minecraft:splash_potion" -> "Splash Fire Resistance Potion"
+pub fn barter_name(item: &str) -> String {
+    match item {
+        "minecraft:potion" => "Fire Resistance Potion".to_owned(),
+        "minecraft:splash_potion" => "Splash Fire Resistance Potion".to_owned(),
+        "minecraft:book" => "Soul Speed Book".to_owned(),
+        "minecraft:iron_boots" => "Soul Speed Boots".to_owned(),
+        _ => loot::item_name(item),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    #[test]
+    fn barters_follow_the_weights() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let gold: u64 = 20_000;
+        let barters: Barters = barter(gold, &mut rng);
+
+        // 20/423 of the trades give 6 pearls on average, 10/423 give each kind of potion
+        let pearls: f64 = gold as f64 * 20.0 / 423.0 * 6.0;
+        let potions: f64 = gold as f64 * 20.0 / 423.0;
+        assert!((barters.count("minecraft:ender_pearl") as f64 - pearls).abs() < 5.0 * (gold as f64 * 20.0 / 423.0 * 38.0).sqrt());
+        assert!((barters.fire_resistance() as f64 - potions).abs() < 5.0 * potions.sqrt());
+        assert_eq!(barters.count("minecraft:diamond"), 0);
+        assert_eq!(barter_name("minecraft:splash_potion"), "Splash Fire Resistance Potion");
+        assert_eq!(barter_name("minecraft:crying_obsidian"), "Crying Obsidian");
+    }
+}
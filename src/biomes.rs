@@ -0,0 +1,199 @@
+use rand::Rng;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::OnceLock;
+
+// Biome weights for !rollbiome, one table per biome set. The 1.16 table counts how much of the
+// surface each biome covers; a table applies from its version until the next table's version.
+// The 1.18 table is only a guess at how common each biome is, so it's marked as estimated and the
+// commands say so.
+const BUNDLED: &str = include_str!("../data/biomes.json");
+pub const DEFAULT_VERSION: &str = "1.16";
+
+static BIOME_TABLES: OnceLock<BiomeTables> = OnceLock::new();
+
+pub fn init_biome_tables() -> Result<(), BiomeError> {
+    let tables: BiomeTables = BiomeTables::parse(BUNDLED)?;
+
+    // the commands only start after this, so the tables can't have been built yet
+    let _ = BIOME_TABLES.set(tables);
+    Ok(())
+}
+
+pub fn biome_tables() -> &'static BiomeTables {
+    BIOME_TABLES.get_or_init(|| BiomeTables::parse(BUNDLED).expect("the bundled biome tables are valid"))
+}
+
+#[derive(Debug)]
+pub enum BiomeError {
+    Parse(serde_json::Error),
+    Invalid(String, String),
+}
+
+impl fmt::Display for BiomeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BiomeError::Parse(err) => write!(f, "couldn't parse the biome tables: {}", err),
+            BiomeError::Invalid(version, message) => write!(f, "invalid biome table {}: {}", version, message),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BiomeTableData {
+    version: String,
+    total: u64,
+    biomes: Vec<(String, u64)>,
+    #[serde(default)]
+    estimated: bool,
+}
+
+pub struct BiomeTables {
+    // sorted by version
+    tables: Vec<BiomeTable>,
+}
+
+impl BiomeTables {
+    pub fn parse(json: &str) -> Result<Self, BiomeError> {
+        let data: Vec<BiomeTableData> = serde_json::from_str(json).map_err(BiomeError::Parse)?;
+        let mut tables: Vec<BiomeTable> = data.into_iter().map(BiomeTable::new).collect::<Result<_, _>>()?;
+
+        tables.sort_by_key(|table| table.minor);
+        if tables.windows(2).any(|pair| pair[0].minor == pair[1].minor) {
+            return Err(BiomeError::Invalid(String::new(), "two tables have the same version".to_owned()));
+        }
+        if !tables.iter().any(|table| table.version == DEFAULT_VERSION) {
+            return Err(BiomeError::Invalid(DEFAULT_VERSION.to_owned(), "the default table is missing".to_owned()));
+        }
+
+        Ok(BiomeTables { tables })
+    }
+
+    // The table for a version like "1.17.1" or "1.20", or None before the first table.
+    pub fn for_version(&self, version: &str) -> Option<&BiomeTable> {
+        let minor: u32 = minor_version(version)?;
+
+        self.tables.iter().rev().find(|table| table.minor <= minor)
+    }
+
+    pub fn default_table(&self) -> &BiomeTable {
+        self.for_version(DEFAULT_VERSION).expect("the default table was checked when parsing")
+    }
+}
+
+pub struct BiomeTable {
+    pub version: String,
+    // the weights weren't counted from real worlds
+    pub estimated: bool,
+    minor: u32,
+    names: Vec<String>,
+    // cumulative[i] is the weight of the first i + 1 biomes, so a roll is a binary search
+    cumulative: Vec<u64>,
+}
+
+impl BiomeTable {
+    fn new(data: BiomeTableData) -> Result<Self, BiomeError> {
+        let invalid = |message: String| BiomeError::Invalid(data.version.clone(), message);
+        let minor: u32 = minor_version(&data.version).ok_or_else(|| invalid("the version must look like 1.16".to_owned()))?;
+
+        let mut names: Vec<String> = Vec::new();
+        let mut cumulative: Vec<u64> = Vec::new();
+        let mut total: u64 = 0;
+        for (name, weight) in &data.biomes {
+            if *weight == 0 {
+                return Err(invalid(format!("{} has no weight", name)));
+            }
+            if names.iter().any(|other| same_name(other, name)) {
+                return Err(invalid(format!("{} is listed twice", name)));
+            }
+
+            total = total.checked_add(*weight).ok_or_else(|| invalid("the weights add up to more than a u64".to_owned()))?;
+            names.push(name.to_owned());
+            cumulative.push(total);
+        }
+
+        if total != data.total {
+            return Err(invalid(format!("the weights add up to {}, not {}", total, data.total)));
+        }
+
+        Ok(BiomeTable { version: data.version, estimated: data.estimated, minor, names, cumulative })
+    }
+
+    pub fn total(&self) -> u64 {
+        *self.cumulative.last().unwrap_or(&0)
+    }
+
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> &str {
+        let n: u64 = rng.gen_range(1..=self.total());
+
+        &self.names[self.cumulative.partition_point(|weight| *weight < n)]
+    }
+
+    // The biome's name as the table writes it and the odds of rolling it. Names ignore case and
+    // can use underscores, like "deep_ocean".
+    pub fn odds(&self, name: &str) -> Option<(&str, f64)> {
+        let index: usize = self.names.iter().position(|other| same_name(other, name))?;
+        let weight: u64 = self.cumulative[index] - if index == 0 { 0 } else { self.cumulative[index - 1] };
+
+        Some((&self.names[index], weight as f64 / self.total() as f64))
+    }
+}
+
+fn same_name(name: &str, other: &str) -> bool {
+    name.to_lowercase().replace('_', " ") == other.to_lowercase().replace('_', " ")
+}
+
+fn minor_version(version: &str) -> Option<u32> {
+    let mut parts = version.split('.');
+
+    if parts.next()? != "1" { return None; }
+    let minor: u32 = parts.next()?.parse().ok()?;
+    if let Some(patch) = parts.next() {
+        patch.parse::<u32>().ok()?;
+    }
+    if parts.next().is_some() { return None; }
+
+    Some(minor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    #[test]
+    fn versions_pick_their_biome_set() {
+        let tables: &BiomeTables = biome_tables();
+
+        assert_eq!(tables.default_table().total(), 443_808_771_309);
+        assert_eq!(tables.for_version("1.17.1").unwrap().version, "1.16");
+        assert_eq!(tables.for_version("1.20.5").unwrap().version, "1.18");
+        assert!(tables.for_version("1.12").is_none());
+        assert!(tables.for_version("1.18").unwrap().odds("Mountains").is_none());
+        assert!(tables.for_version("1.18").unwrap().estimated);
+        assert!(!tables.default_table().estimated);
+        assert_eq!(tables.default_table().odds("modified_jungle_edge").map(|(name, _)| name), Some("Modified Jungle Edge"));
+    }
+
+    #[test]
+    fn rolls_follow_the_weights() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let table: &BiomeTable = biome_tables().default_table();
+        let rolls: u32 = 20_000;
+
+        let plains: f64 = (0..rolls).filter(|_| table.roll(&mut rng) == "Plains").count() as f64;
+        let expected: f64 = table.odds("Plains").unwrap().1 * rolls as f64;
+        assert!((plains - expected).abs() < 5.0 * expected.sqrt(), "{} vs {}", plains, expected);
+    }
+
+    #[test]
+    fn totals_are_checked() {
+        let wrong_total: &str = r#"[{"version": "1.16", "total": 10, "biomes": [["Plains", 4], ["Forest", 5]]}]"#;
+        let twice: &str = r#"[{"version": "1.16", "total": 9, "biomes": [["Plains", 4], ["plains", 5]]}]"#;
+
+        assert!(matches!(BiomeTables::parse(wrong_total), Err(BiomeError::Invalid(_, message)) if message == "the weights add up to 9, not 10"));
+        assert!(BiomeTables::parse(twice).is_err());
+        assert!(BiomeTables::parse(r#"[{"version": "1.16", "total": 9, "biomes": [["Plains", 4], ["Forest", 5]]}]"#).is_ok());
+    }
+}
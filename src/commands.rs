@@ -1,341 +1,463 @@
-use rand::distributions::{Uniform, Distribution};
-use rand::{Rng, rngs::StdRng, SeedableRng};
+use rand::Rng;
 use rspotify::model::{PlayableItem, AdditionalType};
 use rspotify::{prelude::*, AuthCodeSpotify};
-use sqlite::{Connection, State};
-use std::collections::HashMap;
+use sqlite::Connection;
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
+use serde_json::json;
 use std::time::{SystemTime, UNIX_EPOCH};
 // use rand_xoshiro::rand_core::;
 use rand_xoshiro::Xoroshiro128PlusPlus;
 
+use crate::args::{NumberArg, parse_weather_model, reject_unknown_options, take_option};
+use crate::rng::Roll;
+use crate::registry::{Command, CommandContext, CommandError, CommandFuture, CommandRegistry, CommandResult, Handler, SimpleCommand};
 use crate::thunder::{self, format_start_time};
-use crate::math::bernoullis_scheme;
-use crate::phantoms::get_phantoms_spawn_time;
+use crate::thunder_stats;
+use crate::weather::WeatherModel;
+use crate::{check_raid_file, RAID_FILE_DEFAULT_VALUE};
+use crate::permissions::PermissionLevel;
+use crate::database;
+use crate::mob_drops::{self, DrownedDrops};
+use crate::loot::{self, loot_tables, ItemStack, LootContext};
+use crate::fishing::{self, Catch, CatchCategory, FishingRod};
+use crate::math::{bernoullis_scheme, binomial_tail};
+use crate::phantoms::{self, Difficulty, PhantomModel};
+use crate::bartering::{self, Barters};
+use crate::aassg::{self, Run, RunOdds};
+use crate::eyes;
+use crate::biomes::{self, BiomeTable};
+use crate::leaderboards::{Leaderboard, Metric, Source, Window};
+
+// Limits for the numeric command arguments. They keep every command answering within the
+// simulation timeout and every count inside the integer types the simulations use.
+const LOOTING_LEVEL: NumberArg<u32> = NumberArg::new("looting level", 0, 3);
+const THUNDER_MINUTES: NumberArg<f64> = NumberArg::new("time in minutes", 0.0, 10_000.0);
+const THUNDER_PERCENTILE: NumberArg<f64> = NumberArg::new("percentile", 0.0, 99.99);
+const SKULL_KILLS: NumberArg<u128> = NumberArg::new("kills", 0, 10_000);
+const TRIDENT_DURABILITY: NumberArg<u32> = NumberArg::new("durability", 0, 250);
+const DROWNED_KILLS: NumberArg<u32> = NumberArg::new("drowned", 0, 1_000_000_000);
+const CATS: NumberArg<u32> = NumberArg::new("cats number", 0, 1_000_000_000);
+const BLAZE_RODS: NumberArg<u32> = NumberArg::new("rods", 0, 1_000_000_000);
+const SKULLS: NumberArg<u32> = NumberArg::new("skulls", 0, 1_000_000_000);
+const LOOT_ROLLS: NumberArg<u32> = NumberArg::new("rolls", 1, 1_000_000_000);
+const CASTS: NumberArg<u32> = NumberArg::new("casts", 1, 10_000);
+const LUCK_OF_THE_SEA: NumberArg<u32> = NumberArg::new("luck of the sea level", 0, 3);
+const LURE: NumberArg<u32> = NumberArg::new("lure level", 0, 3);
+const GOLD_INGOTS: NumberArg<u64> = NumberArg::new("gold ingots", 1, 1_000_000_000);
+const AASSG_RUNS: NumberArg<u64> = NumberArg::new("runs", 1, 10_000);
+const EYES: NumberArg<u32> = NumberArg::new("eyes", 0, eyes::FRAMES);
+const PHANTOM_MINUTES: NumberArg<f64> = NumberArg::new("time in minutes", 0.0, 10_000.0);
+const LOCAL_DIFFICULTY: NumberArg<f64> = NumberArg::new("local", 0.0, 6.75);
+const MOON_PHASE: NumberArg<u64> = NumberArg::new("moon", 0, 7);
+const MINUTES_SINCE_REST: NumberArg<u64> = NumberArg::new("rest", 0, 100_000);
+const PHANTOM_GROUP: NumberArg<u64> = NumberArg::new("group", 1, 4);
+const SKY_EXPOSURE: NumberArg<f64> = NumberArg::new("sky", 0.0, 100.0);
+
+// Roll commands where the lower score is the luckier roll, like fewer chests for the Silence Trim.
+pub const LOWER_SCORES_WIN: &[&str] = &["!weather", "!rollblazerods", "!rollskulls", "!rollphantoms", "!rollaassg", "!rollsilence", "!rollheavycore"];
+const RECENT_ROLLS: i64 = 5;
+
+pub fn register_commands(registry: &mut CommandRegistry) {
+    registry.register(Combo);
+    registry.register(SimpleCommand::new("!nomic", Handler::Static(nomic)));
+    registry.register(SimpleCommand::new("!rolltrident", Handler::Roll(rolltrident)));
+    registry.register(SimpleCommand::new("!age", Handler::Roll(age)));
+    registry.register(SimpleCommand::new("!rollseed", Handler::Roll(rollseed)));
+    registry.register(SimpleCommand::new("!findseed", Handler::DatabaseUserRoll(findseed)));
+    registry.register(SimpleCommand::new("!eyeodds", Handler::Args(eyeodds)).usage("!eyeodds {eyes}"));
+    registry.register(Leaderboard::new("!findseedjuicers", "best findseed portals", Source::Rolls("!findseed"), Metric::Best).format(" - ", " eye").usage("!findseedjuicers [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(SimpleCommand::new("!weather", Handler::Roll(weather)).usage("!weather [version]"));
+    registry.register(SimpleCommand::new("!thunderodds", Handler::Simulation(thunderodds)).usage("!thunderodds {time in minutes} [version]"));
+    registry.register(SimpleCommand::new("!thundertime", Handler::Args(thundertime)).usage("!thundertime [percentile] [version]"));
+    registry.register(SimpleCommand::new("!thunderduration", Handler::Args(thunderduration)).usage("!thunderduration [time in minutes] [version]"));
+    registry.register(SimpleCommand::new("!thunderwindow", Handler::Simulation(thunderwindow)).usage("!thunderwindow {start in minutes} {end in minutes} [version]"));
+    registry.register(SimpleCommand::new("!skullodds", Handler::Simulation(skullodds)).usage("!skullodds {drops} {kills} {looting level}"));
+    registry.register(SimpleCommand::new("!tridentodds", Handler::Args(tridentodds)).usage("!tridentodds {durability}"));
+    registry.register(SimpleCommand::new("!rolldrowned", Handler::RollSimulation(rolldrowned)).usage("!rolldrowned {drowned} {looting level}"));
+    // chat fished with the Fishinge emote before there was a !fish
+    registry.register(SimpleCommand::new("!fish", Handler::DatabaseUserRoll(fish)).aliases(&["!fishinge", "fishinge"]).usage("!fish [casts] [luck of the sea level] [lure level] [open/closed water]"));
+    registry.register(Leaderboard::new("!fishjuicers", "chatters with the most {} caught", Source::Catches("treasure"), Metric::Sum).usage("!fishjuicers [fish/treasure/junk] [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(SimpleCommand::new("!song", Handler::Spotify(|spotify| Box::pin(song(spotify)))));
+    registry.register(SimpleCommand::new("!wr", Handler::Static(wr)));
+    registry.register(SimpleCommand::new("!pb", Handler::Static(pb)));
+    registry.register(SimpleCommand::new("!myrolls", Handler::DatabaseUserArgs(myrolls)).usage("!myrolls {command}"));
+    registry.register(SimpleCommand::new("!stats", Handler::DatabaseUserArgs(stats)).usage("!stats [user]"));
+    registry.register(Leaderboard::new("!topcommands", "most used commands", Source::Commands, Metric::Sum).format(": ", " uses").usage("!topcommands [today/week/month/all/stream] [size]"));
+    registry.register(Leaderboard::new("!topchatters", "chatters", Source::Messages, Metric::Sum).format(": ", " messages").usage("!topchatters [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(Leaderboard::new("!topspammers", "command spammers", Source::CommandUses, Metric::Sum).format(": ", " command uses").usage("!topspammers [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(SimpleCommand::new("!rollgp", Handler::Roll(rollgp)));
+    registry.register(SimpleCommand::new("!rollbiome", Handler::Roll(rollbiome)).usage("!rollbiome [version]"));
+    registry.register(SimpleCommand::new("!biomeodds", Handler::Args(biomeodds)).usage("!biomeodds {biome} [version]"));
+    registry.register(SimpleCommand::new("!commands", Handler::Registry(commands)).aliases(&["!help"]));
+    registry.register(SimpleCommand::new("!rollcats", Handler::RollSimulation(rollcats)).usage("!rollcats {cats number}"));
+    registry.register(SimpleCommand::new("!rollblazerods", Handler::RollSimulation(rollblazerods)).usage("!rollblazerods {rods} {looting level}"));
+    registry.register(Leaderboard::new("!tridentjuicers", "best trident rolls", Source::Rolls("!rolltrident"), Metric::Best).usage("!tridentjuicers [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(Leaderboard::new("!gpjuicers", "best desert temple gunpowder rolls", Source::Rolls("!rollgp"), Metric::Best).usage("!gpjuicers [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(Leaderboard::new("!dailytridentjuicers", "best trident rolls", Source::Rolls("!rolltrident"), Metric::Best).window(Window::LastDay).usage("!dailytridentjuicers [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(Leaderboard::new("!gpnoobs", "worst desert temple gunpowder rolls", Source::Rolls("!rollgp"), Metric::Worst).usage("!gpnoobs [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(Leaderboard::new("!tridentnoobs", "chatters with most 0 durability trident rolls", Source::Rolls("!rolltrident"), Metric::CountOf(0.0)).usage("!tridentnoobs [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(SimpleCommand::new("!rollloot", Handler::RollSimulation(rollloot)).usage("!rollloot {table} [rolls] [looting level]"));
+    registry.register(SimpleCommand::new("!rollskulls", Handler::RollSimulation(rollskulls)).usage("!rollskulls {skulls} {looting level}"));
+    registry.register(Leaderboard::new("!commandstats", "users with most {} uses", Source::UsesOf(String::new()), Metric::Sum).format(": ", " uses").total().usage("!commandstats {command name} [today/week/month/all/stream] [size] [me/@user]"));
+    registry.register(SimpleCommand::new("!raid", Handler::RaidFile(raid)));
+    registry.register(SimpleCommand::new("!setraid", Handler::RaidFileArgs(setraid)).usage("!setraid {raid message}").permission(PermissionLevel::Moderator));
+    registry.register(SimpleCommand::new("!resetraid", Handler::RaidFile(resetraid)).permission(PermissionLevel::Moderator));
+    registry.register(SimpleCommand::new("!rollphantoms", Handler::Roll(rollphantoms)).usage("!rollphantoms [difficulty=hard] [local=difficulty] [moon=0-7] [rest=minutes] [group=2] [sky=100]"));
+    registry.register(SimpleCommand::new("!phantomodds", Handler::Simulation(phantomodds)).usage("!phantomodds {time in minutes} [difficulty=hard] [local=difficulty] [moon=0-7] [rest=minutes] [group=2] [sky=100]"));
+    registry.register(SimpleCommand::new("!rollaassg", Handler::Roll(rollaassg)));
+    registry.register(SimpleCommand::new("!aassgodds", Handler::RollSimulation(aassgodds)).usage("!aassgodds [runs]"));
+    registry.register(SimpleCommand::new("!rollbarters", Handler::RollSimulation(rollbarters)).usage("!rollbarters {gold ingots}"));
+    registry.register(SimpleCommand::new("!route", Handler::Static(route)));
+    registry.register(SimpleCommand::new("!rollsilence", Handler::Roll(rollsilence)));
+    registry.register(SimpleCommand::new("!hdwghfix", Handler::Static(hdwghfix)));
+    registry.register(SimpleCommand::new("!caamel", Handler::Static(caamel)));
+    registry.register(SimpleCommand::new("!rollheavycore", Handler::Roll(rollheavycore)));
+    registry.register(SimpleCommand::new("!seed", Handler::Static(seed)));
+    registry.register(SimpleCommand::new("!lootingseedroute", Handler::Static(lootingseedroute)));
+}
+
+// !combo runs every following word of the message as a command.
+pub struct Combo;
+
+impl Command for Combo {
+    fn name(&self) -> &'static str {
+        "!combo"
+    }
+
+    fn chains(&self) -> bool {
+        true
+    }
 
-pub fn nomic() -> Result<String, String> {
+    fn execute<'a>(&'a self, _context: &'a CommandContext<'a>) -> CommandFuture<'a> {
+        Box::pin(std::future::ready(Ok(String::new())))
+    }
+}
+
+pub fn nomic() -> CommandResult {
     Ok("No Microphone.".to_owned())
 }
 
-pub fn rolltrident(sqlite_connection: &Connection, user_id: &str) -> Result<(String, u32), String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+pub fn rolltrident(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
 
     let n: u32 = rng.gen_range(0..=250);
     let durability: u32 = rng.gen_range(0..=n);
-
-    // add data to the database
-    let unix_time: u128 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-    let trident_query: &str = &format!("INSERT INTO trident_rolls (durability, unix_time, user_id) VALUES ({}, {}, {});", durability, unix_time, user_id);
-    let query_result = sqlite_connection.execute(trident_query);
-
-    if let Err(err) = query_result {
-        println!("Trident durability database error: {}", err);
-    }
+    roll.record(Some(durability as f64), json!({ "durability": durability }));
 
     if durability == 0 || durability == 1 {
         // let duration: u32 = if durability == 0 { 300 } else { 600 }; 
 
         // twitch_api::ban(user_id, "Your trident roll sucks.", duration);
-        return Ok((format!("Your trident has {} durability LULW !", durability), durability))
+        Ok(format!("Your trident has {} durability LULW !", durability))
     } else {
-        return Ok((format!("Your trident has {} durability.", durability), durability))
+        Ok(format!("Your trident has {} durability.", durability))
     }
 }
 
-pub fn age() -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+pub fn age(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
 
     let age: i32 = rng.gen_range(0..=100);
+    roll.record(Some(age as f64), json!({ "age": age }));
     
     Ok(format!("Oskar is {} years old.", age))
 }
 
-pub fn rollseed() -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+pub fn rollseed(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
 
     let seed: i64 = rng.gen();
+    roll.record(None, json!({ "seed": seed }));
     
     Ok(format!("Your seed: {}.", seed))
 }
 
-pub fn findseed() -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+pub fn findseed(sqlite_connection: &Connection, channel: &str, user_id: &str, roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let eyes: u32 = eyes::roll_eyes(&mut roll.rng);
+    roll.record(Some(eyes as f64), json!({ "eyes": eyes }));
 
-    let v: Vec<i32> = vec!(0; 12);
-    let rolls: Vec<i32> = v.iter().map(|n| (*n == rng.gen_range(0..10)) as i32).collect::<Vec<i32>>(); 
-    let eyes: i32 = rolls.iter().sum::<i32>();
-    
-    Ok(format!("Your seed is a {} eye.", eyes))
+    if roll.replay {
+        return Ok(format!("Your seed is a {} eye.", eyes));
+    }
+
+    // this roll is only saved once the command has replied, so it isn't in the best yet
+    let best: Option<i64> = match database::best_and_worst_roll(sqlite_connection, channel, user_id, "!findseed") {
+        Ok((best, _)) => best,
+        Err(err) => {
+            println!("Findseed database error: {}", err);
+            None
+        }
+    };
+
+    match best {
+        Some(best) if eyes as i64 > best => Ok(format!("Your seed is a {} eye, your new best!", eyes)),
+        _ => Ok(format!("Your seed is a {} eye.", eyes)),
+    }
 }
 
-pub fn weather() -> Result<String, String> {
-    let (thunder_start, thunder_duration) = thunder::get_first_thunder();
+pub fn eyeodds(message_parts: Vec<&str>) -> CommandResult {
+    let eyes: u32 = EYES.parse(&message_parts, 1)?;
+
+    Ok(format!(
+        "Odds of a {} eye portal: ~{:.8}%; Odds of {} or more eyes: ~{:.8}%",
+        eyes,
+        eyes::eye_odds(eyes) * 100.0,
+        eyes,
+        eyes::eyes_or_more_odds(eyes) * 100.0
+    ).replace(".", ","))
+}
+
+pub fn weather(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let model: WeatherModel = parse_weather_model(&message_parts, 1)?;
+
+    let (thunder_start, thunder_duration) = thunder::get_first_thunder(model, &mut roll.rng);
+    // scored by how many minutes the thunder takes to start
+    roll.record(Some(thunder_start as f64 / 1200.0), json!({ "start": thunder_start, "duration": thunder_duration, "version": format!("{:?}", model) }));
     let formatted_start_time: String = thunder::format_start_time(thunder_start);
     let formatted_duration: String = thunder::format_duration(thunder_duration);
     
     Ok(format!("First thunder will start at {} and will last {}.", formatted_start_time, formatted_duration))
 }
 
-pub fn thunderodds(message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !thunderodds {time in minutes}".to_owned());
+pub fn thunderodds(message_parts: Vec<&str>) -> CommandResult {
+    let mins: f64 = THUNDER_MINUTES.parse(&message_parts, 1)?;
+    let model: WeatherModel = parse_weather_model(&message_parts, 2)?;
 
-    if message_parts.len() <= 0 {   
-        return error;
-    }
+    let odds: f64 = thunder::get_thunder_odds(model, (mins * 1200.0) as u64);
+    Ok(format!("Odds of thunder in first {} minutes: ~{:.4}%", mins, odds * 100.0).replace(".", ","))
+}
 
-    let arg = message_parts[1].parse::<f64>();
+// True when the optional number of a command is left out and its first argument is the version.
+fn version_first(message_parts: &[&str]) -> bool {
+    message_parts.get(1).is_none_or(|arg| WeatherModel::from_version(arg).is_some())
+}
 
-    match arg {
-        Ok(mins) => {
-            let odds: f64 = thunder::get_thunder_odds((mins * 1200.0) as u64);
-            return Ok(format!("Odds of thunder in first {} minutes: ~{:.4}%", mins, odds * 100.0).replace(".", ","));
-        },
-        Err(_) => {
-            return error;
-        }
+pub fn thundertime(message_parts: Vec<&str>) -> CommandResult {
+    if version_first(&message_parts) {
+        let model: WeatherModel = parse_weather_model(&message_parts, 1)?;
+        let median: String = format_start_time(thunder_stats::first_thunder_percentile(model, 50.0));
+        let ninetieth: String = format_start_time(thunder_stats::first_thunder_percentile(model, 90.0));
+
+        return Ok(format!("Half of all worlds get their first thunder by {}, 90% by {}.", median, ninetieth));
     }
+
+    let percentile: f64 = THUNDER_PERCENTILE.parse(&message_parts, 1)?;
+    let model: WeatherModel = parse_weather_model(&message_parts, 2)?;
+    let time: String = format_start_time(thunder_stats::first_thunder_percentile(model, percentile));
+
+    Ok(format!("{}% of worlds get their first thunder by {}.", percentile, time))
 }
 
-pub fn skullodds(message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !skullodds {drops} {kills} {looting level}".to_owned());
-    
-    if message_parts.len() <= 2 {
-        return error;
+pub fn thunderduration(message_parts: Vec<&str>) -> CommandResult {
+    if version_first(&message_parts) {
+        let model: WeatherModel = parse_weather_model(&message_parts, 1)?;
+        // rounded to whole seconds
+        let expected: u64 = (thunder_stats::expected_first_thunder_duration(model) / 20.0).round() as u64 * 20;
+
+        return Ok(format!("The first thunder lasts {} on average.", thunder::format_duration(expected).trim()));
     }
-    
-    let drops = message_parts[1].parse::<u128>();
-    let kills = message_parts[2].parse::<u128>();
-    let looting_level = message_parts[3].parse::<u32>();
-
-    match (kills, drops, looting_level) {
-        (Ok(kills), Ok(drops), Ok(looting_level)) => {
-            let p: f64 = (looting_level as f64) / 100.0 + 0.025; 
-            if p < 0.0 || p > 1.0 || drops > kills || looting_level > 3 {
-                return error;
-            }
 
-            // println!("{}",p);
-            
-            let mut exact_or_more_drops_probability: f64 = 0.0;
-            for n in drops..=kills { 
-                exact_or_more_drops_probability += bernoullis_scheme(kills, n, p);
-            }
+    let mins: f64 = THUNDER_MINUTES.parse(&message_parts, 1)?;
+    let model: WeatherModel = parse_weather_model(&message_parts, 2)?;
 
-            let exact_drops_probability: f64 = bernoullis_scheme(kills, drops, p);
-
-            return Ok(format!(
-                "Wither skeleton kills: {}; Looting level: {}; Odds of getting exactly {} skull drops: ~{:.8}%; Odds of getting {} or more skull drops: ~{:.8}%",
-                kills,
-                looting_level,
-                drops,
-                exact_drops_probability * 100.0,
-                drops,
-                exact_or_more_drops_probability * 100.0
-            ).replace(".", ","));
-        },
-        _ => {
-            return error;
-        }
-    };
+    let odds: f64 = thunder_stats::first_thunder_lasts_at_least(model, (mins * 1200.0) as u64);
+    Ok(format!("Odds of the first thunder lasting at least {} minutes: ~{:.4}%", mins, odds * 100.0).replace(".", ","))
 }
 
-pub fn tridentodds(message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !tridentodds {durability}".to_owned());
-    
-    if message_parts.len() <= 1 {
-        return error;
-    }
+pub fn thunderwindow(message_parts: Vec<&str>) -> CommandResult {
+    let start: f64 = THUNDER_MINUTES.parse(&message_parts, 1)?;
+    let end: f64 = NumberArg::new("end in minutes", start, THUNDER_MINUTES.max).parse(&message_parts, 2)?;
+    let model: WeatherModel = parse_weather_model(&message_parts, 3)?;
 
-    let durability = message_parts[1].parse::<u32>();
+    let odds: f64 = thunder_stats::thunder_odds_between(model, (start * 1200.0) as u64, (end * 1200.0) as u64);
+    Ok(format!("Odds of thunder between minute {} and {}: ~{:.4}%", start, end, odds * 100.0).replace(".", ","))
+}
 
-    match durability {
-        Ok(durability) => {
-            if durability > 250 {
-                return error;
-            }   
+pub fn skullodds(message_parts: Vec<&str>) -> CommandResult {
+    let kills: u128 = SKULL_KILLS.parse(&message_parts, 2)?;
+    let drops: u128 = NumberArg::new("drops", 0, kills).parse(&message_parts, 1)?;
+    let looting_level: u32 = LOOTING_LEVEL.parse(&message_parts, 3)?;
+
+    let p: f64 = (looting_level as f64) / 100.0 + 0.025;
+
+    let exact_or_more_drops_probability: f64 = binomial_tail(kills, drops, p);
+    let exact_drops_probability: f64 = bernoullis_scheme(kills, drops, p);
+
+    Ok(format!(
+        "Wither skeleton kills: {}; Looting level: {}; Odds of getting exactly {} skull drops: ~{:.8}%; Odds of getting {} or more skull drops: ~{:.8}%",
+        kills,
+        looting_level,
+        drops,
+        exact_drops_probability * 100.0,
+        drops,
+        exact_or_more_drops_probability * 100.0
+    ).replace(".", ","))
+}
 
-            let mut exact_durability_odds: f64 = 0.0;
-            let mut exact_or_more_durability_odds: f64 = 0.0;
+pub fn tridentodds(message_parts: Vec<&str>) -> CommandResult {
+    let durability: u32 = TRIDENT_DURABILITY.parse(&message_parts, 1)?;
 
-            for k in durability..=250 {
-                for n in k..=250 {
-                    if k == durability {
-                        exact_durability_odds += 1.0 / (251.0 * (n + 1) as f64);
-                    }
+    let mut exact_durability_odds: f64 = 0.0;
+    let mut exact_or_more_durability_odds: f64 = 0.0;
 
-                    exact_or_more_durability_odds += 1.0 / (251.0 * (n + 1) as f64);
-                }
+    for k in durability..=250 {
+        for n in k..=250 {
+            if k == durability {
+                exact_durability_odds += 1.0 / (251.0 * (n + 1) as f64);
             }
-            
-            let message: String = if durability == 250 {
-                format!(
-                    "Odds of getting {} durability trident: {:.8}%.", 
-                    durability, 
-                    exact_durability_odds * 100.0, 
-                )
-            } else {
-                format!(
-                    "Odds of getting exactly {} durability trident: ~{:.8}%; Odds of getting {} or more durability trident: ~{:.8}%", 
-                    durability, 
-                    exact_durability_odds * 100.0, 
-                    durability, 
-                    exact_or_more_durability_odds * 100.0
-                ).replace(".", ",")
-            };
-
-                
-            return Ok(message);
-        },
-        Err(_) => {
-            return error;
-        },
+
+            exact_or_more_durability_odds += 1.0 / (251.0 * (n + 1) as f64);
+        }
     }
+    
+    let message: String = if durability == 250 {
+        format!(
+            "Odds of getting {} durability trident: {:.8}%.", 
+            durability, 
+            exact_durability_odds * 100.0, 
+        )
+    } else {
+        format!(
+            "Odds of getting exactly {} durability trident: ~{:.8}%; Odds of getting {} or more durability trident: ~{:.8}%", 
+            durability, 
+            exact_durability_odds * 100.0, 
+            durability, 
+            exact_or_more_durability_odds * 100.0
+        ).replace(".", ",")
+    };
+
+    Ok(message)
 }
 
-pub fn rolldrowned(message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !rolldrowned {drowned} {looting level}".to_owned());
+pub fn rolldrowned(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let kills: u32 = DROWNED_KILLS.parse(&message_parts, 1)?;
+    let looting_level: u32 = LOOTING_LEVEL.parse(&message_parts, 2)?;
+
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
+    let drops: DrownedDrops = mob_drops::roll_drowned(kills as u64, looting_level, rng);
+    roll.record(Some(drops.tridents as f64), json!({
+        "kills": kills,
+        "looting_level": looting_level,
+        "rotten_flesh": drops.rotten_flesh,
+        "copper_ingots": drops.copper_ingots,
+        "nautilus_shells": drops.nautilus_shells,
+        "tridents": drops.tridents,
+        "fishing_rods": drops.fishing_rods,
+    }));
+
+    Ok(format!(
+        "You got {} Rotten Flesh, {} Copper Ingots, {} Nautilus Shells, {} Tridents, {} Fishing Rods from killing {} drowned with looting {}.",
+        drops.rotten_flesh,
+        drops.copper_ingots,
+        drops.nautilus_shells,
+        drops.tridents,
+        drops.fishing_rods,
+        kills,
+        looting_level
+    ))
+}
 
-    if message_parts.len() <= 2 {
-        return error;
-    }
+pub fn fish(sqlite_connection: &Connection, channel: &str, user_id: &str, roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let casts: u32 = if message_parts.len() > 1 { CASTS.parse(&message_parts, 1)? } else { 1 };
+    let luck_of_the_sea: u32 = if message_parts.len() > 2 { LUCK_OF_THE_SEA.parse(&message_parts, 2)? } else { 0 };
+    let lure: u32 = if message_parts.len() > 3 { LURE.parse(&message_parts, 3)? } else { 0 };
+    let open_water: bool = match message_parts.get(4).map(|water| water.to_lowercase()).as_deref() {
+        None | Some("open") => true,
+        Some("closed") => false,
+        Some(_) => return Err(CommandError::Message("Error: water must be open or closed.".to_owned())),
+    };
 
-    let mut rng = Xoroshiro128PlusPlus::from_entropy();
-    // rng::x
+    let rod: FishingRod = FishingRod { luck_of_the_sea, lure, open_water };
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
+    let catches: Vec<Catch> = (0..casts).map(|_| fishing::cast(&rod, rng)).collect();
 
-    let kills = message_parts[1].parse::<u32>();
-    let looting_level = message_parts[2].parse::<u32>();
+    // add data to the database, replays don't count
+    let mut caught: Vec<(String, &str, u64)> = Vec::new();
+    for catch in &catches {
+        match caught.iter_mut().find(|(item, category, _)| *item == catch.stack.item && *category == catch.category.name()) {
+            Some((_, _, count)) => *count += catch.stack.count,
+            None => caught.push((catch.stack.item.to_owned(), catch.category.name(), catch.stack.count)),
+        }
+    }
 
-    match (kills, looting_level) {
-        (Ok(kills), Ok(looting_level)) => {
-            if looting_level > 3 {
-                return error;
-            }
+    let treasure_caught: usize = catches.iter().filter(|catch| catch.category == CatchCategory::Treasure).count();
+    let items: serde_json::Map<String, serde_json::Value> = caught.iter().map(|(item, _, count)| (item.to_owned(), json!(count))).collect();
+    roll.record(Some(treasure_caught as f64), json!({ "casts": casts, "luck_of_the_sea": luck_of_the_sea, "lure": lure, "open_water": open_water, "items": items }));
 
-            let mut rotten_flesh: u32 = 0;
-            let mut tridents: u32 = 0;
-            let mut shells: u32 = 0;
-            let mut fishing_rods: u32 = 0;
-            let mut copper_ingots: u32 = 0;
-
-            let rotten_flesh_range = Uniform::from(0..(2 + looting_level));
-            let one_to_hundred_range = Uniform::from(1..=100);
-            let drowned_first_roll_range = Uniform::from(1..=10000);
-            let drowned_third_roll_range = Uniform::from(0..16);
-
-            for _ in 0..kills {
-                rotten_flesh += rotten_flesh_range.sample(&mut rng);
-                copper_ingots += if one_to_hundred_range.sample(&mut rng) <= (11 + looting_level) {
-                    1
-                } else {
-                    0
-                };
-
-                if drowned_first_roll_range.sample(&mut rng) <= 85 + looting_level * 10  {
-                    if drowned_third_roll_range.sample(&mut rng) < 10 {
-                        tridents += 1;
-                    } else {
-                        fishing_rods += 1;
-                    }
-                }
-                    
-                if one_to_hundred_range.sample(&mut rng) <= 3 {
-                    shells += 1;
-                }
-            }
+    let unix_time: i64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
 
-            return Ok(format!(
-                "You got {} Rotten Flesh, {} Copper Ingots, {} Nautilus Shells, {} Tridents, {} Fishing Rods from killing {} drowned with looting {}.",
-                rotten_flesh,
-                copper_ingots,
-                shells,
-                tridents,
-                fishing_rods,
-                kills,
-                looting_level
-            ));
-        },
-        _ => {
-            return error;
+    if !roll.replay {
+        if let Err(err) = database::add_catches(sqlite_connection, channel, user_id, &caught, roll.seed, unix_time) {
+            println!("Fishing database error: {}", err);
         }
     }
-}
-
-pub fn fishinge() -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
 
-    let n: u32 = rng.gen_range(1..=20);
+    let average: String = format!("{:.1}", fishing::expected_time_to_bite(lure) / 20.0).replace(".", ",");
 
-    let message: String = "You caught ".to_owned() + if n <= 17 {
-        let k: u32 = rng.gen_range(1..=100);
+    if let [catch] = catches.as_slice() {
+        let seconds: String = format!("{:.2}", catch.ticks as f64 / 20.0).replace(".", ",");
+        return Ok(format!("You caught {} It bit after {} seconds (~{} on average).", fishing_message(&catch.stack), seconds, average));
+    }
 
-        if k <= 60 {
-            "a Raw Cod! 🐟"
-        } else if k > 60 && k <= 85 {
-            "a Raw Salmon! 🐟"
-        } else if k > 85 && k <= 87 {
-            "a Tropical Fish! 🐠"
-        } else {
-            "a Pufferfish! 🐡"
-        }
-    } else if n == 18 {
-        match rng.gen_range(1..=6) {
-            1 => {
-                "an Enchanted Bow! 🏹"
-            },
-            2 => {
-                "an Enchanted Book! 📖"
-            },
-            3 => {
-                "an Enchanted Fishing Rod! 🎣"
-            },
-            4 => {
-                "a Name Tag! 📛"
-            },
-            5 => {
-                "a Nautilus Shell! 🐚"
-            },
-            6 => {
-                "a Saddle! 🐎"
-            },
-            _ => {
-                "you should never get this."
-            }
+    let [fish, treasure, junk] = CatchCategory::ALL.map(|category| catches.iter().filter(|catch| catch.category == category).count());
+    let total_ticks: u64 = catches.iter().map(|catch| catch.ticks).sum();
+    let mut message: String = format!(
+        "You caught {} fish, {} treasure and {} junk in {} casts, which took {} (~{} seconds per catch on average).",
+        fish,
+        treasure,
+        junk,
+        casts,
+        thunder::format_start_time(total_ticks),
+        average
+    );
+
+    let mut treasures: Vec<(String, u64)> = Vec::new();
+    for catch in catches.iter().filter(|catch| catch.category == CatchCategory::Treasure) {
+        match treasures.iter_mut().find(|(name, _)| *name == catch.stack.name()) {
+            Some((_, count)) => *count += catch.stack.count,
+            None => treasures.push((catch.stack.name(), catch.stack.count)),
         }
-    } else {
-        let k: u32 = rng.gen_range(1..=100);
-
-        if k <= 17 {
-            "a Lily Pad! 🪷"
-        } else if k > 17 && k <= 27 {
-            "a Bowl! 🥣"
-        } else if k > 27 && k <= 29 {
-            "a Fishing Rod! 🎣"
-        } else if k > 29 && k <= 39 {
-            "Leather! 💼"
-        } else if k > 39 && k <= 49 {
-            "Leather Boots! 👢"
-        } else if k > 49 && k <= 59 {
-            "a Rotten Flesh! 🥩"
-        } else if k > 59 && k <= 64 {
-            "a Stick! 🏑"
-        } else if k > 64 && k <= 69 {
-            "a String! 🪀"
-        } else if k > 69 && k <= 79 {
-            "a Water Bottle! 💦"
-        } else if k > 79 && k <= 89 {
-            "a Bone! 🦴"
-        } else if k == 90  {
-            "10 Ink Sac! 🪶"
-        } else  {
-            "a Tripwire Hook! 🪝"
-        }
-    };
+    }
+    if !treasures.is_empty() {
+        let treasures: Vec<String> = treasures.iter().map(|(name, count)| format!("{} {}", count, name)).collect();
+        message += &format!(" Treasure: {}.", treasures.join(", "));
+    }
 
     Ok(message)
-} 
+}
 
-pub async fn song(spotify: AuthCodeSpotify) -> Result<String, String> {
+fn fishing_message(stack: &ItemStack) -> String {
+    let message: &str = match (stack.item.trim_start_matches("minecraft:"), stack.enchanted) {
+        ("cod", _) => "a Raw Cod! 🐟",
+        ("salmon", _) => "a Raw Salmon! 🐟",
+        ("tropical_fish", _) => "a Tropical Fish! 🐠",
+        ("pufferfish", _) => "a Pufferfish! 🐡",
+        ("bow", true) => "an Enchanted Bow! 🏹",
+        ("book", true) => "an Enchanted Book! 📖",
+        ("fishing_rod", true) => "an Enchanted Fishing Rod! 🎣",
+        ("name_tag", _) => "a Name Tag! 📛",
+        ("nautilus_shell", _) => "a Nautilus Shell! 🐚",
+        ("saddle", _) => "a Saddle! 🐎",
+        ("lily_pad", _) => "a Lily Pad! 🪷",
+        ("bowl", _) => "a Bowl! 🥣",
+        ("fishing_rod", false) => "a Fishing Rod! 🎣",
+        ("leather", _) => "Leather! 💼",
+        ("leather_boots", _) => "Leather Boots! 👢",
+        ("rotten_flesh", _) => "a Rotten Flesh! 🥩",
+        ("stick", _) => "a Stick! 🏑",
+        ("string", _) => "a String! 🪀",
+        ("potion", _) => "a Water Bottle! 💦",
+        ("bone", _) => "a Bone! 🦴",
+        ("ink_sac", _) => return format!("{} Ink Sac! 🪶", stack.count),
+        ("tripwire_hook", _) => "a Tripwire Hook! 🪝",
+        _ => return format!("{} {}!", stack.count, stack.name()),
+    };
+
+    message.to_owned()
+}
+
+pub async fn song(spotify: AuthCodeSpotify) -> CommandResult {
     let song_response = spotify.current_playing(None, Some([&AdditionalType::Track])).await;
     let mut message = String::new();
 
@@ -345,598 +467,463 @@ pub async fn song(spotify: AuthCodeSpotify) -> Result<String, String> {
                 Some(playing) => {
                     match playing.item {
                         Some(plyable_item) => {
-                            match plyable_item {
-                                PlayableItem::Track(track) => {
-                                    let artists = track.artists;
-                        
-                                    for (i, artist) in artists.iter().enumerate() {
-                                        if i != artists.len() - 1 {
-                                            message += &format!("{}, ", artist.name);
-                                        } else {
-                                            message += &format!("{} - ", artist.name);
-                                        }
+                            if let PlayableItem::Track(track) = plyable_item {
+                                let artists = track.artists;
+                    
+                                for (i, artist) in artists.iter().enumerate() {
+                                    if i != artists.len() - 1 {
+                                        message += &format!("{}, ", artist.name);
+                                    } else {
+                                        message += &format!("{} - ", artist.name);
                                     }
-                        
-                                    message += &track.name;
-                                },
-                                _ => { }
+                                }
+                    
+                                message += &track.name;
                             }
                         },
                         None => {
-                            return Err("Error: No song is currently playing.".to_owned());
+                            return Err(CommandError::Message("Error: No song is currently playing.".to_owned()));
                         }
                     }
                 },
                 None => {
-                    return Err("Error: No song is currently playing.".to_owned());
+                    return Err(CommandError::Message("Error: No song is currently playing.".to_owned()));
                 }
             }
         },
         Err(err) => {
             println!("Error when getting the song: {:?}", err);
-            return Err("Error: Couldn't get the current song.".to_owned());
+            return Err(CommandError::Message("Error: Couldn't get the current song.".to_owned()));
         }
     }
 
     Ok(message)
 }
 
-pub fn wr() -> Result<String, String> {
+pub fn wr() -> CommandResult {
     // Ok("AARSG: 1.16: I don't care; | 1.17: 6:52:10 by Fudge; | 1.18: 4:22:12 by Leonn; | 1.19: 5:10:06 by Leonn; | 1.20: 6:41:18 by Leonn; | 1.20.5: 5:37 by Feinberg;$AASSG: 1.0-1.6: 24:06 by Schnidi_; | 1.8-1.11: 1:41:09 by Unease; | 1.12: 4:05:07 by MeisterMaki; | 1.13: N/A; | 1.14: N/A; | 1.15: N/A; | 1.16: 1:30:15 by me; (1:22:06 Thunderless by me); | 1.17: 4:06:49 by me; | 1.18: N/A; | 1.19: N/A; | 1.20: 3:58 by me;".to_owned())
     Ok("https://docs.google.com/spreadsheets/u/0/d/107ijqjELTQQ29KW4phUmtvYFTX9-pfHsjb18TKoWACk/htmlview#".to_owned())
 }
 
-pub fn pb() -> Result<String, String> {
+pub fn pb() -> CommandResult {
     Ok("AARSG: 1.12: 4:38 | 1.16: No pb (3:58 thunderless); | 1.20.5: 8:14 | AASSG: 1.16: 1:30:15 (1:22:06 thunderless); | 1.17: 4:06:49 | 1.20: 3:58;".to_owned())
 }
 
-pub fn topcommands(sqlite_connection: &Connection) -> Result<String, String> {
-    let query = "SELECT name, SUM(uses) as total_uses FROM commands GROUP BY name ORDER BY total_uses DESC LIMIT 3;";
-    let statement = sqlite_connection.prepare(query);
-    let mut message: String = "Top 3 most used commands: ".to_owned();
+pub fn rollgp(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
 
-    match statement {
-        Ok(mut statement) => while let Ok(State::Row) = statement.next() {
-            let command_name = statement.read::<String, _>("name").unwrap();
-            let command_uses = statement.read::<i64, _>("total_uses").unwrap();
-        
-            message += &format!("{}: {} uses; ", command_name.replace("emark_", "!"), command_uses);
-        },
-        Err(error) => {
-            println!("Top commands error: {}", error);
-            return Err(format!("Error: {}", error));
-        }
-    }
-    
-    Ok(message)
-}
+    // a desert temple has 4 chests
+    let gunpowder: u32 = (0..4)
+        .flat_map(|_| loot_tables().roll("chests/desert_pyramid", &LootContext::default(), rng))
+        .filter(|stack| stack.item == "minecraft:gunpowder")
+        .map(|stack| stack.count as u32)
+        .sum();
+    roll.record(Some(gunpowder as f64), json!({ "gunpowder": gunpowder }));
 
-pub fn topchatters(sqlite_connection: &Connection) -> Result<String, String> {
-    let query = "SELECT display_name, messages FROM users ORDER BY messages DESC LIMIT 3;";
-    let statement = sqlite_connection.prepare(query);
-    let mut message: String = "Top 3 chatters: ".to_owned();
-
-    match statement {
-        Ok(mut statement) => while let Ok(State::Row) = statement.next() {
-            let name = statement.read::<String, _>("display_name").unwrap();
-            let messages = statement.read::<i64, _>("messages").unwrap();
-        
-            message += &format!("{}: {} messages; ", name, messages);
-        },
-        Err(error) => {
-            println!("Top chatters error: {}", error);
-            return Err(format!("Error: {}", error));
-        }
-    }
-    
-    Ok(message)
+    Ok(format!("You got {} gunpowder!", gunpowder))
 }
 
-pub fn topspammers(sqlite_connection: &Connection) -> Result<String, String> {
-    let query = "SELECT users.display_name as username, SUM(uses) AS total_uses FROM commands INNER JOIN users on commands.user_id = users.user_id GROUP BY commands.user_id ORDER BY total_uses DESC LIMIT 3;";
-    let statement = sqlite_connection.prepare(query);
-    let mut message: String = "Top 3 command spammers: ".to_owned();
-
-    match statement {
-        Ok(mut statement) => while let Ok(State::Row) = statement.next() {
-            let user = statement.read::<String, _>("username").unwrap();
-            let command_uses = statement.read::<i64, _>("total_uses").unwrap();
-        
-            message += &format!("{}: {} command uses; ", user, command_uses);
-        },
-        Err(error) => {
-            println!("Top commands error: {}", error);
-            return Err(format!("Error: {}", error));
-        }
+fn biome_table(version: Option<&str>) -> Result<&'static BiomeTable, CommandError> {
+    match version {
+        None => Ok(biomes::biome_tables().default_table()),
+        Some(version) => biomes::biome_tables().for_version(version).ok_or_else(|| {
+            CommandError::Message("Error: version must be 1.16 or later, like 1.16 or 1.18.2.".to_owned())
+        }),
     }
-    
-    Ok(message)
 }
 
-pub fn rollgp(sqlite_connection: &Connection, user_id: &str) -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
-    let mut gunpowder: u32 = 0;
+pub fn rollbiome(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let table: &BiomeTable = biome_table(message_parts.get(1).copied())?;
+    let biome: &str = table.roll(&mut roll.rng);
 
-    for _ in 1..=16 {
-        if rng.gen_range(1..=50) <= 10 {
-            gunpowder += rng.gen_range(1..=8);
-        }
-    }
+    // scored by rarity, the N in "1 in N"
+    let odds: f64 = table.odds(biome).map_or(1.0, |(_, odds)| odds);
+    roll.record(Some((1.0 / odds).round()), json!({ "biome": biome, "version": table.version }));
 
-    // add data to the database
-    let unix_time: u128 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-    let gunpowder_query: &str = &format!("INSERT INTO gunpowder_rolls (gunpowder, unix_time, user_id) VALUES ({}, {}, {});", gunpowder, unix_time, user_id);
-    let query_result = sqlite_connection.execute(gunpowder_query);
-
-    if let Err(err) = query_result {
-        println!("Gunpowder ammount database error: {}", err);
-    }
+    Ok(format!("You got {}!{}", biome, estimated_note(table)))
+}
 
-    Ok(format!("You got {} gunpowder!", gunpowder))
+fn estimated_note(table: &BiomeTable) -> String {
+    if table.estimated { format!(" (the {} odds are estimates)", table.version) } else { String::new() }
 }
 
-pub fn rollbiome() -> Result<String, String> {
-    // This is a shit way to do this but i'm too lazy to do it in a better way.
-    let biomes = HashMap::from([
-        ("Forest", 38060816951),
-        ("Plains", 39985547347),
-        ("River", 18041713211),
-        ("Ocean", 33794996808),
-        ("Mountains", 29898531283),
-        ("Deep Ocean", 32573867472),
-        ("Swamp", 16124718827),
-        ("Desert", 20897575614),
-        ("Taiga", 18633313544),
-        ("Wooded Hills", 10223682968),
-        ("Lukewarm Ocean", 15745952870),
-        ("Cold Ocean", 14917861632),
-        ("Beach", 14199898280),
-        ("Dark Forest", 11692097469),
-        ("Birch Forest", 11661838126),
-        ("Savanna", 12964991674),
-        ("Wooded Mountains", 7014132060),
-        ("Deep Cold Ocean", 10577186376),
-        ("Deep Lukewarm Ocean", 11617367856),
-        ("Desert Hills", 5810895353),
-        ("Taiga Hills", 4128834830),
-        ("Birch Forest Hills", 3339030439),
-        ("Warm Ocean", 5940180755),
-        ("Jungle", 4460886481),
-        ("Savanna Plateau", 3282841807),
-        ("Snowy Tundra", 8841400745),
-        ("Giant Tree Taiga", 2996994360),
-        ("Sunflower Plains", 2616855572),
-        ("Deep Frozen Ocean", 3627404262),
-        ("Flower Forest", 2055260714),
-        ("Frozen Ocean", 2631260823),
-        ("Jungle Hills", 1550080250),
-        ("Snowy Mountains", 2923917321),
-        ("Stone Shore", 1945888784),
-        ("Giant Tree Taiga Hills", 1342424498),
-        ("Badlands", 1815318568),
-        ("Snowy Taiga", 2682541180),
-        ("Gravelly Mountains", 1353924708),
-        ("Wooded Badlands Plateau", 1256286198),
-        ("Modified Gravelly Mountains", 884083327),
-        ("Desert Lakes", 926615257),
-        ("Jungle Edge", 407387345),
-        ("Taiga Mountains", 809720951),
-        ("Badlands Plateau", 547928247),
-        ("Dark Forest Hills", 560542003),
-        ("Tall Birch Forest", 551946119),
-        ("Swamp Hills", 540238804),
-        ("Snowy Taiga Hills", 614618565),
-        ("Shattered Savanna", 588903346),
-        ("Tall Birch Hills", 415490146),
-        ("Bamboo Jungle", 407153563),
-        ("Shattered Savanna Plateau", 409085452),
-        ("Frozen River", 509199135),
-        ("Snowy Beach", 795354682),
-        ("Bamboo Jungle Hills", 132055350),
-        ("Modified Jungle", 210736272),
-        ("Ice Spikes", 413334512),
-        ("Giant Spruce Taiga Hills", 160486493),
-        ("Giant Spruce Taiga", 159615088),
-        ("Mushroom Fields", 135122523),
-        ("Eroded Badlands", 101993043),
-        ("Mushroom Field Shore", 91394091),
-        ("Modified Wooded Badlands Plateau", 60069217),
-        ("Snowy Taiga Mountains", 118770189),
-        ("Modified Badlands Plateau", 26848829),
-        ("Modified Jungle Edge", 1760744)
-    ]);
-    
-    let mut rng: StdRng = SeedableRng::from_entropy();
-    let n: u64 = rng.gen_range(1..=443_808_771_309);
-    let mut index: u64 = 0;
-
-    for (name, value) in biomes {
-        if n > index && n <= index + value {
-            return Ok(format!("You got {}!", name));
-        } else {
-            index += value;
-        }
+pub fn biomeodds(message_parts: Vec<&str>) -> CommandResult {
+    // the name can be several words, with the version after it
+    let mut words: Vec<&str> = message_parts.into_iter().skip(1).collect();
+    let version: Option<&str> = if words.len() > 1 && words.last().is_some_and(|word| word.starts_with("1.")) { words.pop() } else { None };
+    if words.is_empty() {
+        return Err(CommandError::InvalidSyntax);
     }
 
-    Err("Error: Couldn't find a biome.".to_owned())
+    let table: &BiomeTable = biome_table(version)?;
+    let name: String = words.join(" ");
+    match table.odds(&name) {
+        Some((name, odds)) => {
+            let percent: String = format!("{:.4}", odds * 100.0).replace(".", ",");
+            Ok(format!("Odds of rolling {} in {}: ~{}%{}", name, table.version, percent, estimated_note(table)))
+        },
+        None => Err(CommandError::Message(format!("Error: there's no biome called {} in {}.", name, table.version))),
+    }
 }
 
-pub fn commands() -> Result<String, String> {
-    Ok("Commands file: https://github.com/Oskar-Dev/trident_bot/blob/master/src/commands.rs - good luck with figuring out how all this works.".to_owned())
-}
+pub fn commands(registry: &CommandRegistry) -> CommandResult {
+    let names: Vec<&str> = registry.commands().map(|command| command.name()).collect();
 
-pub fn rollcats(message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !rollcats {cats number}".to_owned());
+    Ok(format!("Commands: {}. Commands file: https://github.com/Oskar-Dev/trident_bot/blob/master/src/commands.rs", names.join(", ")))
+}
 
-    if message_parts.len() <= 1 {
-        return error;
-    }
-
-    let mut rng: StdRng = SeedableRng::from_entropy();
-    let cats = message_parts[1].parse::<u32>();
-
-    let mut jellie: u32 = 0;
-    let mut calico: u32 = 0;
-    let mut red: u32 = 0;
-    let mut tuxedo: u32 = 0;
-    let mut white: u32 = 0;
-    let mut ragdoll: u32 = 0;
-    let mut british: u32 = 0;
-    let mut tabby: u32 = 0;
-    let mut persian: u32 = 0;
-    let mut siamese: u32 = 0;
-
-    match cats {
-        Ok(rolls) => {
-            for _ in 0..rolls {
-                match rng.gen_range(1..=10) {
-                    1 => { jellie += 1; },
-                    2 => { calico += 1; },
-                    3 => { red += 1; },
-                    4 => { tuxedo += 1; },
-                    5 => { white += 1; },
-                    6 => { ragdoll += 1; },
-                    7 => { british += 1; },
-                    8 => { tabby += 1; },
-                    9 => { persian += 1; },
-                    10 => { siamese += 1; },
-                    _ => {}
-                };
-            };
-        }
-        Err(_) => {
-            return error;
-        }
-    }
+pub fn rollcats(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let cats: u32 = CATS.parse(&message_parts, 1)?;
+
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
+    let [jellie, calico, red, tuxedo, white, ragdoll, british, tabby, persian, siamese] = mob_drops::roll_cats(cats as u64, rng);
+    roll.record(Some(jellie as f64), json!({
+        "cats": cats,
+        "jellie": jellie,
+        "calico": calico,
+        "red": red,
+        "tuxedo": tuxedo,
+        "white": white,
+        "ragdoll": ragdoll,
+        "british": british,
+        "tabby": tabby,
+        "persian": persian,
+        "siamese": siamese,
+    }));
         
     Ok(format!("You got {} Jellie, {} Calico, {} Red, {} Tuxedo, {} White, {} Ragdoll, {} British, {} Tabby, {} Persian, {} Siamese.", 
     jellie, calico, red, tuxedo, white, ragdoll, british, tabby, persian, siamese))
 }
 
-pub fn rollblazerods(message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !rollblazerods {rods} {looting level}".to_owned());
-
-    if message_parts.len() <= 2 {
-        return error;
-    }
-
-    let mut rng: StdRng = SeedableRng::from_entropy();
-    let rods_number = message_parts[1].parse::<u32>();
-    let looting_level = message_parts[2].parse::<u32>();
-
-    let mut rods: u32 = 0;
-    let mut kills: u32 = 0;
+pub fn rollblazerods(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let rods_number: u32 = BLAZE_RODS.parse(&message_parts, 1)?;
+    let looting_level: u32 = LOOTING_LEVEL.parse(&message_parts, 2)?;
 
-    match (rods_number, looting_level) {
-        (Ok(rods_number), Ok(looting_level)) => {
-            if looting_level > 3 {
-                return error;
-            }
-
-            while rods < rods_number {
-                rods += rng.gen_range(0..=(1+looting_level));
-                kills += 1;
-            }
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
+    let kills: u64 = mob_drops::roll_blaze_kills(rods_number as u64, looting_level, rng);
+    roll.record(Some(kills as f64), json!({ "rods": rods_number, "looting_level": looting_level, "kills": kills }));
 
-            return Ok(format!("You got {} blaze rods from killing {} blazes with looting {}.", rods_number, kills, looting_level))
-        },
-        _ => {
-            return error;
-        }
-    }
+    Ok(format!("You got {} blaze rods from killing {} blazes with looting {}.", rods_number, kills, looting_level))
 }
 
-pub fn tridentjuicers(sqlite_connection: &Connection) -> Result<String, String> {
-    let query = "SELECT users.display_name as username, durability FROM trident_rolls INNER JOIN users on trident_rolls.user_id = users.user_id ORDER BY durability DESC LIMIT 3;";
-    let statement = sqlite_connection.prepare(query);
-    let mut message: String = "Top 3 best trident rolls: ".to_owned();
+// Rolls any loot table, so a new loot command only needs its table.
+pub fn rollloot(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let name: &str = message_parts.get(1).ok_or(CommandError::InvalidSyntax)?;
+    let rolls: u32 = if message_parts.len() > 2 { LOOT_ROLLS.parse(&message_parts, 2)? } else { 1 };
+    let looting_level: u32 = if message_parts.len() > 3 { LOOTING_LEVEL.parse(&message_parts, 3)? } else { 0 };
 
-    match statement {
-        Ok(mut statement) => while let Ok(State::Row) = statement.next() {
-            let user = statement.read::<String, _>("username").unwrap();
-            let durability = statement.read::<i64, _>("durability").unwrap();
-        
-            message += &format!("{} - {}; ", user, durability);
-        },
-        Err(error) => {
-            println!("Trident juicers error: {}", error);
-            return Err(format!("Error: {}", error));
-        }
+    if !loot_tables().contains(name) {
+        return Err(CommandError::Message(format!("Error: there's no loot table called {}.", name)));
     }
-    
-    Ok(message)
-}
-
-pub fn gpjuicers(sqlite_connection: &Connection) -> Result<String, String> {
-    let query = "SELECT users.display_name as username, gunpowder FROM gunpowder_rolls INNER JOIN users on gunpowder_rolls.user_id = users.user_id ORDER BY gunpowder DESC LIMIT 3;";
-    let statement = sqlite_connection.prepare(query);
-    let mut message: String = "Top 3 best desert temple gunpowder rolls: ".to_owned();
 
-    match statement {
-        Ok(mut statement) => while let Ok(State::Row) = statement.next() {
-            let user = statement.read::<String, _>("username").unwrap();
-            let durability = statement.read::<i64, _>("gunpowder").unwrap();
-        
-            message += &format!("{} - {}; ", user, durability);
-        },
-        Err(error) => {
-            println!("Gunpowder juicers error: {}", error);
-            return Err(format!("Error: {}", error));
-        }
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
+    let context: LootContext = LootContext { open_water: true, ..LootContext::player_kill(looting_level) };
+    let totals: Vec<(String, u64)> = mob_drops::roll_totals(loot_tables(), name, rolls as u64, &context, rng);
+    let items: serde_json::Map<String, serde_json::Value> = totals.iter().map(|(item, count)| (item.to_owned(), json!(count))).collect();
+    // scored by the number of items
+    roll.record(Some(totals.iter().map(|(_, count)| count).sum::<u64>() as f64), json!({ "table": name, "rolls": rolls, "looting_level": looting_level, "items": items }));
+
+    let drops: Vec<String> = totals
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(item, count)| format!("{} {}", count, loot::item_name(&item)))
+        .collect();
+    let rolled: String = if rolls == 1 { "1 roll".to_owned() } else { format!("{} rolls", rolls) };
+
+    if drops.is_empty() {
+        Ok(format!("You got nothing from {} of {}.", rolled, name))
+    } else {
+        Ok(format!("You got {} from {} of {}.", drops.join(", "), rolled, name))
     }
-    
-    Ok(message)
 }
 
-pub fn dailytridentjuicers(sqlite_connection: &Connection) -> Result<String, String> {
-    let one_day_ms: u128 = 86_400_000;
-    let unix_time: u128 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-    let query = &format!("SELECT users.display_name as username, durability FROM trident_rolls INNER JOIN users on trident_rolls.user_id = users.user_id WHERE unix_time > {} ORDER BY durability DESC LIMIT 3;", unix_time - one_day_ms);
-    let statement = sqlite_connection.prepare(query);
-    let mut message: String = "Top 3 best trident rolls in last 24 hours: ".to_owned();
+pub fn rollskulls(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let skulls_number: u32 = SKULLS.parse(&message_parts, 1)?;
+    let looting_level: u32 = LOOTING_LEVEL.parse(&message_parts, 2)?;
 
-    match statement {
-        Ok(mut statement) => while let Ok(State::Row) = statement.next() {
-            let user = statement.read::<String, _>("username").unwrap();
-            let durability = statement.read::<i64, _>("durability").unwrap();
-        
-            message += &format!("{} - {}; ", user, durability);
-        },
-        Err(error) => {
-            println!("Trident juicers error: {}", error);
-            return Err(format!("Error: {}", error));
-        }
-    }
-    
-    Ok(message)
-}
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
+    let kills: u64 = mob_drops::roll_wither_skeleton_kills(skulls_number as u64, looting_level, rng);
+    roll.record(Some(kills as f64), json!({ "skulls": skulls_number, "looting_level": looting_level, "kills": kills }));
 
-pub fn tridentnoobs(sqlite_connection: &Connection) -> Result<String, String> {
-    let query = "SELECT users.display_name as username, COUNT(durability) as zeros FROM trident_rolls INNER JOIN users on trident_rolls.user_id = users.user_id WHERE durability = 0 GROUP BY username ORDER BY zeros DESC LIMIT 3;";
+    Ok(format!("You got {} skulls from killing {} wither skeletons with looting {}.", skulls_number, kills, looting_level))
+}
 
-    let statement = sqlite_connection.prepare(query);
-    let mut message: String = "Top 3 chatters with most 0 durability trident rolls: ".to_owned();
+pub fn myrolls(sqlite_connection: &Connection, channel: &str, user_id: &str, message_parts: Vec<&str>) -> CommandResult {
+    let command: String = match message_parts.get(1) {
+        Some(command) => format!("!{}", command.trim_start_matches('!').to_lowercase()),
+        None => return Err(CommandError::InvalidSyntax),
+    };
 
-    match statement {
-        Ok(mut statement) => while let Ok(State::Row) = statement.next() {
-            let user = statement.read::<String, _>("username").unwrap();
-            let zeros = statement.read::<i64, _>("zeros").unwrap();
-        
-            message += &format!("{} - {}; ", user, zeros);
-        },
+    let lower_wins: bool = LOWER_SCORES_WIN.contains(&command.as_str());
+    let history = database::recent_roll_scores(sqlite_connection, channel, user_id, &command, RECENT_ROLLS)
+        .and_then(|scores| Ok((scores, database::roll_summary(sqlite_connection, channel, user_id, &command, lower_wins)?)));
+    let (scores, (rolls, best, average)) = match history {
+        Ok(history) => history,
         Err(error) => {
-            println!("Trident noobs error: {}", error);
-            return Err(format!("Error: {}", error));
+            println!("My rolls error: {}", error);
+            return Err(CommandError::Message(format!("Error: {}", error)));
         }
-    }
-    
-    Ok(message)
-}
-
-pub fn rollskulls(message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !rollskulls {skulls} {looting level}".to_owned());
+    };
 
-    if message_parts.len() <= 2 {
-        return error;
+    if rolls == 0 {
+        return Ok(format!("You haven't rolled {} yet.", command));
     }
 
-    let mut rng: StdRng = SeedableRng::from_entropy();
-    let skulls_number = message_parts[1].parse::<u32>();
-    let looting_level = message_parts[2].parse::<u32>();
-
-    let mut skulls: u32 = 0;
-    let mut kills: u32 = 0;
-
-    match (skulls_number, looting_level) {
-        (Ok(skulls_number), Ok(looting_level)) => {
-            if looting_level > 3 {
-                return error;
-            }
-
-            while skulls < skulls_number {
-                skulls += if rng.gen_range(1..=1000) <= 25 + looting_level * 10 { 1 } else { 0 };
-                kills += 1;
-            }
-
-            return Ok(format!("You got {} skulls from killing {} wither skeletons with looting {}.", skulls_number, kills, looting_level))
-        },
-        _ => {
-            return error;
-        }
+    let recent: Vec<String> = scores.iter().map(|score| score.map_or("-".to_owned(), format_score)).collect();
+    let mut message: String = format!("Your last {} rolls: {}", command, recent.join(", "));
+    if let (Some(best), Some(average)) = (best, average) {
+        message += &format!("; best {}, average {}", format_score(best), format!("{:.2}", average).replace(".", ","));
     }
-}
 
-pub fn commandstats(sqlite_connection: &Connection, message_parts: Vec<&str>) -> Result<String, String> {
-    let error: Result<String, String> = Err("Error: Invalid syntax; !commandstats {command name}".to_owned());
+    Ok(format!("{} over {} rolls.", message, rolls))
+}
 
-    if message_parts.len() <= 1 {
-        return error;
+// Whole scores without decimals, the rest with two and a decimal comma.
+pub fn format_score(score: f64) -> String {
+    if score.fract() == 0.0 {
+        format!("{}", score as i64)
+    } else {
+        format!("{:.2}", score).replace(".", ",")
     }
+}
 
-    let command_name = message_parts[1].parse::<String>();
+pub fn stats(sqlite_connection: &Connection, channel: &str, user_id: &str, message_parts: Vec<&str>) -> CommandResult {
+    let database_error = |error: sqlite::Error| {
+        println!("Stats error: {}", error);
+        CommandError::Message(format!("Error: {}", error))
+    };
 
-    match command_name {
-        Ok(command_name) => {
-            let db_command_name: &str = &command_name.replace("!", "emark_");
-            let total_uses_query = &format!("SELECT name, SUM(uses) AS total_uses FROM commands WHERE name = '{}';", db_command_name);
-            let top_users_query = &format!("SELECT name, SUM(uses) AS uses, users.display_name as username from commands INNER JOIN users on commands.user_id = users.user_id WHERE name = '{}' GROUP BY username ORDER BY uses DESC LIMIT 3;", db_command_name);
+    let user_id: String = match message_parts.get(1) {
+        Some(name) => find_chatter(sqlite_connection, channel, name)?,
+        None => user_id.to_owned(),
+    };
 
-            let total_uses_statement = sqlite_connection.prepare(total_uses_query);
-            let top_users_statement = sqlite_connection.prepare(top_users_query);
-            let mut message: String = format!("Top 3 users with most {} uses: ", command_name);
+    // only the caller can be missing, since everyone found by name has chatted
+    let profile: database::Profile = match database::profile(sqlite_connection, channel, &user_id).map_err(database_error)? {
+        Some(profile) => profile,
+        None => return Ok("You haven't chatted here yet.".to_owned()),
+    };
 
-            match top_users_statement {
-                Ok(mut top_users_statement) => while let Ok(State::Row) = top_users_statement.next() {
-                    let user = top_users_statement.read::<String, _>("username").unwrap();
-                    let uses = top_users_statement.read::<i64, _>("uses").unwrap();
-                    
-                    message += &format!("{}: {} uses; ", user, uses);
-                },
-                Err(error) => {
-                    println!("Command stats error: {}", error);
-                    return Err(format!("Error: {}", error));
-                }
-            }
+    let mut message: String = format!("{}: {} messages (#{} chatter)", profile.display_name, profile.messages, profile.rank);
+    if let Some((command, uses)) = &profile.favourite_command {
+        message += &format!("; favourite command {} ({} uses)", command.replace("emark_", "!"), uses);
+    }
+    if let (Some(best), Some(worst)) = (profile.best_trident, profile.worst_trident) {
+        message += &format!("; best trident {}, worst trident {}", best, worst);
+    }
+    if let Some(best) = profile.best_gunpowder {
+        message += &format!("; best gunpowder {}", best);
+    }
+    if let Some(first_seen) = profile.first_seen {
+        message += &format!("; first seen {}", format_date(first_seen));
+    }
 
-            match total_uses_statement {
-                Ok(mut total_uses_statement) => if let Ok(State::Row) = total_uses_statement.next() {
-                    let total_uses = total_uses_statement.read::<String, _>("total_uses").unwrap();
+    Ok(message + ".")
+}
 
-                    message += &format!("Total uses: {}.", total_uses);
-                },
-                Err(error) => {
-                    println!("Command stats error: {}", error);
-                    return Err(format!("Error: {}", error));
-                }
-            }
+// The user id of a chatter named in a command, by login or display name, with or without an "@".
+pub fn find_chatter(sqlite_connection: &Connection, channel: &str, name: &str) -> Result<String, CommandError> {
+    let name: &str = name.trim_start_matches('@');
 
-            return Ok(message) 
-        },
-        _ => {
-            return error
+    match database::find_user_id(sqlite_connection, channel, name) {
+        Ok(Some(user_id)) => Ok(user_id),
+        Ok(None) => Err(CommandError::Message(format!("Error: there's no chatter called {}.", name))),
+        Err(error) => {
+            println!("Find chatter error: {}", error);
+            Err(CommandError::Message(format!("Error: {}", error)))
         }
     }
 }
 
-pub fn raid(file_path: &str) -> Result<String, String> {
+// A unix time in ms as a UTC date like "2023-11-14".
+fn format_date(unix_time: i64) -> String {
+    // days to a civil date, from Howard Hinnant's date algorithms
+    let days: i64 = unix_time.div_euclid(86_400_000) + 719_468;
+    let era: i64 = days.div_euclid(146_097);
+    let day_of_era: i64 = days - era * 146_097;
+    let year_of_era: i64 = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: i64 = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year: i64 = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{}-{:02}-{:02}", year, month, day)
+}
+
+pub fn raid(file_path: &str) -> CommandResult {
     let error: String = "Error: Couldn't get the raids.".to_owned();
 
+    check_raid_file(file_path);
+
     match File::open(file_path) {
         Err(err) => {
             println!("{}, {}", error, err);
-            return Err(error);
+            Err(CommandError::Message(error))
         },
         Ok(mut file) => {
             let mut value: String = String::new();
             match file.read_to_string(&mut value) {
                 Err(err) => {
                     println!("{}, {}", error, err);
-                    return Err(error);
+                    Err(CommandError::Message(error))
                 },
-                Ok(_) => {
-                    return Ok(value);
-                }
+                Ok(_) => Ok(value),
             }
         }
     }
 }
 
-pub fn rollphantoms() -> Result<String, String> {
-    let (time, spawns) = get_phantoms_spawn_time(); 
-    let formatted_time: String = format_start_time(time);
+pub fn setraid(file_path: &str, message_parts: Vec<&str>) -> CommandResult {
+    if message_parts.len() <= 1 {
+        return Err(CommandError::InvalidSyntax);
+    }
 
+    let value: String = message_parts[1..].join(" ");
+    write_raid_file(file_path, &value)?;
 
-    Ok(format!("You got {} phantoms spawn at {}!", spawns, formatted_time))
+    Ok(format!("Updated the !raid command to: {}", value))
 }
 
-pub fn rollaassg() -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+pub fn resetraid(file_path: &str) -> CommandResult {
+    write_raid_file(file_path, RAID_FILE_DEFAULT_VALUE)?;
 
-    // Temple
-    if rng.gen_range(1..=100) <= 10 {
-        match rng.gen_range(1..=100) {
-            1..=10 => {
-                return Ok("Your any% Temple blew up.".to_owned());
-            },
-            11..=30 => {
-                return Ok("You died to a Creeper in your any% Temple.".to_owned());
-            },
-            31..=65 => {
-                return Ok("You died to a Zombie in your any% Temple.".to_owned());
-            },
-            66..=95 => {
-                return Ok("You died to a Skeleton in your any% Temple.".to_owned());
-            },
-            96..=100 => {
-                return Ok("You died to a Witch in your any% Temple.".to_owned());
+    Ok("Cleared the !raid command.".to_owned())
+}
 
-            },
-            _ => {
-                return Ok("If you see this, something broke.".to_owned());
+fn write_raid_file(file_path: &str, value: &str) -> Result<(), CommandError> {
+    let error: String = "Error: Couldn't update the raids.".to_owned();
+
+    match File::create(file_path) {
+        Err(err) => {
+            println!("{}, {}", error, err);
+            return Err(CommandError::Message(error));
+        },
+        Ok(mut file) => {
+            if let Err(err) = file.write_all(value.as_bytes()) {
+                println!("{}, {}", error, err);
+                return Err(CommandError::Message(error));
             }
         }
     }
 
-    // random events
-    if rng.gen_range(1..=100) <= 3 {
-        match rng.gen_range(1..=100) {
-            1..=10 => {
-                return Ok("You mispalced obby when building your first nether portal.".to_owned());
-            },
-            11..=55 => {
-                return Ok("Your run died to slow pre-Nether any% crafting.".to_owned());
-            },
-            56..=100 => {
-                return Ok("You couldn't find your wood after blowing up trees in any%.".to_owned());
-            },
-            _ => {
-                return Ok("If you see this, something broke.".to_owned());
-            }
-        }
+    Ok(())
+}
+
+// Reads the phantom options, like "difficulty=normal" or "rest=60", out of the message. The
+// time since rest is in minutes and the sky exposure in percent.
+fn phantom_model(message_parts: &mut Vec<&str>) -> Result<PhantomModel, CommandError> {
+    let mut model: PhantomModel = PhantomModel::default();
+
+    if let Some(difficulty) = take_option(message_parts, "difficulty") {
+        model.difficulty = Difficulty::from_name(difficulty)
+            .ok_or_else(|| CommandError::Message("Error: difficulty must be easy, normal or hard.".to_owned()))?;
+    }
+    if let Some(local) = take_option(message_parts, "local") {
+        model.local_difficulty = Some(LOCAL_DIFFICULTY.parse(&[local], 0)?);
+    }
+    if let Some(moon) = take_option(message_parts, "moon") {
+        model.moon_phase = MOON_PHASE.parse(&[moon], 0)?;
+    }
+    if let Some(rest) = take_option(message_parts, "rest") {
+        model.time_since_rest = MINUTES_SINCE_REST.parse(&[rest], 0)? * 1200;
+    }
+    if let Some(group) = take_option(message_parts, "group") {
+        model.min_group = PHANTOM_GROUP.parse(&[group], 0)?;
+    }
+    if let Some(sky) = take_option(message_parts, "sky") {
+        model.sky_exposure = SKY_EXPOSURE.parse(&[sky], 0)? / 100.0;
     }
+    reject_unknown_options(message_parts)?;
 
-    // nether
-    if rng.gen_range(1..=100) == 1 {
-        match rng.gen_range(1..=100) {
-            1..=10 => {
-                return Ok("You mispalced obby when building your first nether portal.".to_owned());
-            },
-            11..=100 => {
-                return Ok("You accidentally hit one of the Zombiefied Piglins in pre-Bastion any%, so they killed you.".to_owned());
-            },
-            _ => {
-                return Ok("If you see this, something broke.".to_owned());
-            }
-        }
+    Ok(model)
+}
+
+pub fn rollphantoms(roll: &mut Roll, mut message_parts: Vec<&str>) -> CommandResult {
+    let model: PhantomModel = phantom_model(&mut message_parts)?;
+
+    let spawn: Option<(u64, u64)> = phantoms::get_phantoms_spawn_time(&model, &mut roll.rng);
+    // scored by the minutes until the phantoms spawn
+    roll.record(spawn.map(|(time, _)| time as f64 / 1200.0), json!({ "time": spawn.map(|(time, _)| time), "phantoms": spawn.map(|(_, spawns)| spawns) }));
+
+    match spawn {
+        Some((time, spawns)) => Ok(format!("You got {} phantoms spawn at {}!", spawns, format_start_time(time))),
+        None => Ok(format!("No group of {} or more phantoms spawned in {} minutes.", model.min_group, phantoms::LATEST_SPAWN / 1200)),
     }
+}
 
-    // bastion
-    if rng.gen_range(1..=100) <= 75 {
-        match rng.gen_range(1..=100) {
-            1..=5 => {
-                return Ok("You died to Blazes during any% Nether, beacuse you didn't get Fire Resistance from barters.".to_owned());
-            },
-            6..=60 => {
-                return Ok("You didn't get enough Ender Pearls from barters in any% Nether.".to_owned());
-            },
-            61..=100 => {
-                return Ok("You didn't get enough materials for Explosives from barters in any% Nether".to_owned());
-            },
-            _ => {
-                return Ok("If you see this, something broke.".to_owned());
-            }
-        }
+pub fn phantomodds(mut message_parts: Vec<&str>) -> CommandResult {
+    let model: PhantomModel = phantom_model(&mut message_parts)?;
+    let mins: f64 = PHANTOM_MINUTES.parse(&message_parts, 1)?;
+
+    let odds: f64 = phantoms::phantom_odds_by(&model, (mins * 1200.0) as u64);
+    Ok(format!("Odds of {} or more phantoms spawning in first {} minutes: ~{:.4}%", model.min_group, mins, odds * 100.0).replace(".", ","))
+}
+
+pub fn rollaassg(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let run: Run = aassg::simulate_run(&mut roll.rng);
+    // finished runs are scored by their time in seconds
+    let death: Option<(&str, &str)> = run.death.map(|(split, reason, _)| (split.name(), reason));
+    roll.record(
+        if run.death.is_none() { Some(run.time() as f64) } else { None },
+        json!({ "time": run.time(), "died_in": death.map(|(split, _)| split), "reason": death.map(|(_, reason)| reason) })
+    );
+
+    if let Some((split, reason, time)) = run.death {
+        return Ok(format!("{} The run died at {} in the {} split.", reason, aassg::format_run_time(time), split.name()));
     }
 
-    Ok("Yhh uhh i didn't make this yet.".to_owned())
+    let splits: Vec<String> = run.splits
+        .iter()
+        .map(|(split, time)| format!("{} {}", split.name(), aassg::format_run_time(*time)))
+        .collect();
+
+    Ok(format!("You finished AASSG in {}! {}", aassg::format_run_time(run.time()), splits.join(" | ")))
 }
 
-pub fn route() -> Result<String, String> {
+pub fn aassgodds(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let runs: u64 = if message_parts.len() > 1 { AASSG_RUNS.parse(&message_parts, 1)? } else { 1_000 };
+
+    let odds: RunOdds = aassg::simulate_runs(runs, &mut roll.rng);
+    // finish times in 10 minute buckets
+    let histogram: Vec<String> = odds.histogram(600)
+        .iter()
+        .map(|(start, count)| format!("{} - ~{:.1}%", aassg::format_run_time(*start), *count as f64 / runs as f64 * 100.0))
+        .collect();
+
+    let mut reply: String = format!("~{:.4}% of {} runs finish AASSG", odds.completion_rate() * 100.0, runs);
+    if let Some((deadliest, deaths)) = odds.deadliest_split() {
+        reply.push_str(&format!(", the {} split ends the most (~{:.4}%)", deadliest.name(), deaths as f64 / runs as f64 * 100.0));
+    }
+    if !histogram.is_empty() {
+        reply.push_str(&format!(" | Finish times: {}", histogram.join("; ")));
+    }
+
+    Ok(reply.replace(".", ","))
+}
+
+pub fn rollbarters(roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+    let gold: u64 = GOLD_INGOTS.parse(&message_parts, 1)?;
+
+    let barters: Barters = bartering::barter(gold, &mut roll.rng);
+    let items: serde_json::Map<String, serde_json::Value> = barters.items.iter().map(|(item, count)| (item.to_owned(), json!(count))).collect();
+    // scored by Ender Pearls
+    roll.record(Some(barters.count("minecraft:ender_pearl") as f64), json!({ "gold": gold, "items": items }));
+    let items: Vec<String> = barters.items
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(item, count)| format!("{} {}", count, bartering::barter_name(item)))
+        .collect();
+
+    Ok(format!("You got {} from {} gold ingots.", items.join(", "), gold))
+}
+
+pub fn route() -> CommandResult {
     Ok("1.20: https://docs.google.com/document/d/1K2axBuCsNOdQ9vA7AYUaxhqgX5zXmWBWN-rBELLjxJM/edit".to_owned())
 }
 
-pub fn rollsilence() -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+pub fn rollsilence(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
     let mut rolls: i32 = 0;
     
     loop {
@@ -945,20 +932,21 @@ pub fn rollsilence() -> Result<String, String> {
             break;
         }
     }
+    roll.record(Some(rolls as f64), json!({ "chests": rolls }));
 
     Ok(format!("You needed to check only {} chests to get the Silence Trim!", rolls).to_owned())
 }
 
-pub fn hdwghfix() -> Result<String, String> {
+pub fn hdwghfix() -> CommandResult {
     Ok("Mojang added six new effects in the 24w13a snapshot, but they are currently not required for HDWGH, so i made a Data Pack that adds these new effects to HDWGH. Link: https://github.com/Oskar-Dev/24w13a_hdwgh_fix".to_owned())
 }
 
-pub fn caamel() -> Result<String, String> {
+pub fn caamel() -> CommandResult {
     Ok("chilling cAAmel - Cross-Platform (In the future), high performance AA Tracker made by me in C with SDL2! You can check out my bad code here: https://github.com/Oskar-Dev/kAAmel chilling".to_owned())
 }
 
-pub fn rollheavycore() -> Result<String, String> {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+pub fn rollheavycore(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+    let rng: &mut Xoroshiro128PlusPlus = &mut roll.rng;
     let mut rolls: i32 = 0;
     
     loop {
@@ -969,14 +957,313 @@ pub fn rollheavycore() -> Result<String, String> {
     }
 
     let only: &str = if rng.gen_range(1..=2) == 1 { " only" } else { "" };
+    roll.record(Some(rolls as f64), json!({ "vaults": rolls }));
 
     Ok(format!("You needed to open{} {} Ominous Vaults to get the Heavy Core!", only, rolls).to_owned())
 }
 
-pub fn seed() -> Result<String, String> {
+pub fn seed() -> CommandResult {
     Ok("-1850838705525738103 - Found by me.".to_owned())
 }
 
-pub fn lootingseedroute() -> Result<String, String> {
+pub fn lootingseedroute() -> CommandResult {
     Ok("https://docs.google.com/document/d/1TkCVcu2HO5iMjSyN8F_9S3RpvTLVZR6hKLyEkPY9sLk/edit?usp=sharing".to_owned())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::RollRecord;
+    use proptest::prelude::*;
+    use sqlite::ConnectionWithFullMutex;
+    use std::time::{Duration, Instant};
+
+    // Every command that reads numbers from chat.
+    type ArgsHandler = fn(Vec<&str>) -> CommandResult;
+    type RollHandler = fn(&mut Roll, Vec<&str>) -> CommandResult;
+
+    const NUMERIC_COMMANDS: &[(&str, ArgsHandler)] = &[
+        ("!thunderodds", thunderodds),
+        ("!thundertime", thundertime),
+        ("!thunderduration", thunderduration),
+        ("!thunderwindow", thunderwindow),
+        ("!skullodds", skullodds),
+        ("!tridentodds", tridentodds),
+        ("!phantomodds", phantomodds),
+        ("!eyeodds", eyeodds),
+    ];
+
+    const NUMERIC_ROLL_COMMANDS: &[(&str, RollHandler)] = &[
+        ("!rolldrowned", rolldrowned),
+        ("!rollcats", rollcats),
+        ("!rollblazerods", rollblazerods),
+        ("!rollskulls", rollskulls),
+        ("!rollloot", rollloot),
+        ("!rollphantoms", rollphantoms),
+        ("!rollbarters", rollbarters),
+        ("!aassgodds", aassgodds),
+    ];
+
+    fn arg() -> impl Strategy<Value = String> {
+        prop_oneof![
+            (0u64..20).prop_map(|n| n.to_string()),
+            (0u64..2_000_000_000).prop_map(|n| n.to_string()),
+            any::<i128>().prop_map(|n| n.to_string()),
+            any::<f64>().prop_map(|n| n.to_string()),
+            "[a-zA-Z0-9.,-]{0,8}",
+        ]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn numeric_commands_never_panic_or_hang(args in prop::collection::vec(arg(), 0..5)) {
+            for (name, command) in NUMERIC_COMMANDS {
+                let mut message_parts: Vec<&str> = vec![name];
+                message_parts.extend(args.iter().map(|arg| arg.as_str()));
+
+                let started: Instant = Instant::now();
+                let _ = command(message_parts);
+                prop_assert!(started.elapsed() < Duration::from_secs(10), "{} {:?} took {:?}", name, args, started.elapsed());
+            }
+
+            for (name, command) in NUMERIC_ROLL_COMMANDS {
+                let mut message_parts: Vec<&str> = vec![name];
+                message_parts.extend(args.iter().map(|arg| arg.as_str()));
+
+                let started: Instant = Instant::now();
+                let _ = command(&mut Roll::new(1234, false), message_parts);
+                prop_assert!(started.elapsed() < Duration::from_secs(10), "{} {:?} took {:?}", name, args, started.elapsed());
+            }
+        }
+
+        #[test]
+        fn thunderodds_rejects_minutes_out_of_range(minutes in prop_oneof![-1e300f64..-1e-9, 10_000.000_001f64..1e300]) {
+            prop_assert!(thunderodds(vec!["!thunderodds", &minutes.to_string()]).is_err());
+        }
+    }
+
+    #[test]
+    fn missing_args_show_the_usage() {
+        assert_eq!(skullodds(vec!["!skullodds", "1", "100"]), Err(CommandError::InvalidSyntax));
+        assert_eq!(rollcats(&mut Roll::new(1, false), vec!["!rollcats"]), Err(CommandError::InvalidSyntax));
+    }
+
+    #[test]
+    fn args_over_the_limit_are_explained() {
+        assert_eq!(
+            rollcats(&mut Roll::new(1, false), vec!["!rollcats", "4294967295"]),
+            Err(CommandError::Message("Error: cats number must be a number from 0 to 1000000000.".to_owned()))
+        );
+        assert_eq!(
+            skullodds(vec!["!skullodds", "101", "100", "3"]),
+            Err(CommandError::Message("Error: drops must be a number from 0 to 100.".to_owned()))
+        );
+        assert!(skullodds(vec!["!skullodds", "10", "10000", "3"]).is_ok());
+    }
+
+    #[test]
+    fn weather_commands_take_a_version() {
+        let unknown_version: CommandResult =
+            Err(CommandError::Message("Error: version must be 1.12 or later, like 1.16 or 1.20.5.".to_owned()));
+
+        assert!(weather(&mut Roll::new(1, false), vec!["!weather", "1.20.5"]).is_ok());
+        assert_eq!(weather(&mut Roll::new(1, false), vec!["!weather", "1.8.9"]), unknown_version);
+        assert_eq!(thunderodds(vec!["!thunderodds", "60", "b1.7"]), unknown_version);
+        // a lone version skips the optional percentile
+        assert_eq!(thundertime(vec!["!thundertime", "1.16"]), thundertime(vec!["!thundertime"]));
+        assert!(thundertime(vec!["!thundertime", "75", "1.12"]).is_ok());
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_roll() {
+        for seed in [0, 1234, crate::rng::MAX_SEED] {
+            assert_eq!(rollbiome(&mut Roll::new(seed, false), vec![]), rollbiome(&mut Roll::new(seed, true), vec![]));
+            assert_eq!(
+                rolldrowned(&mut Roll::new(seed, false), vec!["!rolldrowned", "500"]),
+                rolldrowned(&mut Roll::new(seed, true), vec!["!rolldrowned", "500"])
+            );
+
+            let mut roll: Roll = Roll::new(seed, false);
+            let mut replay: Roll = Roll::new(seed, true);
+            assert_eq!(rolltrident(&mut roll, vec![]), rolltrident(&mut replay, vec![]));
+            assert_eq!(roll.record, replay.record);
+        }
+    }
+
+    #[test]
+    fn loot_commands_roll_the_tables() {
+        let mut roll: Roll = Roll::new(1234, false);
+
+        assert!(rollloot(&mut roll, vec!["!rollloot", "entities/blaze", "100", "3"]).unwrap().ends_with("Blaze Rod from 100 rolls of entities/blaze."));
+        assert_eq!(rollloot(&mut roll, vec!["!rollloot"]), Err(CommandError::InvalidSyntax));
+        assert_eq!(
+            rollloot(&mut roll, vec!["!rollloot", "entities/creeper"]),
+            Err(CommandError::Message("Error: there's no loot table called entities/creeper.".to_owned()))
+        );
+    }
+
+    #[test]
+    fn phantom_options_change_the_model() {
+        let mut message_parts: Vec<&str> = vec!["!phantomodds", "difficulty=Normal", "60", "group=3", "rest=60", "sky=50"];
+        let model: PhantomModel = phantom_model(&mut message_parts).unwrap();
+
+        assert_eq!(message_parts, vec!["!phantomodds", "60"]);
+        assert_eq!(model, PhantomModel { difficulty: Difficulty::Normal, min_group: 3, time_since_rest: 72_000, sky_exposure: 0.5, ..Default::default() });
+        assert!(phantom_model(&mut vec!["!rollphantoms", "difficulty=peaceful"]).is_err());
+        assert_eq!(
+            phantom_model(&mut vec!["!rollphantoms", "moon=2", "insomnia=3"]),
+            Err(CommandError::Message("Error: there's no option called insomnia.".to_owned()))
+        );
+        assert_eq!(
+            rollphantoms(&mut Roll::new(1234, false), vec!["!rollphantoms", "difficulty=easy", "group=3"]),
+            Ok("No group of 3 or more phantoms spawned in 10000 minutes.".to_owned())
+        );
+        assert_eq!(phantomodds(vec!["!phantomodds", "60"]), Ok("Odds of 2 or more phantoms spawning in first 60 minutes: ~0,0000%".to_owned()));
+    }
+
+    #[test]
+    fn the_fishinge_emote_still_fishes() {
+        let mut registry: CommandRegistry = CommandRegistry::new();
+        register_commands(&mut registry);
+
+        assert_eq!(registry.find("Fishinge").map(|command| command.name()), Some("!fish"));
+        assert_eq!(registry.find("!fishinge").map(|command| command.name()), Some("!fish"));
+    }
+
+    #[test]
+    fn aassg_runs_barter_in_the_bastion() {
+        let replies: Vec<String> = (0..200).map(|seed| rollaassg(&mut Roll::new(seed, false), vec!["!rollaassg"]).unwrap()).collect();
+
+        assert!(replies.iter().any(|reply| reply.starts_with("You finished AASSG in ")));
+        assert!(replies.iter().any(|reply| reply.contains("from barters") && reply.ends_with(" in the Bastion split.")));
+        let finished: String = (0..1000)
+            .map(|seed| aassgodds(&mut Roll::new(seed, false), vec!["!aassgodds", "1"]).unwrap())
+            .find(|reply| reply.starts_with("~100,0000%"))
+            .unwrap();
+        assert!(finished.starts_with("~100,0000% of 1 runs finish AASSG | Finish times: "), "{}", finished);
+        assert!(rollbarters(&mut Roll::new(1234, false), vec!["!rollbarters", "320"]).unwrap().ends_with(" from 320 gold ingots."));
+        assert_eq!(rollbarters(&mut Roll::new(1234, false), vec!["!rollbarters"]), Err(CommandError::InvalidSyntax));
+    }
+
+    #[test]
+    fn findseed_keeps_everyones_best_portal() {
+        let sqlite_connection: ConnectionWithFullMutex = database::test_connection();
+        database::add_user_message(&sqlite_connection, "channel", "1", "chatter", "chatter", 1).unwrap();
+        database::add_user_message(&sqlite_connection, "channel", "2", "other", "other", 1).unwrap();
+        let portal = |eyes: u32| RollRecord { score: Some(eyes as f64), result: json!({ "eyes": eyes }) };
+        database::add_roll(&sqlite_connection, "channel", "1", "!findseed", &portal(eyes::FRAMES), 1, 1).unwrap();
+        database::add_roll(&sqlite_connection, "channel", "2", "!findseed", &portal(0), 1, 1).unwrap();
+
+        let new_best = |user_id: &str, seed: u64| -> bool {
+            findseed(&sqlite_connection, "channel", user_id, &mut Roll::new(seed, false), vec!["!findseed"]).unwrap().ends_with("your new best!")
+        };
+        assert!(!(0..50).any(|seed| new_best("1", seed)));
+        assert!((0..50).any(|seed| new_best("2", seed)));
+    }
+
+    #[test]
+    fn eyeodds_counts_the_portal_and_better() {
+        assert_eq!(eyeodds(vec!["!eyeodds", "0"]), Ok("Odds of a 0 eye portal: ~28,24295365%; Odds of 0 or more eyes: ~100,00000000%".to_owned()));
+    }
+
+    #[test]
+    fn biomes_come_from_the_version_tables() {
+        assert_eq!(biomeodds(vec!["!biomeodds", "deep", "ocean"]), Ok("Odds of rolling Deep Ocean in 1.16: ~7,3396%".to_owned()));
+        assert!(biomeodds(vec!["!biomeodds", "Meadow", "1.18.2"]).unwrap().ends_with("% (the 1.18 odds are estimates)"));
+        assert_eq!(biomeodds(vec!["!biomeodds", "Meadow"]), Err(CommandError::Message("Error: there's no biome called Meadow in 1.16.".to_owned())));
+        assert_eq!(biomeodds(vec!["!biomeodds"]), Err(CommandError::InvalidSyntax));
+        assert!(rollbiome(&mut Roll::new(1234, false), vec!["!rollbiome", "1.8"]).is_err());
+        assert!(rollbiome(&mut Roll::new(1234, false), vec!["!rollbiome", "1.19"]).unwrap().ends_with("! (the 1.18 odds are estimates)"));
+        assert!(rollbiome(&mut Roll::new(1234, false), vec!["!rollbiome"]).unwrap().ends_with('!'));
+    }
+
+    #[test]
+    fn myrolls_shows_the_history() {
+        let sqlite_connection: ConnectionWithFullMutex = database::test_connection();
+        let record = |command: &str, score: Option<f64>| {
+            let record: RollRecord = RollRecord { score, result: json!({}) };
+            database::add_roll(&sqlite_connection, "channel", "1", command, &record, 1234, score.unwrap_or(0.0) as i64).unwrap();
+        };
+
+        record("!rolltrident", Some(100.0));
+        record("!rolltrident", Some(250.0));
+        record("!rolltrident", Some(3.0));
+        record("!rollsilence", Some(80.0));
+        record("!rollsilence", Some(20.0));
+        record("!rollphantoms", None);
+
+        assert_eq!(
+            myrolls(&sqlite_connection, "channel", "1", vec!["!myrolls", "rolltrident"]),
+            Ok("Your last !rolltrident rolls: 250, 100, 3; best 250, average 117,67 over 3 rolls.".to_owned())
+        );
+        assert_eq!(
+            myrolls(&sqlite_connection, "channel", "1", vec!["!myrolls", "!rollsilence"]),
+            Ok("Your last !rollsilence rolls: 80, 20; best 20, average 50,00 over 2 rolls.".to_owned())
+        );
+        assert_eq!(myrolls(&sqlite_connection, "channel", "1", vec!["!myrolls", "!rollphantoms"]), Ok("Your last !rollphantoms rolls: - over 1 rolls.".to_owned()));
+        assert_eq!(myrolls(&sqlite_connection, "channel", "2", vec!["!myrolls", "!rollgp"]), Ok("You haven't rolled !rollgp yet.".to_owned()));
+        assert_eq!(myrolls(&sqlite_connection, "channel", "1", vec!["!myrolls"]), Err(CommandError::InvalidSyntax));
+    }
+
+    #[test]
+    fn stats_show_a_chatters_profile() {
+        let sqlite_connection: ConnectionWithFullMutex = database::test_connection();
+        database::add_user_message(&sqlite_connection, "channel", "1", "chatter", "Chatter", 1_700_000_000_000).unwrap();
+        database::add_user_message(&sqlite_connection, "channel", "1", "chatter", "Chatter", 1_700_000_100_000).unwrap();
+        database::add_user_message(&sqlite_connection, "channel", "2", "lurker", "lurker", 1_700_000_000_000).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rolltrident", "1", 1).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rolltrident", "1", 1).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rollgp", "1", 1).unwrap();
+        for (command, score) in [("!rolltrident", 250.0), ("!rolltrident", 3.0), ("!rollgp", 9.0)] {
+            let record: RollRecord = RollRecord { score: Some(score), result: json!({}) };
+            database::add_roll(&sqlite_connection, "channel", "1", command, &record, 1, 1).unwrap();
+        }
+
+        let profile: &str = "Chatter: 2 messages (#1 chatter); favourite command !rolltrident (2 uses); best trident 250, worst trident 3; best gunpowder 9; first seen 2023-11-14.";
+        assert_eq!(stats(&sqlite_connection, "channel", "1", vec!["!stats"]), Ok(profile.to_owned()));
+        assert_eq!(stats(&sqlite_connection, "channel", "2", vec!["!stats", "@chatter"]), Ok(profile.to_owned()));
+        assert_eq!(stats(&sqlite_connection, "channel", "2", vec!["!stats"]), Ok("lurker: 1 messages (#2 chatter); first seen 2023-11-14.".to_owned()));
+        assert_eq!(stats(&sqlite_connection, "channel", "3", vec!["!stats"]), Ok("You haven't chatted here yet.".to_owned()));
+        assert_eq!(stats(&sqlite_connection, "channel", "1", vec!["!stats", "nobody"]), Err(CommandError::Message("Error: there's no chatter called nobody.".to_owned())));
+        assert_eq!(format_date(951_782_400_000), "2000-02-29");
+    }
+
+    #[test]
+    fn renamed_chatters_keep_their_stats() {
+        let sqlite_connection: ConnectionWithFullMutex = database::test_connection();
+        database::add_user_message(&sqlite_connection, "channel", "1", "oldname", "OldName", 1).unwrap();
+        database::add_user_message(&sqlite_connection, "other_channel", "1", "oldname", "OldName", 1).unwrap();
+        database::add_user_message(&sqlite_connection, "channel", "1", "newname", "NewName", 2).unwrap();
+        database::add_user_message(&sqlite_connection, "channel", "2", "jp_chatter", "日本語", 3).unwrap();
+
+        assert_eq!(find_chatter(&sqlite_connection, "channel", "@NEWNAME"), Ok("1".to_owned()));
+        assert_eq!(find_chatter(&sqlite_connection, "channel", "oldname"), Ok("1".to_owned()));
+        assert_eq!(find_chatter(&sqlite_connection, "channel", "@jp_chatter"), Ok("2".to_owned()));
+        assert_eq!(find_chatter(&sqlite_connection, "third_channel", "newname"), Err(CommandError::Message("Error: there's no chatter called newname.".to_owned())));
+        assert!(stats(&sqlite_connection, "other_channel", "2", vec!["!stats", "@newname"]).unwrap().starts_with("NewName: 1 messages"));
+    }
+
+    #[test]
+    fn fish_saves_what_was_caught() {
+        let sqlite_connection: ConnectionWithFullMutex = database::test_connection();
+        database::add_user_message(&sqlite_connection, "channel", "1", "chatter", "chatter", 1).unwrap();
+
+        assert!(fish(&sqlite_connection, "channel", "1", &mut Roll::new(1234, false), vec!["!fish"]).unwrap().starts_with("You caught "));
+        let many: String = fish(&sqlite_connection, "channel", "1", &mut Roll::new(1234, false), vec!["!fish", "1000", "3", "3"]).unwrap();
+        assert!(many.starts_with("You caught ") && many.contains(" in 1000 casts, "), "{}", many);
+        fish(&sqlite_connection, "channel", "1", &mut Roll::new(1234, true), vec!["!fish", "1000"]).unwrap();
+
+        let caught = |category: &'static str| -> f64 {
+            database::leaderboard(&sqlite_connection, "channel", &Source::Catches(category), Metric::Sum, false, None).unwrap()[0].2
+        };
+        let (fish_caught, treasure_caught): (f64, f64) = (caught("fish"), caught("treasure"));
+        assert!(fish_caught > 500.0 && treasure_caught > 50.0, "{} fish, {} treasure", fish_caught, treasure_caught);
+        assert!(fish_caught + treasure_caught <= 1001.0);
+
+        assert_eq!(
+            fish(&sqlite_connection, "channel", "1", &mut Roll::new(1, false), vec!["!fish", "1", "0", "0", "lava"]),
+            Err(CommandError::Message("Error: water must be open or closed.".to_owned()))
+        );
+    }
+}
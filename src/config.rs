@@ -0,0 +1,376 @@
+use crate::cooldowns::{self, CooldownFeedback, CooldownSettings};
+use crate::registry::CommandRegistry;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+
+pub const DEFAULT_CONFIG_PATH: &str = "./config.toml";
+const CONFIG_PATH_VARIABLE: &str = "TRIDENT_BOT_CONFIG";
+// Twitch rejects chat messages longer than 500 characters.
+const TWITCH_MESSAGE_LIMIT: usize = 500;
+// Replaced with the channel name in per-channel file paths.
+const CHANNEL_PLACEHOLDER: &str = "{channel}";
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub login: String,
+    pub oauth_token: String,
+    pub channels: Vec<String>,
+    #[serde(default = "default_database_path")]
+    pub database_path: String,
+    // None keeps the first channel's raid list in ./raid.txt, where it was before the bot joined
+    // more than one channel, and gives the others ./raid_{channel}.txt
+    #[serde(default)]
+    pub raid_file_path: Option<String>,
+    #[serde(default = "default_max_message_length")]
+    pub max_message_length: usize,
+    // channel -> commands that are turned off in that channel
+    #[serde(default)]
+    pub disabled_commands: HashMap<String, Vec<String>>,
+    // command -> cooldowns; replaces the built-in defaults when set
+    #[serde(default = "cooldowns::default_cooldowns")]
+    pub cooldowns: HashMap<String, CooldownSettings>,
+    #[serde(default)]
+    pub cooldown_feedback: CooldownFeedback,
+    // the Twitch application the OAuth token was made for, needed to send whispers and to know
+    // when streams start
+    #[serde(default)]
+    pub client_id: Option<String>,
+    // how many simulation commands can run at the same time
+    #[serde(default = "default_max_simulations")]
+    pub max_simulations: usize,
+    // seconds before the bot gives up on a simulation
+    #[serde(default = "default_simulation_timeout")]
+    pub simulation_timeout: u64,
+    // a datapack style data folder with extra loot tables for !rollloot
+    #[serde(default)]
+    pub loot_tables_path: Option<String>,
+}
+
+fn default_database_path() -> String {
+    "chat_data.sqlite".to_owned()
+}
+
+fn default_max_message_length() -> usize {
+    450
+}
+
+fn default_max_simulations() -> usize {
+    2
+}
+
+fn default_simulation_timeout() -> u64 {
+    10
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(String, std::io::Error),
+    Parse(String, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "couldn't read the config file {}: {}", path, err),
+            ConfigError::Parse(path, err) => write!(f, "couldn't parse the config file {}: {}", path, err),
+            ConfigError::Invalid(message) => write!(f, "invalid config: {}", message),
+        }
+    }
+}
+
+impl Config {
+    // Reads the config file (./config.toml or the path in TRIDENT_BOT_CONFIG) and applies the
+    // TRIDENT_BOT_* environment variable overrides on top of it.
+    pub fn load(registry: &CommandRegistry) -> Result<Config, ConfigError> {
+        let path: String = env::var(CONFIG_PATH_VARIABLE).unwrap_or(DEFAULT_CONFIG_PATH.to_owned());
+
+        let contents: String = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => return Err(ConfigError::Read(path, err)),
+        };
+
+        let mut config: Config = Config::parse(&path, &contents)?;
+        config.apply_overrides(|name| env::var(name).ok());
+        config.validate()?;
+        config.resolve_commands(registry)?;
+
+        Ok(config)
+    }
+
+    pub fn parse(path: &str, contents: &str) -> Result<Config, ConfigError> {
+        toml::from_str(contents).map_err(|err| ConfigError::Parse(path.to_owned(), err))
+    }
+
+    pub fn apply_overrides<F: Fn(&str) -> Option<String>>(&mut self, variable: F) {
+        if let Some(login) = variable("TRIDENT_BOT_LOGIN") {
+            self.login = login;
+        }
+
+        if let Some(oauth_token) = variable("TRIDENT_BOT_OAUTH_TOKEN") {
+            self.oauth_token = oauth_token;
+        }
+
+        if let Some(channels) = variable("TRIDENT_BOT_CHANNELS") {
+            self.channels = channels.split(',').map(|channel| channel.to_owned()).collect();
+        }
+
+        if let Some(database_path) = variable("TRIDENT_BOT_DATABASE_PATH") {
+            self.database_path = database_path;
+        }
+
+        if let Some(raid_file_path) = variable("TRIDENT_BOT_RAID_FILE_PATH") {
+            self.raid_file_path = Some(raid_file_path);
+        }
+    }
+
+    pub fn validate(&mut self) -> Result<(), ConfigError> {
+        self.login = self.login.trim().to_lowercase();
+        self.channels = self.channels.iter().map(|channel| normalize_channel(channel)).collect();
+        self.disabled_commands = self.disabled_commands
+            .drain()
+            .map(|(channel, commands)| (normalize_channel(&channel), commands.iter().map(|command| command.to_lowercase()).collect()))
+            .collect();
+        self.cooldowns = self.cooldowns
+            .drain()
+            .map(|(command, settings)| (command.to_lowercase(), settings))
+            .collect();
+        // the irc client adds the "oauth:" prefix itself
+        self.oauth_token = self.oauth_token.trim().trim_start_matches("oauth:").to_owned();
+
+        if self.login.is_empty() {
+            return Err(ConfigError::Invalid("`login` can't be empty".to_owned()));
+        }
+
+        if self.oauth_token.is_empty() {
+            return Err(ConfigError::Invalid("`oauth_token` can't be empty".to_owned()));
+        }
+
+        if self.channels.is_empty() {
+            return Err(ConfigError::Invalid("`channels` needs at least one channel".to_owned()));
+        }
+
+        for (i, channel) in self.channels.iter().enumerate() {
+            if channel.is_empty() || !channel.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(ConfigError::Invalid(format!("`{}` is not a valid channel name", channel)));
+            }
+
+            if self.channels[..i].contains(channel) {
+                return Err(ConfigError::Invalid(format!("`{}` is listed in `channels` more than once", channel)));
+            }
+        }
+
+        for channel in self.disabled_commands.keys() {
+            if !self.channels.contains(channel) {
+                return Err(ConfigError::Invalid(format!("`disabled_commands` has an entry for `{}`, which isn't in `channels`", channel)));
+            }
+        }
+
+        if self.database_path.trim().is_empty() {
+            return Err(ConfigError::Invalid("`database_path` can't be empty".to_owned()));
+        }
+
+        if let Some(raid_file_path) = &self.raid_file_path {
+            if raid_file_path.trim().is_empty() {
+                return Err(ConfigError::Invalid("`raid_file_path` can't be empty".to_owned()));
+            }
+
+            if self.channels.len() > 1 && !raid_file_path.contains(CHANNEL_PLACEHOLDER) {
+                return Err(ConfigError::Invalid(format!("`raid_file_path` must contain {} when the bot joins more than one channel", CHANNEL_PLACEHOLDER)));
+            }
+        }
+
+        if self.max_message_length == 0 || self.max_message_length > TWITCH_MESSAGE_LIMIT {
+            return Err(ConfigError::Invalid(format!("`max_message_length` must be between 1 and {}", TWITCH_MESSAGE_LIMIT)));
+        }
+
+        if self.cooldown_feedback == CooldownFeedback::Whisper && self.client_id.as_deref().is_none_or(|client_id| client_id.trim().is_empty()) {
+            return Err(ConfigError::Invalid("`cooldown_feedback = \"whisper\"` needs a `client_id`".to_owned()));
+        }
+
+        if self.max_simulations == 0 {
+            return Err(ConfigError::Invalid("`max_simulations` must be at least 1".to_owned()));
+        }
+
+        if self.simulation_timeout == 0 {
+            return Err(ConfigError::Invalid("`simulation_timeout` must be at least 1 second".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    // Replaces the command names in disabled_commands and cooldowns with the names the commands
+    // are registered under, so "!fishinge" turns off !fish. Names that aren't commands are
+    // rejected instead of doing nothing.
+    pub fn resolve_commands(&mut self, registry: &CommandRegistry) -> Result<(), ConfigError> {
+        let resolve = |setting: &str, name: &str| match registry.find(name) {
+            Some(command) => Ok(command.name().to_owned()),
+            None => Err(ConfigError::Invalid(format!("`{}` names `{}`, which isn't a command", setting, name))),
+        };
+
+        for commands in self.disabled_commands.values_mut() {
+            *commands = commands.iter().map(|command| resolve("disabled_commands", command)).collect::<Result<_, _>>()?;
+        }
+
+        let mut cooldowns: HashMap<String, CooldownSettings> = HashMap::new();
+        for (command, settings) in self.cooldowns.drain() {
+            let name: String = resolve("cooldowns", &command)?;
+            if cooldowns.insert(name.to_owned(), settings).is_some() {
+                return Err(ConfigError::Invalid(format!("`cooldowns` has more than one entry for `{}`", name)));
+            }
+        }
+        self.cooldowns = cooldowns;
+
+        Ok(())
+    }
+
+    pub fn raid_file_path(&self, channel: &str) -> String {
+        match &self.raid_file_path {
+            Some(raid_file_path) => raid_file_path.replace(CHANNEL_PLACEHOLDER, channel),
+            None if self.channels.first().is_some_and(|first| first == channel) => "./raid.txt".to_owned(),
+            None => format!("./raid_{}.txt", channel),
+        }
+    }
+
+    pub fn is_command_enabled(&self, channel: &str, command_name: &str) -> bool {
+        match self.disabled_commands.get(channel) {
+            Some(commands) => !commands.iter().any(|command| command == command_name),
+            None => true,
+        }
+    }
+}
+
+fn normalize_channel(channel: &str) -> String {
+    channel.trim().trim_start_matches('#').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL_CONFIG: &str = "
+        login = \"Trident_Bot\"
+        oauth_token = \"oauth:abc123\"
+        channels = [\"#Oskar\"]
+    ";
+
+    #[test]
+    fn fills_in_defaults_and_normalizes() {
+        let mut config: Config = Config::parse("config.toml", MINIMAL_CONFIG).unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.login, "trident_bot");
+        assert_eq!(config.oauth_token, "abc123");
+        assert_eq!(config.channels, vec!["oskar".to_owned()]);
+        assert_eq!(config.database_path, "chat_data.sqlite");
+        assert_eq!(config.raid_file_path("oskar"), "./raid.txt");
+        assert_eq!(config.raid_file_path("other_channel"), "./raid_other_channel.txt");
+        assert_eq!(config.max_message_length, 450);
+        assert_eq!(config.cooldowns, cooldowns::default_cooldowns());
+        assert_eq!(config.cooldown_feedback, CooldownFeedback::Silent);
+        assert_eq!(config.max_simulations, 2);
+        assert_eq!(config.simulation_timeout, 10);
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let mut config: Config = Config::parse("config.toml", MINIMAL_CONFIG).unwrap();
+        config.apply_overrides(|name| match name {
+            "TRIDENT_BOT_CHANNELS" => Some("oskar,other_channel".to_owned()),
+            "TRIDENT_BOT_DATABASE_PATH" => Some("/data/chat.sqlite".to_owned()),
+            _ => None,
+        });
+        config.validate().unwrap();
+
+        assert_eq!(config.channels, vec!["oskar".to_owned(), "other_channel".to_owned()]);
+        assert_eq!(config.database_path, "/data/chat.sqlite");
+
+        config.apply_overrides(|name| (name == "TRIDENT_BOT_RAID_FILE_PATH").then(|| "/data/raid_{channel}.txt".to_owned()));
+        assert_eq!(config.raid_file_path("other_channel"), "/data/raid_other_channel.txt");
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_keys() {
+        assert!(matches!(Config::parse("config.toml", "login = \"bot\""), Err(ConfigError::Parse(_, _))));
+        assert!(matches!(
+            Config::parse("config.toml", &format!("{}\nchanel = \"typo\"", MINIMAL_CONFIG)),
+            Err(ConfigError::Parse(_, _))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let mut config: Config = Config::parse("config.toml", &format!("{}\nmax_message_length = 600", MINIMAL_CONFIG)).unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let mut config: Config = Config::parse("config.toml", MINIMAL_CONFIG).unwrap();
+        config.channels = vec!["not a channel".to_owned()];
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let mut config: Config = Config::parse("config.toml", &format!("{}\nraid_file_path = \"./raid.txt\"", MINIMAL_CONFIG)).unwrap();
+        config.channels.push("other_channel".to_owned());
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let mut config: Config = Config::parse("config.toml", &format!("{}\nmax_simulations = 0", MINIMAL_CONFIG)).unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let mut config: Config = Config::parse("config.toml", &format!("{}\ncooldown_feedback = \"whisper\"", MINIMAL_CONFIG)).unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+        config.client_id = Some("abc".to_owned());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn reads_per_command_settings() {
+        let mut config: Config = Config::parse(
+            "config.toml",
+            "
+                login = \"trident_bot\"
+                oauth_token = \"abc123\"
+                channels = [\"oskar\", \"other_channel\"]
+
+                cooldown_feedback = \"reply\"
+
+                [disabled_commands]
+                Other_Channel = [\"!Song\", \"!FISHINGE\"]
+
+                [cooldowns.\"!RollCats\"]
+                user = 15
+            "
+        ).unwrap();
+        config.validate().unwrap();
+        config.resolve_commands(&registry()).unwrap();
+
+        assert!(config.is_command_enabled("oskar", "!song"));
+        assert!(!config.is_command_enabled("other_channel", "!fish"));
+        assert!(!config.is_command_enabled("other_channel", "!song"));
+        assert!(config.is_command_enabled("other_channel", "!rolltrident"));
+        assert_eq!(config.cooldowns, HashMap::from([("!rollcats".to_owned(), CooldownSettings { global: 0, user: 15 })]));
+        assert_eq!(config.cooldown_feedback, CooldownFeedback::Reply);
+    }
+
+    #[test]
+    fn rejects_names_that_arent_commands() {
+        let mut config: Config = Config::parse("config.toml", &format!("{}\n[disabled_commands]\noskar = [\"!nope\"]", MINIMAL_CONFIG)).unwrap();
+        config.validate().unwrap();
+        assert!(matches!(config.resolve_commands(&registry()), Err(ConfigError::Invalid(message)) if message == "`disabled_commands` names `!nope`, which isn't a command"));
+
+        let mut config: Config = Config::parse("config.toml", &format!("{}\n[cooldowns.fish]\nuser = 5", MINIMAL_CONFIG)).unwrap();
+        config.validate().unwrap();
+        assert!(config.resolve_commands(&registry()).is_err());
+
+        let mut config: Config = Config::parse("config.toml", &format!("{}\n[cooldowns.\"!fish\"]\nuser = 5\n[cooldowns.\"!fishinge\"]\nuser = 10", MINIMAL_CONFIG)).unwrap();
+        config.validate().unwrap();
+        assert!(config.resolve_commands(&registry()).is_err());
+    }
+
+    fn registry() -> CommandRegistry {
+        let mut registry: CommandRegistry = CommandRegistry::new();
+        crate::commands::register_commands(&mut registry);
+        registry
+    }
+}
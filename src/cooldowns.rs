@@ -0,0 +1,195 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CooldownSettings {
+    // seconds before anyone in the channel can use the command again
+    #[serde(default)]
+    pub global: u64,
+    // seconds before the same chatter can use the command again
+    #[serde(default)]
+    pub user: u64,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CooldownFeedback {
+    // commands on cooldown are ignored
+    #[default]
+    Silent,
+    // the bot whispers the chatter the time left, through the Helix API
+    Whisper,
+    // the bot replies to the chatter's message with the time left, in public chat
+    Reply,
+}
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+pub struct Cooldowns<C: Clock> {
+    clock: C,
+    settings: HashMap<String, CooldownSettings>,
+    // (channel, command) -> last use
+    global_uses: HashMap<(String, String), Instant>,
+    // (channel, command, user id) -> last use
+    user_uses: HashMap<(String, String, String), Instant>,
+}
+
+impl<C: Clock> Cooldowns<C> {
+    pub fn new(clock: C, settings: HashMap<String, CooldownSettings>) -> Self {
+        Cooldowns { clock, settings, global_uses: HashMap::new(), user_uses: HashMap::new() }
+    }
+
+    // Records a use of the command and returns Ok, or returns how long the chatter still has to wait.
+    // Exempt chatters (moderators and the broadcaster) are never put on cooldown and don't start one either.
+    pub fn try_use(&mut self, channel: &str, command_name: &str, user_id: &str, exempt: bool) -> Result<(), Duration> {
+        if exempt {
+            return Ok(());
+        }
+
+        let settings: CooldownSettings = match self.settings.get(command_name) {
+            Some(settings) => *settings,
+            None => return Ok(()),
+        };
+
+        let now: Instant = self.clock.now();
+        let global_key: (String, String) = (channel.to_owned(), command_name.to_owned());
+        let user_key: (String, String, String) = (channel.to_owned(), command_name.to_owned(), user_id.to_owned());
+
+        let global_remaining: Duration = remaining(self.global_uses.get(&global_key), settings.global, now);
+        let user_remaining: Duration = remaining(self.user_uses.get(&user_key), settings.user, now);
+        let wait: Duration = global_remaining.max(user_remaining);
+
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        if settings.global > 0 {
+            self.global_uses.insert(global_key, now);
+        }
+
+        if settings.user > 0 {
+            self.user_uses.insert(user_key, now);
+        }
+
+        Ok(())
+    }
+}
+
+fn remaining(last_use: Option<&Instant>, cooldown_seconds: u64, now: Instant) -> Duration {
+    match last_use {
+        Some(last_use) => Duration::from_secs(cooldown_seconds).saturating_sub(now.saturating_duration_since(*last_use)),
+        None => Duration::ZERO,
+    }
+}
+
+pub fn default_cooldowns() -> HashMap<String, CooldownSettings> {
+    HashMap::from([
+        ("!thunderodds".to_owned(), CooldownSettings { global: 10, user: 60 }),
+        ("!combo".to_owned(), CooldownSettings { global: 0, user: 30 }),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct ManualClock {
+        start: Instant,
+        elapsed: Cell<Duration>,
+    }
+
+    impl ManualClock {
+        fn advance(&self, seconds: u64) {
+            self.elapsed.set(self.elapsed.get() + Duration::from_secs(seconds));
+        }
+    }
+
+    impl Clock for &ManualClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed.get()
+        }
+    }
+
+    fn clock() -> ManualClock {
+        ManualClock { start: Instant::now(), elapsed: Cell::new(Duration::ZERO) }
+    }
+
+    fn settings(global: u64, user: u64) -> HashMap<String, CooldownSettings> {
+        HashMap::from([("!thunderodds".to_owned(), CooldownSettings { global, user })])
+    }
+
+    #[test]
+    fn global_cooldown_blocks_everyone_until_it_expires() {
+        let clock: ManualClock = clock();
+        let mut cooldowns = Cooldowns::new(&clock, settings(10, 0));
+
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "1", false), Ok(()));
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "2", false), Err(Duration::from_secs(10)));
+
+        clock.advance(4);
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "2", false), Err(Duration::from_secs(6)));
+
+        clock.advance(6);
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "2", false), Ok(()));
+    }
+
+    #[test]
+    fn user_cooldown_only_blocks_that_user() {
+        let clock: ManualClock = clock();
+        let mut cooldowns = Cooldowns::new(&clock, settings(0, 60));
+
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "1", false), Ok(()));
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "2", false), Ok(()));
+
+        clock.advance(30);
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "1", false), Err(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn the_longer_cooldown_wins() {
+        let clock: ManualClock = clock();
+        let mut cooldowns = Cooldowns::new(&clock, settings(10, 60));
+
+        cooldowns.try_use("oskar", "!thunderodds", "1", false).unwrap();
+        clock.advance(20);
+
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "1", false), Err(Duration::from_secs(40)));
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "2", false), Ok(()));
+    }
+
+    #[test]
+    fn exempt_users_skip_cooldowns() {
+        let clock: ManualClock = clock();
+        let mut cooldowns = Cooldowns::new(&clock, settings(10, 60));
+
+        cooldowns.try_use("oskar", "!thunderodds", "1", false).unwrap();
+
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "1", true), Ok(()));
+        assert_eq!(cooldowns.try_use("oskar", "!thunderodds", "mod", true), Ok(()));
+    }
+
+    #[test]
+    fn channels_and_unlisted_commands_are_independent() {
+        let clock: ManualClock = clock();
+        let mut cooldowns = Cooldowns::new(&clock, settings(10, 60));
+
+        cooldowns.try_use("oskar", "!thunderodds", "1", false).unwrap();
+
+        assert_eq!(cooldowns.try_use("other_channel", "!thunderodds", "1", false), Ok(()));
+        assert_eq!(cooldowns.try_use("oskar", "!rolltrident", "1", false), Ok(()));
+        assert_eq!(cooldowns.try_use("oskar", "!rolltrident", "1", false), Ok(()));
+    }
+}
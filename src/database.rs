@@ -0,0 +1,462 @@
+use sqlite::{Connection, State, Statement, Value};
+#[cfg(test)]
+use sqlite::ConnectionWithFullMutex;
+
+use crate::leaderboards::{Metric, Source};
+use crate::rng::RollRecord;
+
+// Every query in the bot goes through this module. Values coming from chat are always bound as
+// parameters and never formatted into the query string.
+
+fn prepare<'a>(sqlite_connection: &'a Connection, query: &str, parameters: Vec<(&str, Value)>) -> Result<Statement<'a>, sqlite::Error> {
+    let mut statement: Statement = sqlite_connection.prepare(query)?;
+    statement.bind_iter::<_, (_, Value)>(parameters)?;
+
+    Ok(statement)
+}
+
+fn execute(sqlite_connection: &Connection, query: &str, parameters: Vec<(&str, Value)>) -> Result<(), sqlite::Error> {
+    let mut statement: Statement = prepare(sqlite_connection, query, parameters)?;
+    while statement.next()? != State::Done {}
+
+    Ok(())
+}
+
+// Reads every row as a (text, number) pair, like a chatter and how many fish they caught.
+fn read_pairs(mut statement: Statement, text_column: &str, number_column: &str) -> Result<Vec<(String, i64)>, sqlite::Error> {
+    let mut rows: Vec<(String, i64)> = Vec::new();
+
+    while let State::Row = statement.next()? {
+        rows.push((statement.read::<String, _>(text_column)?, statement.read::<i64, _>(number_column)?));
+    }
+
+    Ok(rows)
+}
+
+// Rows recorded before the bot supported multiple channels have an empty channel; they belong to `channel`.
+pub fn claim_unowned_rows(sqlite_connection: &Connection, channel: &str) -> Result<(), sqlite::Error> {
+    for table in ["commands", "users", "rolls"] {
+        execute(
+            sqlite_connection,
+            &format!("UPDATE OR IGNORE {} SET channel = :channel WHERE channel = '';", table),
+            vec![(":channel", channel.into())]
+        )?;
+    }
+
+    Ok(())
+}
+
+// Every roll command's result goes through here, see registry::finish_roll.
+pub fn add_roll(sqlite_connection: &Connection, channel: &str, user_id: &str, command: &str, record: &RollRecord, seed: u64, unix_time: i64) -> Result<(), sqlite::Error> {
+    execute(
+        sqlite_connection,
+        "INSERT INTO rolls (channel, user_id, command, score, result, seed, unix_time) VALUES (:channel, :user_id, :command, :score, :result, :seed, :unix_time);",
+        vec![
+            (":channel", channel.into()),
+            (":user_id", user_id.into()),
+            (":command", command.into()),
+            (":score", record.score.map_or(Value::Null, Value::Float)),
+            (":result", record.result.to_string().into()),
+            (":seed", (seed as i64).into()),
+            (":unix_time", unix_time.into()),
+        ]
+    )
+}
+
+// One row per (item, category, count) caught by a single !fish.
+pub fn add_catches(sqlite_connection: &Connection, channel: &str, user_id: &str, catches: &[(String, &str, u64)], seed: u64, unix_time: i64) -> Result<(), sqlite::Error> {
+    for (item, category, count) in catches {
+        execute(
+            sqlite_connection,
+            "INSERT INTO catches (channel, user_id, item, category, count, seed, unix_time) VALUES (:channel, :user_id, :item, :category, :count, :seed, :unix_time);",
+            vec![
+                (":channel", channel.into()),
+                (":user_id", user_id.into()),
+                (":item", item.as_str().into()),
+                (":category", (*category).into()),
+                (":count", (*count as i64).into()),
+                (":seed", (seed as i64).into()),
+                (":unix_time", unix_time.into()),
+            ]
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn add_command_use(sqlite_connection: &Connection, channel: &str, command_name: &str, user_id: &str, unix_time: i64) -> Result<(), sqlite::Error> {
+    execute(
+        sqlite_connection,
+        "INSERT INTO command_uses (channel, user_id, name, unix_time) VALUES (:channel, :user_id, :name, :unix_time);",
+        vec![(":channel", channel.into()), (":user_id", user_id.into()), (":name", command_name.into()), (":unix_time", unix_time.into())]
+    )?;
+
+    // one statement, so messages handled at the same time can't both insert the row
+    execute(
+        sqlite_connection,
+        "INSERT INTO commands (channel, name, uses, user_id) VALUES (:channel, :name, 1, :user_id) ON CONFLICT (channel, name, user_id) DO UPDATE SET uses = uses + 1;",
+        vec![(":channel", channel.into()), (":name", command_name.into()), (":user_id", user_id.into())]
+    )
+}
+
+// Chatters are first seen at their first message after migration 6; older chatters get the time of
+// their first roll, if they have one.
+pub fn add_user_message(sqlite_connection: &Connection, channel: &str, user_id: &str, login: &str, display_name: &str, unix_time: i64) -> Result<(), sqlite::Error> {
+    update_names(sqlite_connection, user_id, login, display_name, unix_time)?;
+
+    execute(
+        sqlite_connection,
+        "INSERT INTO messages (channel, user_id, unix_time) VALUES (:channel, :user_id, :unix_time);",
+        vec![(":channel", channel.into()), (":user_id", user_id.into()), (":unix_time", unix_time.into())]
+    )?;
+
+    execute(
+        sqlite_connection,
+        "INSERT INTO users (channel, user_id, login, display_name, messages, first_seen) VALUES (:channel, :user_id, :login, :display_name, 1, :unix_time) ON CONFLICT (channel, user_id) DO UPDATE SET messages = messages + 1;",
+        vec![(":channel", channel.into()), (":user_id", user_id.into()), (":login", login.into()), (":display_name", display_name.into()), (":unix_time", unix_time.into())]
+    )
+}
+
+// Twitch names can change, so every new login and display name goes into user_names and replaces
+// the old one in every channel. Both checks happen inside their statement, so two messages from the
+// same chatter at once can't add the name twice.
+fn update_names(sqlite_connection: &Connection, user_id: &str, login: &str, display_name: &str, unix_time: i64) -> Result<(), sqlite::Error> {
+    execute(
+        sqlite_connection,
+        "INSERT INTO user_names (user_id, login, display_name, unix_time) SELECT :user_id, :login, :display_name, :unix_time
+             WHERE NOT EXISTS (SELECT 1 FROM (SELECT login, display_name FROM user_names WHERE user_id = :user_id ORDER BY id DESC LIMIT 1) AS latest WHERE latest.login = :login AND latest.display_name = :display_name);",
+        vec![(":user_id", user_id.into()), (":login", login.into()), (":display_name", display_name.into()), (":unix_time", unix_time.into())]
+    )?;
+    execute(
+        sqlite_connection,
+        "UPDATE users SET login = :login, display_name = :display_name WHERE user_id = :user_id AND (login IS NOT :login OR display_name IS NOT :display_name);",
+        vec![(":user_id", user_id.into()), (":login", login.into()), (":display_name", display_name.into())]
+    )
+}
+
+// Everything !stats shows about a chatter. Counts and rolls are for one channel.
+#[derive(Debug, PartialEq)]
+pub struct Profile {
+    pub display_name: String,
+    pub messages: i64,
+    // 1 for whoever sent the most messages
+    pub rank: i64,
+    pub favourite_command: Option<(String, i64)>,
+    pub best_trident: Option<i64>,
+    pub worst_trident: Option<i64>,
+    pub best_gunpowder: Option<i64>,
+    pub first_seen: Option<i64>,
+}
+
+// None if the chatter hasn't sent a message in the channel.
+pub fn profile(sqlite_connection: &Connection, channel: &str, user_id: &str) -> Result<Option<Profile>, sqlite::Error> {
+    let parameters = || vec![(":channel", channel.into()), (":user_id", user_id.into())];
+
+    let mut statement: Statement = prepare(
+        sqlite_connection,
+        "SELECT display_name, messages, first_seen, (SELECT COUNT(*) + 1 FROM users AS others WHERE others.channel = users.channel AND others.messages > users.messages) AS rank FROM users WHERE channel = :channel AND user_id = :user_id;",
+        parameters()
+    )?;
+    if statement.next()? == State::Done {
+        return Ok(None);
+    }
+    let display_name: String = statement.read::<String, _>("display_name")?;
+    let messages: i64 = statement.read::<i64, _>("messages")?;
+    let first_seen: Option<i64> = statement.read::<Option<i64>, _>("first_seen")?;
+    let rank: i64 = statement.read::<i64, _>("rank")?;
+
+    let favourite_command: Option<(String, i64)> = read_pairs(
+        prepare(
+            sqlite_connection,
+            "SELECT name, SUM(uses) AS total_uses FROM commands WHERE channel = :channel AND user_id = :user_id GROUP BY name ORDER BY total_uses DESC, name ASC LIMIT 1;",
+            parameters()
+        )?,
+        "name",
+        "total_uses"
+    )?.pop();
+
+    let (best_trident, worst_trident): (Option<i64>, Option<i64>) = best_and_worst_roll(sqlite_connection, channel, user_id, "!rolltrident")?;
+    let (best_gunpowder, _): (Option<i64>, Option<i64>) = best_and_worst_roll(sqlite_connection, channel, user_id, "!rollgp")?;
+
+    Ok(Some(Profile { display_name, messages, rank, favourite_command, best_trident, worst_trident, best_gunpowder, first_seen }))
+}
+
+// The highest and lowest score someone rolled with a command, None if they never rolled it.
+pub fn best_and_worst_roll(sqlite_connection: &Connection, channel: &str, user_id: &str, command: &str) -> Result<(Option<i64>, Option<i64>), sqlite::Error> {
+    let mut statement: Statement = prepare(
+        sqlite_connection,
+        "SELECT CAST(MAX(score) AS INTEGER) AS best, CAST(MIN(score) AS INTEGER) AS worst FROM rolls WHERE channel = :channel AND user_id = :user_id AND command = :command;",
+        vec![(":channel", channel.into()), (":user_id", user_id.into()), (":command", command.into())]
+    )?;
+    statement.next()?;
+
+    Ok((statement.read::<Option<i64>, _>("best")?, statement.read::<Option<i64>, _>("worst")?))
+}
+
+// The user id of the chatter in the channel with this login or display name, ignoring case.
+// Chatters who renamed are still found by their old logins.
+pub fn find_user_id(sqlite_connection: &Connection, channel: &str, name: &str) -> Result<Option<String>, sqlite::Error> {
+    let queries: [&str; 2] = [
+        "SELECT CAST(user_id AS TEXT) AS user_id FROM users WHERE channel = :channel AND (login = :login OR display_name = :login COLLATE NOCASE) ORDER BY login = :login DESC LIMIT 1;",
+        "SELECT CAST(user_names.user_id AS TEXT) AS user_id FROM user_names INNER JOIN users on user_names.user_id = users.user_id AND users.channel = :channel WHERE user_names.login = :login ORDER BY user_names.id DESC LIMIT 1;",
+    ];
+
+    for query in queries {
+        let mut statement: Statement = prepare(sqlite_connection, query, vec![(":channel", channel.into()), (":login", name.to_lowercase().into())])?;
+        if let State::Row = statement.next()? {
+            return statement.read::<String, _>("user_id").map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+// Everyone's standing on a leaderboard as (id, display name, value), best first. Chatters are
+// ranked by user id, commands by name. Boards only count what happened from `since_unix_time` on;
+// without one, messages and command uses come from the all time counters. Roll boards rank ties
+// by who got there first.
+pub fn leaderboard(sqlite_connection: &Connection, channel: &str, source: &Source, metric: Metric, lower_wins: bool, since_unix_time: Option<i64>) -> Result<Vec<(String, String, f64)>, sqlite::Error> {
+    let mut parameters: Vec<(&str, Value)> = vec![(":channel", channel.into())];
+    if let Some(since) = since_unix_time {
+        parameters.push((":since", since.into()));
+    }
+    let since: &str = if since_unix_time.is_some() { " AND unix_time >= :since" } else { "" };
+
+    // the table the chatters are ranked from, with the value of their row group and the rows it counts
+    let (table, value, filter): (&str, &str, String) = match source {
+        Source::Rolls(command) => {
+            let (value, order, filter): (&str, &str, &str) = match (metric, lower_wins) {
+                (Metric::Best, false) | (Metric::Worst, true) => ("MAX(score)", "DESC", "score IS NOT NULL"),
+                (Metric::Best, true) | (Metric::Worst, false) => ("MIN(score)", "ASC", "score IS NOT NULL"),
+                (Metric::CountOf(_), _) => ("COUNT(*)", "DESC", "score = :score"),
+                (Metric::Sum, _) => ("SUM(score)", "DESC", "score IS NOT NULL"),
+            };
+            parameters.push((":command", (*command).into()));
+            if let Metric::CountOf(score) = metric {
+                parameters.push((":score", score.into()));
+            }
+
+            let query: String = format!(
+                "SELECT CAST(rolls.user_id AS TEXT) AS id, users.display_name AS name, CAST({} AS REAL) AS value FROM rolls INNER JOIN users on rolls.user_id = users.user_id AND rolls.channel = users.channel WHERE rolls.channel = :channel AND command = :command{} AND {} GROUP BY rolls.user_id ORDER BY value {}, MIN(rolls.id) ASC;",
+                value, since, filter, order
+            );
+            return read_standings(sqlite_connection, &query, parameters);
+        },
+        Source::Messages if since_unix_time.is_none() => {
+            let query: &str = "SELECT CAST(user_id AS TEXT) AS id, display_name AS name, CAST(messages AS REAL) AS value FROM users WHERE channel = :channel ORDER BY value DESC;";
+            return read_standings(sqlite_connection, query, parameters);
+        },
+        Source::Messages => ("messages", "COUNT(*)", since.to_owned()),
+        Source::CommandUses if since_unix_time.is_none() => ("commands", "SUM(uses)", String::new()),
+        Source::CommandUses => ("command_uses", "COUNT(*)", since.to_owned()),
+        Source::UsesOf(command) => {
+            // commands are stored as "emark_rollgp"
+            parameters.push((":name", command.replace("!", "emark_").into()));
+            match since_unix_time {
+                None => ("commands", "SUM(uses)", " AND name = :name".to_owned()),
+                Some(_) => ("command_uses", "COUNT(*)", format!(" AND name = :name{}", since)),
+            }
+        },
+        Source::Catches(category) => {
+            parameters.push((":category", (*category).into()));
+            ("catches", "SUM(count)", format!(" AND category = :category{}", since))
+        },
+        Source::Commands => {
+            let query: String = match since_unix_time {
+                None => "SELECT name AS id, name, CAST(SUM(uses) AS REAL) AS value FROM commands WHERE channel = :channel GROUP BY name ORDER BY value DESC;".to_owned(),
+                Some(_) => format!("SELECT name AS id, name, CAST(COUNT(*) AS REAL) AS value FROM command_uses WHERE channel = :channel{} GROUP BY name ORDER BY value DESC;", since),
+            };
+            return read_standings(sqlite_connection, &query, parameters);
+        },
+    };
+
+    let query: String = format!(
+        "SELECT CAST({table}.user_id AS TEXT) AS id, users.display_name AS name, CAST({value} AS REAL) AS value FROM {table} INNER JOIN users on {table}.user_id = users.user_id AND {table}.channel = users.channel WHERE {table}.channel = :channel{filter} GROUP BY {table}.user_id ORDER BY value DESC;",
+        table = table, value = value, filter = filter
+    );
+    read_standings(sqlite_connection, &query, parameters)
+}
+
+fn read_standings(sqlite_connection: &Connection, query: &str, parameters: Vec<(&str, Value)>) -> Result<Vec<(String, String, f64)>, sqlite::Error> {
+    let mut statement: Statement = prepare(sqlite_connection, query, parameters)?;
+    let mut standings: Vec<(String, String, f64)> = Vec::new();
+    while let State::Row = statement.next()? {
+        standings.push((statement.read::<String, _>("id")?, statement.read::<String, _>("name")?, statement.read::<f64, _>("value")?));
+    }
+
+    Ok(standings)
+}
+
+// The scores of someone's latest rolls of a command, newest first. Rolls without a score are None.
+pub fn recent_roll_scores(sqlite_connection: &Connection, channel: &str, user_id: &str, command: &str, limit: i64) -> Result<Vec<Option<f64>>, sqlite::Error> {
+    let mut statement: Statement = prepare(
+        sqlite_connection,
+        "SELECT score FROM rolls WHERE channel = :channel AND user_id = :user_id AND command = :command ORDER BY unix_time DESC, id DESC LIMIT :limit;",
+        vec![(":channel", channel.into()), (":user_id", user_id.into()), (":command", command.into()), (":limit", limit.into())]
+    )?;
+
+    let mut scores: Vec<Option<f64>> = Vec::new();
+    while let State::Row = statement.next()? {
+        scores.push(statement.read::<Option<f64>, _>("score")?);
+    }
+
+    Ok(scores)
+}
+
+// How many times someone rolled a command, with their best and average score.
+pub fn roll_summary(sqlite_connection: &Connection, channel: &str, user_id: &str, command: &str, lower_wins: bool) -> Result<(i64, Option<f64>, Option<f64>), sqlite::Error> {
+    let best: &str = if lower_wins { "MIN(score)" } else { "MAX(score)" };
+    let mut statement: Statement = prepare(
+        sqlite_connection,
+        &format!("SELECT COUNT(*) AS rolls, {} AS best, AVG(score) AS average FROM rolls WHERE channel = :channel AND user_id = :user_id AND command = :command;", best),
+        vec![(":channel", channel.into()), (":user_id", user_id.into()), (":command", command.into())]
+    )?;
+
+    statement.next()?;
+    Ok((statement.read::<i64, _>("rolls")?, statement.read::<Option<f64>, _>("best")?, statement.read::<Option<f64>, _>("average")?))
+}
+
+// A migrated in-memory database for the tests of every module that reads or writes chat data.
+#[cfg(test)]
+pub fn test_connection() -> ConnectionWithFullMutex {
+    let sqlite_connection: ConnectionWithFullMutex = Connection::open_with_full_mutex(":memory:").unwrap();
+    crate::migrations::migrate(&sqlite_connection).unwrap();
+    sqlite_connection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+
+    const CHANNEL: &str = "oskar";
+    const INJECTION: &str = "x'; DROP TABLE users;--";
+
+    fn count_rows(sqlite_connection: &Connection, table: &str) -> i64 {
+        let mut statement: Statement = sqlite_connection.prepare(format!("SELECT COUNT(*) AS rows FROM {};", table)).unwrap();
+        statement.next().unwrap();
+        statement.read::<i64, _>("rows").unwrap()
+    }
+
+    fn trident_roll(durability: u32) -> RollRecord {
+        RollRecord { score: Some(durability as f64), result: serde_json::json!({ "durability": durability }) }
+    }
+
+    fn best_trident_rolls(sqlite_connection: &Connection, channel: &str) -> Vec<(String, String, f64)> {
+        leaderboard(sqlite_connection, channel, &Source::Rolls("!rolltrident"), Metric::Best, false, None).unwrap()
+    }
+
+    #[test]
+    fn commandstats_does_not_run_injected_sql() {
+        let sqlite_connection: ConnectionWithFullMutex = test_connection();
+        add_user_message(&sqlite_connection, CHANNEL, "1", "chatter", "chatter", 1).unwrap();
+        add_command_use(&sqlite_connection, CHANNEL, "emark_rolltrident", "1", 1).unwrap();
+
+        let result = leaderboard(&sqlite_connection, CHANNEL, &Source::UsesOf(INJECTION.to_owned()), Metric::Sum, false, None);
+
+        assert!(result.is_ok());
+        assert_eq!(count_rows(&sqlite_connection, "users"), 1);
+        assert_eq!(count_rows(&sqlite_connection, "commands"), 1);
+    }
+
+    #[test]
+    fn display_name_is_stored_verbatim() {
+        let sqlite_connection: ConnectionWithFullMutex = test_connection();
+        add_user_message(&sqlite_connection, CHANNEL, "1", INJECTION, INJECTION, 1).unwrap();
+        add_user_message(&sqlite_connection, CHANNEL, "1", INJECTION, INJECTION, 1).unwrap();
+
+        assert_eq!(leaderboard(&sqlite_connection, CHANNEL, &Source::Messages, Metric::Sum, false, None).unwrap(), vec![("1".to_owned(), INJECTION.to_owned(), 2.0)]);
+    }
+
+    #[test]
+    fn command_name_is_stored_verbatim() {
+        let sqlite_connection: ConnectionWithFullMutex = test_connection();
+        add_user_message(&sqlite_connection, CHANNEL, "1", "chatter", "chatter", 1).unwrap();
+        add_command_use(&sqlite_connection, CHANNEL, INJECTION, "1", 1).unwrap();
+
+        assert_eq!(
+            leaderboard(&sqlite_connection, CHANNEL, &Source::Commands, Metric::Sum, false, None).unwrap(),
+            vec![(INJECTION.to_owned(), INJECTION.to_owned(), 1.0)]
+        );
+        assert_eq!(count_rows(&sqlite_connection, "users"), 1);
+    }
+
+    #[test]
+    fn command_uses_are_counted_per_user() {
+        let sqlite_connection: ConnectionWithFullMutex = test_connection();
+        add_user_message(&sqlite_connection, CHANNEL, "1", "first", "first", 1).unwrap();
+        add_user_message(&sqlite_connection, CHANNEL, "2", "second", "second", 1).unwrap();
+        add_command_use(&sqlite_connection, CHANNEL, "emark_rollgp", "1", 1).unwrap();
+        add_command_use(&sqlite_connection, CHANNEL, "emark_rollgp", "1", 1).unwrap();
+        add_command_use(&sqlite_connection, CHANNEL, "emark_rollgp", "2", 1).unwrap();
+
+        assert_eq!(
+            leaderboard(&sqlite_connection, CHANNEL, &Source::UsesOf("!rollgp".to_owned()), Metric::Sum, false, Some(0)).unwrap(),
+            vec![("1".to_owned(), "first".to_owned(), 2.0), ("2".to_owned(), "second".to_owned(), 1.0)]
+        );
+        assert_eq!(
+            leaderboard(&sqlite_connection, CHANNEL, &Source::CommandUses, Metric::Sum, false, None).unwrap(),
+            vec![("1".to_owned(), "first".to_owned(), 2.0), ("2".to_owned(), "second".to_owned(), 1.0)]
+        );
+    }
+
+    // The bot handles every message in its own task on one shared connection.
+    #[test]
+    fn messages_at_the_same_time_are_all_counted() {
+        let sqlite_connection: Arc<ConnectionWithFullMutex> = Arc::new(test_connection());
+
+        let threads: Vec<JoinHandle<()>> = (0..8).map(|i| {
+            let sqlite_connection: Arc<ConnectionWithFullMutex> = sqlite_connection.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    let name: &str = if i % 2 == 0 { "chatter" } else { "Chatter" };
+                    add_user_message(&sqlite_connection, CHANNEL, "1", "chatter", name, 1).unwrap();
+                    add_command_use(&sqlite_connection, CHANNEL, "emark_rollgp", "1", 1).unwrap();
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(leaderboard(&sqlite_connection, CHANNEL, &Source::Messages, Metric::Sum, false, None).unwrap()[0].2, 200.0);
+        assert_eq!(leaderboard(&sqlite_connection, CHANNEL, &Source::CommandUses, Metric::Sum, false, None).unwrap()[0].2, 200.0);
+        assert_eq!(count_rows(&sqlite_connection, "commands"), 1);
+    }
+
+    #[test]
+    fn profiles_read_the_rolls_of_their_channel() {
+        let sqlite_connection: ConnectionWithFullMutex = test_connection();
+        add_user_message(&sqlite_connection, CHANNEL, "1", "chatter", "chatter", 1).unwrap();
+        add_roll(&sqlite_connection, CHANNEL, "1", "!rolltrident", &trident_roll(250), 1, 1).unwrap();
+        add_roll(&sqlite_connection, CHANNEL, "1", "!rolltrident", &trident_roll(3), 1, 1).unwrap();
+        add_roll(&sqlite_connection, "other_channel", "1", "!rolltrident", &trident_roll(0), 1, 1).unwrap();
+
+        let profile: Profile = profile(&sqlite_connection, CHANNEL, "1").unwrap().unwrap();
+        assert_eq!((profile.best_trident, profile.worst_trident, profile.best_gunpowder), (Some(250), Some(3), None));
+    }
+
+    #[test]
+    fn leaderboards_are_kept_per_channel() {
+        let sqlite_connection: ConnectionWithFullMutex = test_connection();
+        add_user_message(&sqlite_connection, CHANNEL, "1", "chatter", "chatter", 1).unwrap();
+        add_user_message(&sqlite_connection, "other_channel", "1", "chatter", "chatter", 1).unwrap();
+        add_roll(&sqlite_connection, CHANNEL, "1", "!rolltrident", &trident_roll(250), 1, 1).unwrap();
+        add_roll(&sqlite_connection, "other_channel", "1", "!rolltrident", &trident_roll(3), 1, 1).unwrap();
+
+        assert_eq!(best_trident_rolls(&sqlite_connection, CHANNEL), vec![("1".to_owned(), "chatter".to_owned(), 250.0)]);
+        assert_eq!(best_trident_rolls(&sqlite_connection, "other_channel"), vec![("1".to_owned(), "chatter".to_owned(), 3.0)]);
+    }
+
+    #[test]
+    fn unowned_rows_are_claimed_by_a_channel() {
+        let sqlite_connection: ConnectionWithFullMutex = test_connection();
+        add_user_message(&sqlite_connection, "", "1", "chatter", "chatter", 1).unwrap();
+        add_roll(&sqlite_connection, "", "1", "!rolltrident", &trident_roll(100), 1, 1).unwrap();
+
+        claim_unowned_rows(&sqlite_connection, CHANNEL).unwrap();
+
+        assert_eq!(best_trident_rolls(&sqlite_connection, CHANNEL), vec![("1".to_owned(), "chatter".to_owned(), 100.0)]);
+    }
+}
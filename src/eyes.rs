@@ -0,0 +1,49 @@
+use rand::Rng;
+
+use crate::math::{bernoullis_scheme, binomial_tail};
+
+// Every one of the 12 end portal frames generates with an eye in it when a random float is above
+// 0.9, so the eye count is binomial with p = 0.1. This hasn't changed since eyes were added in Beta
+// 1.9, so it holds for every version.
+pub const FRAMES: u32 = 12;
+const EYE_ODDS: f64 = 0.1;
+
+pub fn roll_eyes<R: Rng>(rng: &mut R) -> u32 {
+    (0..FRAMES).filter(|_| rng.gen::<f32>() > 0.9).count() as u32
+}
+
+// odds of a portal with exactly `eyes` eyes
+pub fn eye_odds(eyes: u32) -> f64 {
+    bernoullis_scheme(FRAMES as u128, eyes as u128, EYE_ODDS)
+}
+
+// odds of a portal with `eyes` eyes or more
+pub fn eyes_or_more_odds(eyes: u32) -> f64 {
+    binomial_tail(FRAMES as u128, eyes as u128, EYE_ODDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    #[test]
+    fn odds_match_the_rolls() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let portals: u32 = 100_000;
+        let mut counts: [u32; 13] = [0; 13];
+        for _ in 0..portals {
+            counts[roll_eyes(&mut rng) as usize] += 1;
+        }
+
+        for eyes in 0..=3 {
+            let expected: f64 = eye_odds(eyes) * portals as f64;
+            assert!((counts[eyes as usize] as f64 - expected).abs() < 5.0 * expected.sqrt(), "{} eyes: {} vs {}", eyes, counts[eyes as usize], expected);
+        }
+
+        assert!(((0..=FRAMES).map(eye_odds).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!((eye_odds(12) / 1e-12 - 1.0).abs() < 1e-9);
+        assert_eq!(eyes_or_more_odds(0), 1.0);
+    }
+}
@@ -0,0 +1,142 @@
+use rand::Rng;
+use std::ops::RangeInclusive;
+
+use crate::loot::{loot_tables, ItemStack, LootContext};
+
+// Bobber timing from 1.16's FishingBobberEntity, in ticks. Once the bobber lands, a fish is lured
+// after 100 to 600 ticks minus 100 per Lure level, and then takes 20 to 80 more ticks to bite. A
+// lure time of 0 or less is rolled again on the next tick. Rain and a covered sky also change the
+// lure countdown; the simulator fishes under a clear sky.
+const LURE_TIME: RangeInclusive<i64> = 100..=600;
+const LURE_TIME_PER_LEVEL: i64 = 100;
+const BITE_TIME: RangeInclusive<u64> = 20..=80;
+
+const FISHING: &str = "gameplay/fishing";
+const FISH: &str = "gameplay/fishing/fish";
+const TREASURE: &str = "gameplay/fishing/treasure";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FishingRod {
+    pub luck_of_the_sea: u32,
+    pub lure: u32,
+    // Since 1.16 treasure only bites in a 5x4x5 area of open water around the bobber.
+    pub open_water: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CatchCategory {
+    Fish,
+    Treasure,
+    Junk,
+}
+
+impl CatchCategory {
+    pub const ALL: [CatchCategory; 3] = [CatchCategory::Fish, CatchCategory::Treasure, CatchCategory::Junk];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CatchCategory::Fish => "fish",
+            CatchCategory::Treasure => "treasure",
+            CatchCategory::Junk => "junk",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        CatchCategory::ALL.into_iter().find(|category| category.name() == name.to_lowercase())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Catch {
+    pub stack: ItemStack,
+    pub category: CatchCategory,
+    // from the bobber landing to the bite
+    pub ticks: u64,
+}
+
+pub fn cast<R: Rng>(rod: &FishingRod, rng: &mut R) -> Catch {
+    let ticks: u64 = time_to_bite(rod.lure, rng);
+    let context: LootContext = LootContext::fishing(rod.luck_of_the_sea as f64, rod.open_water);
+    // the fishing table always gives exactly one stack
+    let stack: ItemStack = loot_tables().roll(FISHING, &context, rng).remove(0);
+
+    Catch { category: category(&stack), stack, ticks }
+}
+
+pub fn time_to_bite<R: Rng>(lure: u32, rng: &mut R) -> u64 {
+    let mut ticks: u64 = 0;
+
+    loop {
+        let lure_time: i64 = rng.gen_range(LURE_TIME) - LURE_TIME_PER_LEVEL * lure as i64;
+        if lure_time > 0 {
+            return ticks + lure_time as u64 + rng.gen_range(BITE_TIME);
+        }
+        ticks += 1;
+    }
+}
+
+// Average ticks from the bobber landing to the bite.
+pub fn expected_time_to_bite(lure: u32) -> f64 {
+    let lure_times: Vec<i64> = LURE_TIME.map(|time| time - LURE_TIME_PER_LEVEL * lure as i64).filter(|time| *time > 0).collect();
+    let lured: f64 = lure_times.len() as f64 / LURE_TIME.count() as f64;
+    // every roll of 0 or less costs a tick, and the rolls until a positive one are geometric
+    let rerolls: f64 = (1.0 - lured) / lured;
+
+    rerolls + lure_times.iter().sum::<i64>() as f64 / lure_times.len() as f64 + (BITE_TIME.start() + BITE_TIME.end()) as f64 / 2.0
+}
+
+// Enchanted fishing rods only come from the treasure table, plain ones only from the junk table.
+fn category(stack: &ItemStack) -> CatchCategory {
+    if loot_tables().items(FISH).contains(&stack.item) {
+        CatchCategory::Fish
+    } else if stack.enchanted || (loot_tables().items(TREASURE).contains(&stack.item) && stack.item != "minecraft:fishing_rod") {
+        CatchCategory::Treasure
+    } else {
+        CatchCategory::Junk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    #[test]
+    fn lure_shortens_the_wait() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let casts: u64 = 20_000;
+
+        // no Lure: 350 ticks to lure plus 50 to bite
+        assert_eq!(expected_time_to_bite(0), 400.0);
+
+        for lure in 0..=3 {
+            let waited: f64 = (0..casts).map(|_| time_to_bite(lure, &mut rng) as f64).sum::<f64>() / casts as f64;
+            let expected: f64 = expected_time_to_bite(lure);
+
+            // the spread is below 150 ticks
+            assert!((waited - expected).abs() < 5.0 * 150.0 / (casts as f64).sqrt(), "lure {}: {} vs simulated {}", lure, expected, waited);
+        }
+
+        assert!(expected_time_to_bite(3) < expected_time_to_bite(2));
+    }
+
+    #[test]
+    fn catches_are_sorted_into_categories() {
+        let stack = |item: &str, enchanted: bool| ItemStack { item: format!("minecraft:{}", item), count: 1, enchanted };
+
+        assert_eq!(category(&stack("cod", false)), CatchCategory::Fish);
+        assert_eq!(category(&stack("saddle", false)), CatchCategory::Treasure);
+        assert_eq!(category(&stack("fishing_rod", true)), CatchCategory::Treasure);
+        assert_eq!(category(&stack("fishing_rod", false)), CatchCategory::Junk);
+        assert_eq!(category(&stack("bowl", false)), CatchCategory::Junk);
+    }
+
+    #[test]
+    fn closed_water_never_catches_treasure() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let rod: FishingRod = FishingRod { luck_of_the_sea: 3, lure: 3, open_water: false };
+
+        assert!((0..2_000).all(|_| cast(&rod, &mut rng).category != CatchCategory::Treasure));
+    }
+}
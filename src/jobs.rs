@@ -0,0 +1,142 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::registry::{CommandError, CommandFuture, CommandResult};
+
+pub type JobFuture<T> = Pin<Box<dyn Future<Output = Result<T, CommandError>> + Send>>;
+
+// Runs CPU-heavy command handlers and database work on tokio's blocking thread pool, so neither holds up
+// the chat loop. At most `max_jobs` simulations run at once; when they're all busy new ones are turned down.
+// Queries aren't limited, since the connection's mutex already runs them one at a time.
+pub struct JobPool {
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+impl JobPool {
+    pub fn new(max_jobs: usize, timeout: Duration) -> Self {
+        JobPool { permits: Arc::new(Semaphore::new(max_jobs)), timeout }
+    }
+
+    pub fn run(&self, handler: fn(Vec<&str>) -> CommandResult, args: Vec<String>) -> CommandFuture<'static> {
+        self.run_job(move || handler(args.iter().map(|arg| arg.as_str()).collect()))
+    }
+
+    // Blocking tasks can't be cancelled, so a job that times out keeps its slot until it really finishes.
+    // That way a flood of slow commands can't pile up more work than the limit allows.
+    pub fn run_job<T: Send + 'static, F: FnOnce() -> Result<T, CommandError> + Send + 'static>(&self, job: F) -> JobFuture<T> {
+        let permits: Arc<Semaphore> = self.permits.clone();
+        let timeout: Duration = self.timeout;
+
+        Box::pin(async move {
+            let permit: OwnedSemaphorePermit = match permits.try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    return Err(CommandError::Message("Error: Too many simulations are running, try again in a moment.".to_owned()));
+                }
+            };
+
+            run_blocking(job, Some(permit), timeout, "simulation").await
+        })
+    }
+
+    // For handlers that only read or write the database.
+    pub fn run_query<T: Send + 'static, F: FnOnce() -> Result<T, CommandError> + Send + 'static>(&self, query: F) -> JobFuture<T> {
+        Box::pin(run_blocking(query, None, self.timeout, "database query"))
+    }
+}
+
+async fn run_blocking<T: Send + 'static, F: FnOnce() -> Result<T, CommandError> + Send + 'static>(
+    job: F,
+    permit: Option<OwnedSemaphorePermit>,
+    timeout: Duration,
+    kind: &'static str,
+) -> Result<T, CommandError> {
+    let job = tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        job()
+    });
+
+    match tokio::time::timeout(timeout, job).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(err)) => {
+            println!("{} job error: {}", kind, err);
+            Err(CommandError::Message(format!("Error: The {} failed.", kind)))
+        },
+        Err(_) => Err(CommandError::Message(format!("Error: The {} took longer than {:?} and was stopped.", kind, timeout))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn echo(message_parts: Vec<&str>) -> CommandResult {
+        Ok(message_parts.join(" "))
+    }
+
+    fn slow(_message_parts: Vec<&str>) -> CommandResult {
+        thread::sleep(Duration::from_millis(300));
+        Ok("done".to_owned())
+    }
+
+    fn panics(_message_parts: Vec<&str>) -> CommandResult {
+        panic!("simulation bug");
+    }
+
+    #[tokio::test]
+    async fn runs_the_handler_with_its_args() {
+        let jobs: JobPool = JobPool::new(1, Duration::from_secs(5));
+
+        assert_eq!(jobs.run(echo, vec!["!rollcats".to_owned(), "10".to_owned()]).await, Ok("!rollcats 10".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn stops_waiting_for_jobs_that_take_too_long() {
+        let jobs: JobPool = JobPool::new(1, Duration::from_millis(50));
+
+        assert_eq!(
+            jobs.run(slow, Vec::new()).await,
+            Err(CommandError::Message("Error: The simulation took longer than 50ms and was stopped.".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn turns_down_jobs_over_the_limit() {
+        let jobs: JobPool = JobPool::new(1, Duration::from_secs(5));
+
+        let first = jobs.run(slow, Vec::new());
+        let second = jobs.run(slow, Vec::new());
+        let (first, second) = tokio::join!(first, second);
+
+        assert_eq!(first, Ok("done".to_owned()));
+        assert!(matches!(second, Err(CommandError::Message(_))));
+
+        // the slot is free again once the first job is done
+        assert_eq!(jobs.run(echo, vec!["again".to_owned()]).await, Ok("again".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn queries_run_while_simulations_are_full() {
+        let jobs: JobPool = JobPool::new(1, Duration::from_secs(5));
+
+        let simulation = jobs.run(slow, Vec::new());
+        let query = jobs.run_query(|| Ok("queried"));
+        let (simulation, query) = tokio::join!(simulation, query);
+
+        assert_eq!(simulation, Ok("done".to_owned()));
+        assert_eq!(query, Ok("queried"));
+    }
+
+    #[tokio::test]
+    async fn a_panicking_job_is_reported_as_an_error() {
+        let jobs: JobPool = JobPool::new(1, Duration::from_secs(5));
+
+        assert_eq!(jobs.run(panics, Vec::new()).await, Err(CommandError::Message("Error: The simulation failed.".to_owned())));
+        assert_eq!(jobs.run(echo, vec!["still works".to_owned()]).await, Ok("still works".to_owned()));
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sqlite::Connection;
+
+use crate::args::NumberArg;
+use crate::commands::{find_chatter, format_score, LOWER_SCORES_WIN};
+use crate::database;
+use crate::fishing::CatchCategory;
+use crate::registry::{self, Command, CommandContext, CommandError, CommandFuture, CommandResult};
+
+const SIZE: NumberArg<usize> = NumberArg::new("size", 1, 10);
+const DEFAULT_SIZE: usize = 3;
+
+const HOUR_MS: i64 = 3_600_000;
+
+// When each live channel's stream started, in unix time in ms. Twitch is asked for it (see
+// streams.rs), so channels are missing while they're offline or when the bot has no client id.
+static STREAM_STARTS: OnceLock<Mutex<HashMap<String, i64>>> = OnceLock::new();
+
+pub fn set_stream_start(channel: &str, unix_time: Option<i64>) {
+    let mut starts = STREAM_STARTS.get_or_init(Default::default).lock().unwrap();
+    match unix_time {
+        Some(unix_time) => starts.insert(channel.to_lowercase(), unix_time),
+        None => starts.remove(&channel.to_lowercase()),
+    };
+}
+
+fn stream_start(channel: &str) -> Option<i64> {
+    STREAM_STARTS.get()?.lock().unwrap().get(&channel.to_lowercase()).copied()
+}
+
+// What a leaderboard ranks chatters by. Roll boards can use any metric; messages, command uses
+// and catches only add up, so their boards always rank by the sum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    // everyone's luckiest roll
+    Best,
+    // everyone's unluckiest roll
+    Worst,
+    // how many rolls scored exactly this
+    CountOf(f64),
+    Sum,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    // the scores in the rolls table for one roll command
+    Rolls(&'static str),
+    Messages,
+    CommandUses,
+    // ranks the commands themselves instead of chatters
+    Commands,
+    // what was caught with !fish in one category; chatters can name another: "!fishjuicers junk"
+    Catches(&'static str),
+    // who used one command the most, which chatters have to name: "!commandstats !rollgp"
+    UsesOf(String),
+}
+
+impl Source {
+    // The source for a category or command a chatter named, if the board takes one.
+    fn named(&self, name: &str) -> Option<Source> {
+        match self {
+            Source::Catches(_) => CatchCategory::from_name(name).map(|category| Source::Catches(category.name())),
+            Source::UsesOf(_) if name.starts_with('!') => Some(Source::UsesOf(name.to_owned())),
+            _ => None,
+        }
+    }
+
+    // Fills in the "{}" in a board's title.
+    fn name(&self) -> &str {
+        match self {
+            Source::Catches(category) => category,
+            Source::UsesOf(command) => command,
+            _ => "",
+        }
+    }
+}
+
+// Windows are rolling, so "today" at 1am still counts yesterday evening. "24h", "7d" and "30d" say
+// that more plainly and work too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    LastDay,
+    LastWeek,
+    LastMonth,
+    AllTime,
+    Stream,
+}
+
+impl Window {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "today" | "24h" => Some(Window::LastDay),
+            "week" | "7d" => Some(Window::LastWeek),
+            "month" | "30d" => Some(Window::LastMonth),
+            "all" | "alltime" => Some(Window::AllTime),
+            "stream" => Some(Window::Stream),
+            _ => None,
+        }
+    }
+
+    // Goes after the board's title, like "Top 3 best trident rolls this stream".
+    fn label(self) -> &'static str {
+        match self {
+            Window::LastDay => " in the last 24 hours",
+            Window::LastWeek => " in the last 7 days",
+            Window::LastMonth => " in the last 30 days",
+            Window::AllTime => "",
+            Window::Stream => " this stream",
+        }
+    }
+
+    // The first unix time in ms the window counts, None for all time.
+    pub fn since(self, channel: &str, now: i64) -> Result<Option<i64>, CommandError> {
+        match self {
+            Window::LastDay => Ok(Some(now - 24 * HOUR_MS)),
+            Window::LastWeek => Ok(Some(now - 7 * 24 * HOUR_MS)),
+            Window::LastMonth => Ok(Some(now - 30 * 24 * HOUR_MS)),
+            Window::AllTime => Ok(None),
+            Window::Stream => match stream_start(channel) {
+                Some(start) => Ok(Some(start)),
+                None => Err(CommandError::Message("Error: The bot doesn't know when this stream started.".to_owned())),
+            },
+        }
+    }
+}
+
+// A leaderboard command, declared by what it ranks. Every board takes a window, a size and "me" or
+// "@someone" for their rank, in any order: "!tridentjuicers week 5 me".
+#[derive(Clone)]
+pub struct Leaderboard {
+    name: &'static str,
+    usage: Option<&'static str>,
+    // a "{}" in it is replaced with the category or command the board ranks
+    title: &'static str,
+    source: Source,
+    metric: Metric,
+    window: Window,
+    // a row reads "{name}{separator}{value}{unit}; "
+    separator: &'static str,
+    unit: &'static str,
+    total: bool,
+}
+
+impl Leaderboard {
+    pub fn new(name: &'static str, title: &'static str, source: Source, metric: Metric) -> Self {
+        Leaderboard { name, usage: None, title, source, metric, window: Window::AllTime, separator: " - ", unit: "", total: false }
+    }
+
+    pub fn usage(mut self, usage: &'static str) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    // The window used when the chatter doesn't give one.
+    pub fn window(mut self, window: Window) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn format(mut self, separator: &'static str, unit: &'static str) -> Self {
+        self.separator = separator;
+        self.unit = unit;
+        self
+    }
+
+    // Ends the board with everyone's values added up, like "Total: 120 uses."
+    pub fn total(mut self) -> Self {
+        self.total = true;
+        self
+    }
+
+    fn lower_wins(&self) -> bool {
+        matches!(self.source, Source::Rolls(command) if LOWER_SCORES_WIN.contains(&command))
+    }
+
+    pub fn show(&self, sqlite_connection: &Connection, channel: &str, user_id: &str, message_parts: Vec<&str>, now: i64) -> CommandResult {
+        let mut window: Window = self.window;
+        let mut source: Source = self.source.clone();
+        let mut size: usize = DEFAULT_SIZE;
+        // whose rank to add, with the name they were asked for by, or None for the caller
+        let mut ranked: Option<(String, Option<&str>)> = None;
+
+        for (index, part) in message_parts.iter().enumerate().skip(1) {
+            if let Some(other) = Window::from_name(part) {
+                window = other;
+            } else if part.eq_ignore_ascii_case("me") {
+                ranked = Some((user_id.to_owned(), None));
+            } else if let Some(name) = part.strip_prefix('@') {
+                ranked = Some((find_chatter(sqlite_connection, channel, name)?, Some(name)));
+            } else if part.parse::<f64>().is_ok() {
+                size = SIZE.parse(&message_parts, index)?;
+            } else if let Some(named) = self.source.named(part) {
+                source = named;
+            } else {
+                return Err(CommandError::InvalidSyntax);
+            }
+        }
+
+        if source == Source::UsesOf(String::new()) {
+            return Err(CommandError::InvalidSyntax);
+        }
+        if ranked.is_some() && source == Source::Commands {
+            return Err(CommandError::Message(format!("Error: {} ranks commands, not chatters.", self.name)));
+        }
+
+        let since: Option<i64> = window.since(channel, now)?;
+        let standings = match database::leaderboard(sqlite_connection, channel, &source, self.metric, self.lower_wins(), since) {
+            Ok(standings) => standings,
+            Err(error) => {
+                println!("Leaderboard {} error: {}", self.name, error);
+                return Err(CommandError::Message(format!("Error: {}", error)));
+            }
+        };
+
+        let mut message: String = format!("Top {} {}{}: ", size, self.title.replace("{}", source.name()), window.label());
+        for (id, name, value) in standings.iter().take(size) {
+            // commands are stored as "emark_rollgp"
+            let name: String = if source == Source::Commands { id.replace("emark_", "!") } else { name.to_owned() };
+            message += &format!("{}{}{}{}; ", name, self.separator, format_score(*value), self.unit);
+        }
+
+        if self.total {
+            message += &format!("Total: {}{}. ", format_score(standings.iter().map(|(_, _, value)| value).sum()), self.unit);
+        }
+
+        if let Some((ranked_id, name)) = ranked {
+            let who: String = name.map_or("You're".to_owned(), |name| format!("{} is", name));
+            match standings.iter().position(|(id, _, _)| *id == ranked_id) {
+                Some(index) => message += &format!("| {} #{} with {}{}.", who, index + 1, format_score(standings[index].2), self.unit),
+                None => message += &format!("| {} not on this leaderboard yet.", who),
+            }
+        }
+
+        Ok(message)
+    }
+}
+
+impl Command for Leaderboard {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn usage(&self) -> &'static str {
+        self.usage.unwrap_or(self.name)
+    }
+
+    fn execute<'a>(&'a self, context: &'a CommandContext<'a>) -> CommandFuture<'a> {
+        let now: i64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        let board: Leaderboard = self.clone();
+        let args: Vec<String> = registry::owned_args(&context.args);
+
+        let (sqlite_connection, channel, user_id) = registry::owned_context(context);
+        context.jobs.run_query(move || board.show(&sqlite_connection, &channel, &user_id, registry::borrowed_args(&args), now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::RollRecord;
+    use sqlite::ConnectionWithFullMutex;
+    use serde_json::json;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn add_roll(sqlite_connection: &Connection, user_id: &str, command: &str, score: f64, unix_time: i64) {
+        let record: RollRecord = RollRecord { score: Some(score), result: json!({}) };
+        database::add_roll(sqlite_connection, "channel", user_id, command, &record, 1, unix_time).unwrap();
+    }
+
+    fn with_chatters() -> ConnectionWithFullMutex {
+        let sqlite_connection: ConnectionWithFullMutex = database::test_connection();
+        for (user_id, name) in [("1", "first"), ("2", "second"), ("3", "third")] {
+            database::add_user_message(&sqlite_connection, "channel", user_id, name, name, 1).unwrap();
+        }
+        sqlite_connection
+    }
+
+    #[test]
+    fn boards_rank_by_their_metric() {
+        let sqlite_connection: ConnectionWithFullMutex = with_chatters();
+        add_roll(&sqlite_connection, "1", "!rolltrident", 250.0, NOW);
+        add_roll(&sqlite_connection, "1", "!rolltrident", 0.0, NOW);
+        add_roll(&sqlite_connection, "2", "!rolltrident", 0.0, NOW - 2 * 24 * HOUR_MS);
+        add_roll(&sqlite_connection, "2", "!rolltrident", 0.0, NOW);
+        add_roll(&sqlite_connection, "3", "!rolltrident", 100.0, NOW - 40 * 24 * HOUR_MS);
+
+        let best: Leaderboard = Leaderboard::new("!tridentjuicers", "best trident rolls", Source::Rolls("!rolltrident"), Metric::Best);
+        let zeros: Leaderboard = Leaderboard::new("!tridentnoobs", "chatters with most 0 durability trident rolls", Source::Rolls("!rolltrident"), Metric::CountOf(0.0));
+        let show = |board: &Leaderboard, args: Vec<&str>| board.show(&sqlite_connection, "channel", "2", args, NOW);
+
+        assert_eq!(show(&best, vec!["!tridentjuicers"]), Ok("Top 3 best trident rolls: first - 250; third - 100; second - 0; ".to_owned()));
+        assert_eq!(show(&best, vec!["!tridentjuicers", "24h"]), Ok("Top 3 best trident rolls in the last 24 hours: first - 250; second - 0; ".to_owned()));
+        assert_eq!(
+            show(&best, vec!["!tridentjuicers", "30d", "1", "me"]),
+            Ok("Top 1 best trident rolls in the last 30 days: first - 250; | You're #2 with 0.".to_owned())
+        );
+        assert_eq!(
+            show(&best, vec!["!tridentjuicers", "1", "@Third"]),
+            Ok("Top 1 best trident rolls: first - 250; | Third is #2 with 100.".to_owned())
+        );
+        assert_eq!(show(&zeros, vec!["!tridentnoobs", "2"]), Ok("Top 2 chatters with most 0 durability trident rolls: second - 2; first - 1; ".to_owned()));
+        assert_eq!(show(&best, vec!["!tridentjuicers", "week", "me"]), Ok("Top 3 best trident rolls in the last 7 days: first - 250; second - 0; | You're #2 with 0.".to_owned()));
+        assert_eq!(show(&best, vec!["!tridentjuicers", "Today"]), show(&best, vec!["!tridentjuicers", "24h"]));
+        assert_eq!(show(&best, vec!["!tridentjuicers", "month"]), show(&best, vec!["!tridentjuicers", "30d"]));
+        assert_eq!(show(&best, vec!["!tridentjuicers", "11"]), Err(CommandError::Message("Error: size must be a number from 1 to 10.".to_owned())));
+        assert_eq!(show(&best, vec!["!tridentjuicers", "year"]), Err(CommandError::InvalidSyntax));
+    }
+
+    #[test]
+    fn lower_scores_win_where_they_should() {
+        let sqlite_connection: ConnectionWithFullMutex = with_chatters();
+        add_roll(&sqlite_connection, "1", "!rollblazerods", 12.0, NOW);
+        add_roll(&sqlite_connection, "2", "!rollblazerods", 7.0, NOW);
+        add_roll(&sqlite_connection, "2", "!rollblazerods", 30.0, NOW);
+
+        let best: Leaderboard = Leaderboard::new("!rodjuicers", "blaze rod rolls", Source::Rolls("!rollblazerods"), Metric::Best);
+        let worst: Leaderboard = Leaderboard::new("!rodnoobs", "blaze rod rolls", Source::Rolls("!rollblazerods"), Metric::Worst);
+
+        assert_eq!(best.show(&sqlite_connection, "channel", "1", vec!["!rodjuicers", "me"], NOW), Ok("Top 3 blaze rod rolls: second - 7; first - 12; | You're #2 with 12.".to_owned()));
+        assert_eq!(worst.show(&sqlite_connection, "channel", "3", vec!["!rodnoobs", "me"], NOW), Ok("Top 3 blaze rod rolls: second - 30; first - 12; | You're not on this leaderboard yet.".to_owned()));
+    }
+
+    #[test]
+    fn counters_count_their_window() {
+        let sqlite_connection: ConnectionWithFullMutex = with_chatters();
+        database::add_user_message(&sqlite_connection, "channel", "3", "third", "third", NOW).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rollgp", "1", 1).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rollgp", "2", NOW).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rollgp", "2", NOW).unwrap();
+
+        let chatters: Leaderboard = Leaderboard::new("!topchatters", "chatters", Source::Messages, Metric::Sum).format(": ", " messages");
+        let commands: Leaderboard = Leaderboard::new("!topcommands", "most used commands", Source::Commands, Metric::Sum).format(": ", " uses");
+        let show = |board: &Leaderboard, args: Vec<&str>| board.show(&sqlite_connection, "channel", "1", args, NOW);
+
+        assert!(show(&chatters, vec!["!topchatters"]).unwrap().starts_with("Top 3 chatters: third: 2 messages; "));
+        assert_eq!(show(&chatters, vec!["!topchatters", "24h"]), Ok("Top 3 chatters in the last 24 hours: third: 1 messages; ".to_owned()));
+        assert_eq!(show(&commands, vec!["!topcommands"]), Ok("Top 3 most used commands: !rollgp: 3 uses; ".to_owned()));
+        assert_eq!(show(&commands, vec!["!topcommands", "7d"]), Ok("Top 3 most used commands in the last 7 days: !rollgp: 2 uses; ".to_owned()));
+    }
+
+    #[test]
+    fn boards_can_rank_a_named_command_or_category() {
+        let sqlite_connection: ConnectionWithFullMutex = with_chatters();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rollgp", "1", NOW).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rollgp", "2", NOW).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_rollgp", "2", NOW).unwrap();
+        database::add_command_use(&sqlite_connection, "channel", "emark_age", "3", NOW).unwrap();
+        database::add_catches(&sqlite_connection, "channel", "1", &[("minecraft:cod".to_owned(), "fish", 3), ("minecraft:name_tag".to_owned(), "treasure", 1)], 1, NOW).unwrap();
+        database::add_catches(&sqlite_connection, "channel", "2", &[("minecraft:bowl".to_owned(), "junk", 2)], 1, NOW - 2 * 24 * HOUR_MS).unwrap();
+
+        let users: Leaderboard = Leaderboard::new("!commandstats", "users with most {} uses", Source::UsesOf(String::new()), Metric::Sum).format(": ", " uses").total();
+        let fishers: Leaderboard = Leaderboard::new("!fishjuicers", "chatters with the most {} caught", Source::Catches("treasure"), Metric::Sum);
+        let show = |board: &Leaderboard, args: Vec<&str>| board.show(&sqlite_connection, "channel", "1", args, NOW);
+
+        assert_eq!(
+            show(&users, vec!["!commandstats", "!rollgp", "me"]),
+            Ok("Top 3 users with most !rollgp uses: second: 2 uses; first: 1 uses; Total: 3 uses. | You're #2 with 1 uses.".to_owned())
+        );
+        assert_eq!(show(&users, vec!["!commandstats", "5"]), Err(CommandError::InvalidSyntax));
+        assert_eq!(show(&fishers, vec!["!fishjuicers"]), Ok("Top 3 chatters with the most treasure caught: first - 1; ".to_owned()));
+        assert_eq!(show(&fishers, vec!["!fishjuicers", "Junk"]), Ok("Top 3 chatters with the most junk caught: second - 2; ".to_owned()));
+        assert_eq!(show(&fishers, vec!["!fishjuicers", "junk", "24h"]), Ok("Top 3 chatters with the most junk caught in the last 24 hours: ".to_owned()));
+        assert_eq!(show(&fishers, vec!["!fishjuicers", "lava"]), Err(CommandError::InvalidSyntax));
+    }
+
+    #[test]
+    fn stream_boards_need_the_stream_start() {
+        let sqlite_connection: ConnectionWithFullMutex = with_chatters();
+        // the stream starts are shared by all tests, so this test has its own channel
+        database::add_user_message(&sqlite_connection, "streamer", "1", "first", "first", NOW - 2 * HOUR_MS).unwrap();
+        database::add_user_message(&sqlite_connection, "streamer", "2", "second", "second", NOW).unwrap();
+
+        let chatters: Leaderboard = Leaderboard::new("!topchatters", "chatters", Source::Messages, Metric::Sum).format(": ", " messages");
+        let show = || chatters.show(&sqlite_connection, "streamer", "1", vec!["!topchatters", "stream"], NOW);
+
+        assert_eq!(show(), Err(CommandError::Message("Error: The bot doesn't know when this stream started.".to_owned())));
+        set_stream_start("Streamer", Some(NOW - HOUR_MS));
+        assert_eq!(show(), Ok("Top 3 chatters this stream: second: 1 messages; ".to_owned()));
+        set_stream_start("streamer", None);
+        assert_eq!(show(), Err(CommandError::Message("Error: The bot doesn't know when this stream started.".to_owned())));
+    }
+}
@@ -0,0 +1,1092 @@
+use rand::Rng;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::math;
+
+// Loot tables in the vanilla Java-edition JSON format: pools of weighted entries with number
+// providers, conditions and item functions. The bundled tables are copies of the vanilla ones,
+// plus trident_bot: tables for drops that vanilla rolls outside of loot tables, like the items
+// drowned spawn holding.
+const BUNDLED: &[(&str, &str)] = &[
+    ("minecraft:chests/desert_pyramid", include_str!("../data/minecraft/loot_tables/chests/desert_pyramid.json")),
+    ("minecraft:entities/blaze", include_str!("../data/minecraft/loot_tables/entities/blaze.json")),
+    ("minecraft:entities/drowned", include_str!("../data/minecraft/loot_tables/entities/drowned.json")),
+    ("minecraft:entities/wither_skeleton", include_str!("../data/minecraft/loot_tables/entities/wither_skeleton.json")),
+    ("minecraft:gameplay/fishing", include_str!("../data/minecraft/loot_tables/gameplay/fishing.json")),
+    ("minecraft:gameplay/fishing/fish", include_str!("../data/minecraft/loot_tables/gameplay/fishing/fish.json")),
+    ("minecraft:gameplay/fishing/junk", include_str!("../data/minecraft/loot_tables/gameplay/fishing/junk.json")),
+    ("minecraft:gameplay/fishing/treasure", include_str!("../data/minecraft/loot_tables/gameplay/fishing/treasure.json")),
+    ("minecraft:gameplay/piglin_bartering", include_str!("../data/minecraft/loot_tables/gameplay/piglin_bartering.json")),
+    ("trident_bot:entities/drowned", include_str!("../data/trident_bot/loot_tables/entities/drowned.json")),
+    ("trident_bot:equipment/drowned", include_str!("../data/trident_bot/loot_tables/equipment/drowned.json")),
+];
+
+// Exact distributions enumerate every combination of the randomly conditioned entries of a pool.
+const MAX_RANDOM_ENTRIES: usize = 12;
+// Keeps a single roll of a table cheap enough for the chat loop.
+const MAX_ROLLS: f64 = 1000.0;
+
+static LOOT_TABLES: OnceLock<LootTables> = OnceLock::new();
+
+// Loads the bundled tables plus the ones in `path`, a datapack style data folder
+// (<namespace>/loot_tables/<name>.json). Tables there replace bundled tables with the same name.
+pub fn init_loot_tables(path: Option<&str>) -> Result<(), LootError> {
+    let mut tables: LootTables = LootTables::bundled()?;
+
+    if let Some(path) = path {
+        tables.load_data_folder(Path::new(path))?;
+    }
+    tables.validate()?;
+
+    // the commands only start after this, so the tables can't have been built yet
+    let _ = LOOT_TABLES.set(tables);
+    Ok(())
+}
+
+pub fn loot_tables() -> &'static LootTables {
+    LOOT_TABLES.get_or_init(|| LootTables::bundled().and_then(|tables| tables.validate().map(|_| tables)).expect("the bundled loot tables are valid"))
+}
+
+#[derive(Debug)]
+pub enum LootError {
+    Read(String, std::io::Error),
+    Parse(String, serde_json::Error),
+    Invalid(String, String),
+}
+
+impl fmt::Display for LootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LootError::Read(path, err) => write!(f, "couldn't read the loot table {}: {}", path, err),
+            LootError::Parse(name, err) => write!(f, "couldn't parse the loot table {}: {}", name, err),
+            LootError::Invalid(name, message) => write!(f, "invalid loot table {}: {}", name, message),
+        }
+    }
+}
+
+// What a roll knows about how the loot was generated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LootContext {
+    pub looting_level: u32,
+    // Luck of the Sea level plus the player's luck attribute
+    pub luck: f64,
+    pub killed_by_player: bool,
+    // the fishing bobber is in open water, which treasure needs since 1.16
+    pub open_water: bool,
+}
+
+impl LootContext {
+    pub fn player_kill(looting_level: u32) -> Self {
+        LootContext { looting_level, killed_by_player: true, ..Default::default() }
+    }
+
+    pub fn fishing(luck: f64, open_water: bool) -> Self {
+        LootContext { luck, open_water, ..Default::default() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u64,
+    pub enchanted: bool,
+}
+
+impl ItemStack {
+    // "minecraft:rotten_flesh" -> "Rotten Flesh"
+    pub fn name(&self) -> String {
+        let name: String = item_name(&self.item);
+
+        if self.enchanted {
+            format!("Enchanted {}", name)
+        } else {
+            name
+        }
+    }
+}
+
+pub fn item_name(item: &str) -> String {
+    let path: &str = item.split_once(':').map_or(item, |(_, path)| path);
+
+    path.split('_')
+        .map(|word| {
+            let mut letters = word.chars();
+            match letters.next() {
+                Some(first) => first.to_uppercase().chain(letters).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// "entities/blaze" -> "minecraft:entities/blaze"
+pub fn full_name(name: &str) -> String {
+    if name.contains(':') { name.to_owned() } else { format!("minecraft:{}", name) }
+}
+
+#[derive(Default)]
+pub struct LootTables {
+    tables: HashMap<String, LootTable>,
+}
+
+impl LootTables {
+    pub fn bundled() -> Result<Self, LootError> {
+        let mut tables: LootTables = LootTables::default();
+
+        for (name, json) in BUNDLED {
+            tables.add(name, json)?;
+        }
+
+        Ok(tables)
+    }
+
+    pub fn add(&mut self, name: &str, json: &str) -> Result<(), LootError> {
+        let value: Value = serde_json::from_str(json).map_err(|err| LootError::Parse(name.to_owned(), err))?;
+        let table: LootTable = LootTable::parse(&value).map_err(|message| LootError::Invalid(name.to_owned(), message))?;
+
+        self.tables.insert(full_name(name), table);
+        Ok(())
+    }
+
+    fn load_data_folder(&mut self, path: &Path) -> Result<(), LootError> {
+        let read_dir = |path: &Path| fs::read_dir(path).map_err(|err| LootError::Read(path.display().to_string(), err));
+
+        for namespace in read_dir(path)? {
+            let namespace = namespace.map_err(|err| LootError::Read(path.display().to_string(), err))?;
+            let tables_path = namespace.path().join("loot_tables");
+
+            if tables_path.is_dir() {
+                let prefix: String = format!("{}:", namespace.file_name().to_string_lossy());
+                self.load_folder(&tables_path, &prefix)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_folder(&mut self, path: &Path, prefix: &str) -> Result<(), LootError> {
+        let entries = fs::read_dir(path).map_err(|err| LootError::Read(path.display().to_string(), err))?;
+
+        for entry in entries {
+            let entry_path = entry.map_err(|err| LootError::Read(path.display().to_string(), err))?.path();
+            let file_name: String = entry_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+            if entry_path.is_dir() {
+                self.load_folder(&entry_path, &format!("{}{}/", prefix, file_name))?;
+            } else if let Some(stem) = file_name.strip_suffix(".json") {
+                let json: String = fs::read_to_string(&entry_path).map_err(|err| LootError::Read(entry_path.display().to_string(), err))?;
+                self.add(&format!("{}{}", prefix, stem), &json)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Every referenced table has to exist, and tables can't reference themselves.
+    pub fn validate(&self) -> Result<(), LootError> {
+        for (name, table) in &self.tables {
+            for reference in table.references() {
+                if !self.tables.contains_key(reference) {
+                    return Err(LootError::Invalid(name.to_owned(), format!("there's no loot table called {}", reference)));
+                }
+            }
+
+            if self.reaches(name, name, &mut HashSet::new()) {
+                return Err(LootError::Invalid(name.to_owned(), "the table references itself".to_owned()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn reaches(&self, from: &str, target: &str, seen: &mut HashSet<String>) -> bool {
+        let Some(table) = self.tables.get(from) else { return false; };
+
+        table.references().into_iter().any(|reference| {
+            reference == target || (seen.insert(reference.to_owned()) && self.reaches(reference, target, seen))
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tables.contains_key(&full_name(name))
+    }
+
+    // One roll of the table, with stacks of the same item merged.
+    pub fn roll<R: Rng>(&self, name: &str, context: &LootContext, rng: &mut R) -> Vec<ItemStack> {
+        let mut stacks: Vec<ItemStack> = Vec::new();
+
+        self.roll_into(&full_name(name), context, &[], rng, &mut stacks);
+        stacks
+    }
+
+    fn roll_into<R: Rng>(&self, name: &str, context: &LootContext, functions: &[&Function], rng: &mut R, stacks: &mut Vec<ItemStack>) {
+        let table: &LootTable = &self.tables[name];
+
+        for pool in &table.pools {
+            if !passes(&pool.conditions, context, rng) {
+                continue;
+            }
+
+            let functions: Vec<&Function> = pool.functions.iter().chain(&table.functions).chain(functions.iter().copied()).collect();
+            let rolls: i64 = pool.rolls.sample_int(rng) + Rounding::Floor.apply(pool.bonus_rolls.sample_float(rng) * context.luck);
+
+            for _ in 0..rolls.max(0) {
+                let candidates: Vec<(&Entry, u64)> = pool.entries
+                    .iter()
+                    .filter(|entry| passes(&entry.conditions, context, rng))
+                    .map(|entry| (entry, entry.weight(context)))
+                    .filter(|(_, weight)| *weight > 0)
+                    .collect();
+                let total: u64 = candidates.iter().map(|(_, weight)| weight).sum();
+
+                let chosen: Option<&Entry> = if candidates.len() == 1 {
+                    Some(candidates[0].0)
+                } else if total > 0 {
+                    let mut pick: u64 = rng.gen_range(0..total);
+                    candidates.iter().find(|(_, weight)| {
+                        let found: bool = pick < *weight;
+                        pick = pick.saturating_sub(*weight);
+                        found
+                    }).map(|(entry, _)| *entry)
+                } else {
+                    None
+                };
+
+                if let Some(entry) = chosen {
+                    let functions: Vec<&Function> = entry.functions.iter().chain(functions.iter().copied()).collect();
+
+                    match &entry.kind {
+                        EntryKind::Item(item) => add_stack(stacks, roll_stack(item, &functions, context, rng)),
+                        EntryKind::LootTable(name) => self.roll_into(name, context, &functions, rng, stacks),
+                        EntryKind::Empty => {},
+                    }
+                }
+            }
+        }
+    }
+
+    // Odds of each total count of `item` from one roll of the table: [n] is the odds of getting n.
+    pub fn count_distribution(&self, name: &str, item: &str, context: &LootContext) -> Vec<f64> {
+        self.table_distribution(&full_name(name), &full_name(item), context, &[]).into_counts()
+    }
+
+    fn table_distribution(&self, name: &str, item: &str, context: &LootContext, functions: &[&Function]) -> Distribution {
+        let table: &LootTable = &self.tables[name];
+        let mut total: Distribution = Distribution::point(0);
+
+        for pool in &table.pools {
+            let functions: Vec<&Function> = pool.functions.iter().chain(&table.functions).chain(functions.iter().copied()).collect();
+            total = total.add(&self.pool_distribution(pool, item, context, &functions));
+        }
+
+        total
+    }
+
+    fn pool_distribution(&self, pool: &Pool, item: &str, context: &LootContext, functions: &[&Function]) -> Distribution {
+        let passing: f64 = chance(&pool.conditions, context);
+        if passing == 0.0 {
+            return Distribution::point(0);
+        }
+
+        let rolls: Distribution = pool.rolls.int_distribution().add(&pool.bonus_rolls.scaled_distribution(context.luck, Rounding::Floor)).at_least_zero();
+        let one_roll: Distribution = self.roll_distribution(pool, item, context, functions);
+
+        let mut result: Distribution = Distribution::point(0).scale(1.0 - passing);
+        // the total after n rolls, starting from the fewest rolls the pool can make
+        let mut power: Distribution = (0..rolls.min).fold(Distribution::point(0), |power, _| power.add(&one_roll));
+        for (i, odds) in rolls.odds.iter().enumerate() {
+            if i > 0 {
+                power = power.add(&one_roll);
+            }
+            if *odds > 0.0 {
+                result = result.mix(&power.clone().scale(odds * passing));
+            }
+        }
+
+        result
+    }
+
+    // One roll of a pool.
+    fn roll_distribution(&self, pool: &Pool, item: &str, context: &LootContext, functions: &[&Function]) -> Distribution {
+        let picks: Vec<f64> = pick_odds(pool, context);
+        let nothing: f64 = (1.0 - picks.iter().sum::<f64>()).max(0.0);
+        let mut result: Distribution = Distribution::point(0).scale(nothing);
+
+        for (entry, picked) in pool.entries.iter().zip(picks) {
+            if picked <= 0.0 {
+                continue;
+            }
+
+            let functions: Vec<&Function> = entry.functions.iter().chain(functions.iter().copied()).collect();
+            let entry_distribution: Distribution = match &entry.kind {
+                EntryKind::Item(entry_item) if entry_item == item => stack_distribution(&functions, context).at_least_zero(),
+                EntryKind::LootTable(name) => self.table_distribution(name, item, context, &functions),
+                _ => Distribution::point(0),
+            };
+
+            result = result.mix(&entry_distribution.scale(picked));
+        }
+
+        result
+    }
+
+    // The totals of `rolls` rolls of the table, in the order of `items`, drawn without rolling each one.
+    // Rolls are independent, so all the picks a pool makes over them are one multinomial over its
+    // entries and picking nothing. That keeps entries of one pool excluding each other, like a
+    // drowned dropping a trident or a fishing rod but never both.
+    pub fn sample_totals<R: Rng>(&self, name: &str, rolls: u64, context: &LootContext, rng: &mut R) -> Vec<(String, u64)> {
+        let mut totals: Vec<(String, u64)> = self.items(name).into_iter().map(|item| (item, 0)).collect();
+
+        self.sample_into(&full_name(name), rolls, context, &[], rng, &mut totals);
+        totals
+    }
+
+    fn sample_into<R: Rng>(&self, name: &str, rolls: u64, context: &LootContext, functions: &[&Function], rng: &mut R, totals: &mut Vec<(String, u64)>) {
+        let table: &LootTable = &self.tables[name];
+
+        for pool in &table.pools {
+            let functions: Vec<&Function> = pool.functions.iter().chain(&table.functions).chain(functions.iter().copied()).collect();
+            let passed: u64 = math::binomial(rolls, chance(&pool.conditions, context), rng);
+            let pool_rolls: Distribution = pool.rolls.int_distribution().add(&pool.bonus_rolls.scaled_distribution(context.luck, Rounding::Floor)).at_least_zero();
+            let picks: u64 = pool_rolls.sample_sum(passed, rng);
+
+            let mut odds: Vec<f64> = pick_odds(pool, context);
+            odds.push((1.0 - odds.iter().sum::<f64>()).max(0.0));
+            let picked: Vec<u64> = math::multinomial(picks, &odds, rng);
+
+            for (entry, picked) in pool.entries.iter().zip(picked) {
+                if picked == 0 {
+                    continue;
+                }
+
+                let functions: Vec<&Function> = entry.functions.iter().chain(functions.iter().copied()).collect();
+                match &entry.kind {
+                    EntryKind::Item(item) => {
+                        let count: u64 = stack_distribution(&functions, context).at_least_zero().sample_sum(picked, rng);
+                        if let Some((_, total)) = totals.iter_mut().find(|(total_item, _)| total_item == item) {
+                            *total += count;
+                        }
+                    },
+                    EntryKind::LootTable(name) => self.sample_into(name, picked, context, &functions, rng, totals),
+                    EntryKind::Empty => {},
+                }
+            }
+        }
+    }
+
+    // Items the table can drop, in the order they appear in it.
+    pub fn items(&self, name: &str) -> Vec<String> {
+        let mut items: Vec<String> = Vec::new();
+        self.collect_items(&full_name(name), &mut items);
+        items
+    }
+
+    fn collect_items(&self, name: &str, items: &mut Vec<String>) {
+        for entry in self.tables[name].pools.iter().flat_map(|pool| &pool.entries) {
+            match &entry.kind {
+                EntryKind::Item(item) if !items.contains(item) => items.push(item.to_owned()),
+                EntryKind::LootTable(name) => self.collect_items(name, items),
+                _ => {},
+            }
+        }
+    }
+}
+
+struct LootTable {
+    pools: Vec<Pool>,
+    functions: Vec<Function>,
+}
+
+struct Pool {
+    rolls: NumberProvider,
+    bonus_rolls: NumberProvider,
+    conditions: Vec<Condition>,
+    entries: Vec<Entry>,
+    functions: Vec<Function>,
+}
+
+struct Entry {
+    kind: EntryKind,
+    weight: u64,
+    quality: i64,
+    conditions: Vec<Condition>,
+    functions: Vec<Function>,
+}
+
+impl Entry {
+    fn weight(&self, context: &LootContext) -> u64 {
+        (self.weight as f64 + self.quality as f64 * context.luck).floor().max(0.0) as u64
+    }
+}
+
+enum EntryKind {
+    Item(String),
+    LootTable(String),
+    Empty,
+}
+
+struct Function {
+    kind: FunctionKind,
+    conditions: Vec<Condition>,
+}
+
+enum FunctionKind {
+    SetCount { count: NumberProvider, add: bool },
+    // adds round(looting level * count) items, up to `limit` (0 for no limit)
+    LootingEnchant { count: NumberProvider, limit: i64 },
+    Enchant,
+    // functions that don't change which item drops or how many, like set_damage
+    NoEffect,
+}
+
+enum Condition {
+    RandomChance(f64),
+    RandomChanceWithLooting { chance: f64, looting_multiplier: f64 },
+    KilledByPlayer,
+    InOpenWater(bool),
+    Inverted(Box<Condition>),
+}
+
+impl Condition {
+    fn chance(&self, context: &LootContext) -> f64 {
+        match self {
+            Condition::RandomChance(chance) => chance.clamp(0.0, 1.0),
+            Condition::RandomChanceWithLooting { chance, looting_multiplier } => {
+                (chance + context.looting_level as f64 * looting_multiplier).clamp(0.0, 1.0)
+            },
+            Condition::KilledByPlayer => if context.killed_by_player { 1.0 } else { 0.0 },
+            Condition::InOpenWater(open_water) => if context.open_water == *open_water { 1.0 } else { 0.0 },
+            Condition::Inverted(condition) => 1.0 - condition.chance(context),
+        }
+    }
+}
+
+// Odds of each entry being picked on one roll of the pool, in the pool's order. Entries whose
+// conditions are random can pass or fail on each roll, which changes the total weight, so every
+// combination of them is weighed separately.
+fn pick_odds(pool: &Pool, context: &LootContext) -> Vec<f64> {
+    let odds: Vec<f64> = pool.entries.iter().map(|entry| chance(&entry.conditions, context)).collect();
+    let random: Vec<usize> = (0..odds.len()).filter(|i| odds[*i] > 0.0 && odds[*i] < 1.0).collect();
+    let mut picks: Vec<f64> = vec![0.0; pool.entries.len()];
+
+    for mask in 0..(1usize << random.len()) {
+        let mut combination: f64 = 1.0;
+        let passing: Vec<(usize, u64)> = pool.entries
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| match random.iter().position(|random| random == i) {
+                Some(bit) => {
+                    let passes: bool = mask & (1 << bit) != 0;
+                    combination *= if passes { odds[*i] } else { 1.0 - odds[*i] };
+                    passes
+                },
+                None => odds[*i] >= 1.0,
+            })
+            .map(|(i, entry)| (i, entry.weight(context)))
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+        let total: u64 = passing.iter().map(|(_, weight)| weight).sum();
+
+        for (i, weight) in &passing {
+            picks[*i] += combination * if passing.len() == 1 { 1.0 } else { *weight as f64 / total as f64 };
+        }
+    }
+
+    picks
+}
+
+fn chance(conditions: &[Condition], context: &LootContext) -> f64 {
+    conditions.iter().map(|condition| condition.chance(context)).product()
+}
+
+fn passes<R: Rng>(conditions: &[Condition], context: &LootContext, rng: &mut R) -> bool {
+    conditions.iter().all(|condition| {
+        let chance: f64 = condition.chance(context);
+        chance >= 1.0 || (chance > 0.0 && rng.gen::<f64>() < chance)
+    })
+}
+
+#[derive(Clone, Copy)]
+enum NumberProvider {
+    Constant(f64),
+    Uniform(f64, f64),
+    Binomial(i64, f64),
+}
+
+impl NumberProvider {
+    fn sample_int<R: Rng>(&self, rng: &mut R) -> i64 {
+        match *self {
+            NumberProvider::Constant(value) => value.floor() as i64,
+            NumberProvider::Uniform(min, max) => {
+                let (min, max): (i64, i64) = (min.floor() as i64, max.floor() as i64);
+                if min >= max { min } else { rng.gen_range(min..=max) }
+            },
+            NumberProvider::Binomial(n, p) => (0..n).filter(|_| rng.gen::<f64>() < p).count() as i64,
+        }
+    }
+
+    fn sample_float<R: Rng>(&self, rng: &mut R) -> f64 {
+        match *self {
+            NumberProvider::Constant(value) => value,
+            NumberProvider::Uniform(min, max) => if min >= max { min } else { rng.gen_range(min..max) },
+            NumberProvider::Binomial(..) => self.sample_int(rng) as f64,
+        }
+    }
+
+    fn int_distribution(&self) -> Distribution {
+        match *self {
+            NumberProvider::Constant(value) => Distribution::point(value.floor() as i64),
+            NumberProvider::Uniform(min, max) => {
+                let (min, max): (i64, i64) = (min.floor() as i64, max.floor() as i64);
+                if min >= max {
+                    Distribution::point(min)
+                } else {
+                    Distribution { min, odds: vec![1.0 / (max - min + 1) as f64; (max - min + 1) as usize] }
+                }
+            },
+            NumberProvider::Binomial(n, p) => Distribution {
+                min: 0,
+                odds: (0..=n.max(0)).map(|k| crate::math::bernoullis_scheme(n as u128, k as u128, p)).collect(),
+            },
+        }
+    }
+
+    // Odds of each value of scale * the float, rounded to a whole number.
+    fn scaled_distribution(&self, scale: f64, rounding: Rounding) -> Distribution {
+        match *self {
+            NumberProvider::Uniform(min, max) if min < max && scale != 0.0 => {
+                let (low, high): (f64, f64) = if scale > 0.0 { (min * scale, max * scale) } else { (max * scale, min * scale) };
+                let (first, last): (i64, i64) = (rounding.apply(low), rounding.apply(high));
+
+                // the values in [k + offset, k + 1 + offset) turn into k
+                Distribution {
+                    min: first,
+                    odds: (first..=last).map(|k| {
+                        let from: f64 = (k as f64 + rounding.offset()).max(low);
+                        let to: f64 = (k as f64 + 1.0 + rounding.offset()).min(high);
+                        (to - from).max(0.0) / (high - low)
+                    }).collect(),
+                }
+            },
+            NumberProvider::Binomial(..) => {
+                let counts: Distribution = self.int_distribution();
+                let mut result: Distribution = Distribution::empty();
+
+                for (k, odds) in counts.odds.iter().enumerate() {
+                    result = result.mix(&Distribution::point(rounding.apply(k as f64 * scale)).scale(*odds));
+                }
+                result
+            },
+            NumberProvider::Constant(value) | NumberProvider::Uniform(value, _) => Distribution::point(rounding.apply(value * scale)),
+        }
+    }
+
+    fn max(&self) -> f64 {
+        match *self {
+            NumberProvider::Constant(value) => value,
+            NumberProvider::Uniform(min, max) => min.max(max),
+            NumberProvider::Binomial(n, _) => n as f64,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Rounding {
+    // bonus rolls
+    Floor,
+    // looting_enchant uses Java's Math.round, where halves round up
+    Round,
+}
+
+impl Rounding {
+    fn apply(self, value: f64) -> i64 {
+        match self {
+            Rounding::Floor => value.floor() as i64,
+            Rounding::Round => (value + 0.5).floor() as i64,
+        }
+    }
+
+    fn offset(self) -> f64 {
+        match self {
+            Rounding::Floor => 0.0,
+            Rounding::Round => -0.5,
+        }
+    }
+}
+
+fn roll_stack<R: Rng>(item: &str, functions: &[&Function], context: &LootContext, rng: &mut R) -> ItemStack {
+    let mut count: i64 = 1;
+    let mut enchanted: bool = false;
+
+    for function in functions {
+        if !passes(&function.conditions, context, rng) {
+            continue;
+        }
+
+        match &function.kind {
+            FunctionKind::SetCount { count: provider, add } => {
+                count = if *add { count + provider.sample_int(rng) } else { provider.sample_int(rng) };
+            },
+            FunctionKind::LootingEnchant { count: provider, limit } => {
+                if context.looting_level > 0 {
+                    count += Rounding::Round.apply(context.looting_level as f64 * provider.sample_float(rng));
+                    if *limit > 0 {
+                        count = count.min(*limit);
+                    }
+                }
+            },
+            FunctionKind::Enchant => enchanted = true,
+            FunctionKind::NoEffect => {},
+        }
+    }
+
+    ItemStack { item: item.to_owned(), count: count.max(0) as u64, enchanted }
+}
+
+fn stack_distribution(functions: &[&Function], context: &LootContext) -> Distribution {
+    let mut count: Distribution = Distribution::point(1);
+
+    for function in functions {
+        let applies: f64 = chance(&function.conditions, context);
+
+        let applied: Distribution = match &function.kind {
+            FunctionKind::SetCount { count: provider, add } => {
+                if *add { count.add(&provider.int_distribution()) } else { provider.int_distribution() }
+            },
+            FunctionKind::LootingEnchant { count: provider, limit } if context.looting_level > 0 => {
+                let grown: Distribution = count.add(&provider.scaled_distribution(context.looting_level as f64, Rounding::Round));
+                if *limit > 0 { grown.at_most(*limit) } else { grown }
+            },
+            _ => count.clone(),
+        };
+
+        count = if applies >= 1.0 { applied } else { applied.scale(applies).mix(&count.scale(1.0 - applies)) };
+    }
+
+    count
+}
+
+fn add_stack(stacks: &mut Vec<ItemStack>, stack: ItemStack) {
+    if stack.count == 0 {
+        return;
+    }
+
+    match stacks.iter_mut().find(|other| other.item == stack.item && other.enchanted == stack.enchanted) {
+        Some(other) => other.count += stack.count,
+        None => stacks.push(stack),
+    }
+}
+
+// Odds of each whole number from `min` up. Counts can be negative until the stack is dropped, like
+// coal from a wither skeleton before looting is added.
+#[derive(Clone, Debug)]
+struct Distribution {
+    min: i64,
+    odds: Vec<f64>,
+}
+
+impl Distribution {
+    fn empty() -> Self {
+        Distribution { min: 0, odds: Vec::new() }
+    }
+
+    fn point(value: i64) -> Self {
+        Distribution { min: value, odds: vec![1.0] }
+    }
+
+    fn scale(mut self, factor: f64) -> Self {
+        self.odds.iter_mut().for_each(|odds| *odds *= factor);
+        self
+    }
+
+    // The sum of two independent values.
+    fn add(&self, other: &Distribution) -> Distribution {
+        if self.odds.is_empty() || other.odds.is_empty() {
+            return Distribution::empty();
+        }
+
+        let mut odds: Vec<f64> = vec![0.0; self.odds.len() + other.odds.len() - 1];
+        for (i, a) in self.odds.iter().enumerate() {
+            for (j, b) in other.odds.iter().enumerate() {
+                odds[i + j] += a * b;
+            }
+        }
+
+        Distribution { min: self.min + other.min, odds }
+    }
+
+    // Adds the odds of `other`, for combining already weighted outcomes.
+    fn mix(self, other: &Distribution) -> Distribution {
+        if self.odds.is_empty() {
+            return other.clone();
+        }
+        if other.odds.is_empty() {
+            return self;
+        }
+
+        let min: i64 = self.min.min(other.min);
+        let max: i64 = (self.min + self.odds.len() as i64).max(other.min + other.odds.len() as i64);
+        let mut odds: Vec<f64> = vec![0.0; (max - min) as usize];
+
+        for distribution in [&self, other] {
+            for (i, value) in distribution.odds.iter().enumerate() {
+                odds[(distribution.min - min) as usize + i] += value;
+            }
+        }
+
+        Distribution { min, odds }
+    }
+
+    fn clamp(self, low: i64, high: i64) -> Distribution {
+        let mut result: Distribution = Distribution::empty();
+
+        for (i, odds) in self.odds.iter().enumerate() {
+            let value: i64 = (self.min + i as i64).clamp(low, high);
+            result = result.mix(&Distribution::point(value).scale(*odds));
+        }
+
+        result
+    }
+
+    // Empty stacks and zero rolls: anything below zero counts as zero.
+    // The sum of `draws` independent values, for distributions of counts that can't be negative.
+    fn sample_sum<R: Rng>(&self, draws: u64, rng: &mut R) -> u64 {
+        math::multinomial(draws, &self.odds, rng)
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (self.min as u64 + i as u64) * count)
+            .sum()
+    }
+
+    fn at_least_zero(self) -> Distribution {
+        if self.min >= 0 { self } else { self.clamp(0, i64::MAX) }
+    }
+
+    fn at_most(self, limit: i64) -> Distribution {
+        if self.min + self.odds.len() as i64 - 1 <= limit { self } else { self.clamp(i64::MIN, limit) }
+    }
+
+    fn into_counts(self) -> Vec<f64> {
+        let distribution: Distribution = self.at_least_zero();
+        let mut counts: Vec<f64> = vec![0.0; distribution.min as usize];
+
+        counts.extend(distribution.odds);
+        counts
+    }
+}
+
+// Parsing from the JSON. Names can leave out the "minecraft:" namespace like in vanilla, and
+// anything the engine can't evaluate is an error rather than silently ignored.
+impl LootTable {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let object: &Map<String, Value> = value.as_object().ok_or("a loot table must be an object")?;
+
+        Ok(LootTable {
+            pools: parse_list(object.get("pools"), Pool::parse)?,
+            functions: parse_list(object.get("functions"), Function::parse)?,
+        })
+    }
+
+    fn references(&self) -> Vec<&str> {
+        self.pools
+            .iter()
+            .flat_map(|pool| &pool.entries)
+            .filter_map(|entry| match &entry.kind {
+                EntryKind::LootTable(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Pool {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let object: &Map<String, Value> = value.as_object().ok_or("a pool must be an object")?;
+        let rolls: NumberProvider = NumberProvider::parse(object.get("rolls").ok_or("a pool needs rolls")?)?;
+        let bonus_rolls: NumberProvider = match object.get("bonus_rolls") {
+            Some(bonus_rolls) => NumberProvider::parse(bonus_rolls)?,
+            None => NumberProvider::Constant(0.0),
+        };
+
+        if rolls.max() > MAX_ROLLS {
+            return Err(format!("a pool can't have more than {} rolls", MAX_ROLLS));
+        }
+
+        let pool: Pool = Pool {
+            rolls,
+            bonus_rolls,
+            conditions: parse_list(object.get("conditions"), Condition::parse)?,
+            entries: parse_list(object.get("entries"), Entry::parse)?,
+            functions: parse_list(object.get("functions"), Function::parse)?,
+        };
+
+        let random_entries: usize = pool.entries.iter().filter(|entry| entry.conditions.iter().any(Condition::is_random)).count();
+        if random_entries > MAX_RANDOM_ENTRIES {
+            return Err(format!("a pool can't have more than {} entries with random conditions", MAX_RANDOM_ENTRIES));
+        }
+
+        Ok(pool)
+    }
+}
+
+impl Entry {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let object: &Map<String, Value> = value.as_object().ok_or("an entry must be an object")?;
+        let name = || -> Result<String, String> {
+            object.get("name").and_then(Value::as_str).map(full_name).ok_or_else(|| "an entry needs a name".to_owned())
+        };
+
+        let kind: EntryKind = match strip_namespace(get_str(object, "type")?) {
+            "item" => EntryKind::Item(name()?),
+            "loot_table" => EntryKind::LootTable(name()?),
+            "empty" => EntryKind::Empty,
+            other => return Err(format!("entries of type {} aren't supported", other)),
+        };
+
+        Ok(Entry {
+            kind,
+            weight: match object.get("weight") {
+                Some(weight) => weight.as_u64().ok_or("weight must be a whole number")?,
+                None => 1,
+            },
+            quality: match object.get("quality") {
+                Some(quality) => quality.as_i64().ok_or("quality must be a whole number")?,
+                None => 0,
+            },
+            conditions: parse_list(object.get("conditions"), Condition::parse)?,
+            functions: parse_list(object.get("functions"), Function::parse)?,
+        })
+    }
+}
+
+impl Function {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let object: &Map<String, Value> = value.as_object().ok_or("a function must be an object")?;
+
+        let kind: FunctionKind = match strip_namespace(get_str(object, "function")?) {
+            "set_count" => FunctionKind::SetCount {
+                count: NumberProvider::parse(object.get("count").ok_or("set_count needs a count")?)?,
+                add: object.get("add").and_then(Value::as_bool).unwrap_or(false),
+            },
+            "looting_enchant" => FunctionKind::LootingEnchant {
+                count: NumberProvider::parse(object.get("count").ok_or("looting_enchant needs a count")?)?,
+                limit: object.get("limit").and_then(Value::as_i64).unwrap_or(0),
+            },
+            "enchant_randomly" | "enchant_with_levels" => FunctionKind::Enchant,
+            // nothing explodes and nothing burns in the rolls the bot makes
+            "set_damage" | "set_nbt" | "set_name" | "set_lore" | "set_potion" | "explosion_decay" | "furnace_smelt" => FunctionKind::NoEffect,
+            other => return Err(format!("the function {} isn't supported", other)),
+        };
+
+        Ok(Function { kind, conditions: parse_list(object.get("conditions"), Condition::parse)? })
+    }
+}
+
+impl Condition {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let object: &Map<String, Value> = value.as_object().ok_or("a condition must be an object")?;
+        let number = |key: &str| -> Result<f64, String> {
+            object.get(key).and_then(Value::as_f64).ok_or_else(|| format!("the condition needs a number called {}", key))
+        };
+
+        match strip_namespace(get_str(object, "condition")?) {
+            "random_chance" => Ok(Condition::RandomChance(number("chance")?)),
+            "random_chance_with_looting" => Ok(Condition::RandomChanceWithLooting {
+                chance: number("chance")?,
+                looting_multiplier: number("looting_multiplier")?,
+            }),
+            "killed_by_player" => Ok(Condition::KilledByPlayer),
+            "inverted" => Ok(Condition::Inverted(Box::new(Condition::parse(object.get("term").ok_or("inverted needs a term")?)?))),
+            "entity_properties" => {
+                let open_water: Option<bool> = object
+                    .get("predicate")
+                    .and_then(|predicate| predicate.get("fishing_hook"))
+                    .and_then(|fishing_hook| fishing_hook.get("in_open_water"))
+                    .and_then(Value::as_bool);
+
+                open_water.map(Condition::InOpenWater).ok_or_else(|| "only the fishing hook's in_open_water entity property is supported".to_owned())
+            },
+            other => Err(format!("the condition {} isn't supported", other)),
+        }
+    }
+
+    fn is_random(&self) -> bool {
+        match self {
+            Condition::RandomChance(_) | Condition::RandomChanceWithLooting { .. } => true,
+            Condition::Inverted(condition) => condition.is_random(),
+            _ => false,
+        }
+    }
+}
+
+impl NumberProvider {
+    // A plain number, {"min": .., "max": ..} (uniform, the pre-1.17 form), or one with a "type".
+    fn parse(value: &Value) -> Result<Self, String> {
+        if let Some(number) = value.as_f64() {
+            return Ok(NumberProvider::Constant(number));
+        }
+
+        let object: &Map<String, Value> = value.as_object().ok_or("a number must be a number or an object")?;
+        let number = |key: &str| -> Result<f64, String> {
+            object.get(key).and_then(Value::as_f64).ok_or_else(|| format!("the number needs a {}", key))
+        };
+        let kind: &str = match object.get("type") {
+            Some(kind) => strip_namespace(kind.as_str().ok_or("type must be a string")?),
+            None if object.contains_key("n") => "binomial",
+            None => "uniform",
+        };
+
+        match kind {
+            "constant" => Ok(NumberProvider::Constant(number("value")?)),
+            "uniform" => Ok(NumberProvider::Uniform(number("min")?, number("max")?)),
+            "binomial" => {
+                let p: f64 = number("p")?;
+                if !(0.0..=1.0).contains(&p) {
+                    return Err("p must be from 0 to 1".to_owned());
+                }
+                Ok(NumberProvider::Binomial(number("n")? as i64, p))
+            },
+            other => Err(format!("numbers of type {} aren't supported", other)),
+        }
+    }
+}
+
+fn parse_list<T>(value: Option<&Value>, parse: fn(&Value) -> Result<T, String>) -> Result<Vec<T>, String> {
+    match value {
+        Some(Value::Array(values)) => values.iter().map(parse).collect(),
+        Some(_) => Err("expected a list".to_owned()),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn get_str<'a>(object: &'a Map<String, Value>, key: &str) -> Result<&'a str, String> {
+    object.get(key).and_then(Value::as_str).ok_or_else(|| format!("missing {}", key))
+}
+
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{:?} vs {:?}", actual, expected);
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-12, "{:?} vs {:?}", actual, expected);
+        }
+    }
+
+    fn invalid(json: &str) -> String {
+        let mut tables: LootTables = LootTables::bundled().unwrap();
+
+        match tables.add("test", json).and_then(|_| tables.validate()) {
+            Err(LootError::Invalid(_, message)) => message,
+            other => panic!("expected an invalid table, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn bundled_tables_are_valid() {
+        let tables: LootTables = LootTables::bundled().unwrap();
+
+        tables.validate().unwrap();
+        assert!(tables.contains("entities/blaze"));
+        assert!(tables.contains("trident_bot:entities/drowned"));
+        assert_eq!(tables.items("gameplay/fishing").len(), 21);
+    }
+
+    #[test]
+    fn looting_rounds_like_java() {
+        let tables: &LootTables = loot_tables();
+
+        // 0 to 1 rods, plus round(3 * a float from 0 to 1): 0 and 3 half as likely as 1 and 2
+        let rods: Vec<f64> = tables.count_distribution("entities/blaze", "blaze_rod", &LootContext::player_kill(3));
+        assert_close(&rods, &[1.0 / 12.0, 3.0 / 12.0, 4.0 / 12.0, 3.0 / 12.0, 1.0 / 12.0]);
+
+        // nothing drops unless a player killed it
+        assert_close(&tables.count_distribution("entities/blaze", "blaze_rod", &LootContext::default()), &[1.0]);
+    }
+
+    #[test]
+    fn negative_counts_drop_nothing() {
+        // -1 to 1 coal, where -1 and 0 are both an empty stack
+        let coal: Vec<f64> = loot_tables().count_distribution("entities/wither_skeleton", "coal", &LootContext::player_kill(0));
+        assert_close(&coal, &[2.0 / 3.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn luck_and_open_water_change_the_fishing_odds() {
+        let tables: &LootTables = loot_tables();
+        let saddle = |context: LootContext| tables.count_distribution("gameplay/fishing", "saddle", &context)[1];
+
+        assert!((saddle(LootContext::fishing(0.0, true)) - 5.0 / 100.0 / 6.0).abs() < 1e-12);
+        // luck 3: junk 10 - 2 * 3, treasure 5 + 2 * 3, fish 85 - 3
+        assert!((saddle(LootContext::fishing(3.0, true)) - 11.0 / 97.0 / 6.0).abs() < 1e-12);
+        assert_eq!(tables.count_distribution("gameplay/fishing", "saddle", &LootContext::fishing(0.0, false)), vec![1.0]);
+    }
+
+    #[test]
+    fn distributions_match_rolls() {
+        let tables: &LootTables = loot_tables();
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let rolls: usize = 20_000;
+        let cases: [(&str, &str, LootContext); 5] = [
+            ("trident_bot:entities/drowned", "rotten_flesh", LootContext::player_kill(2)),
+            ("trident_bot:entities/drowned", "copper_ingot", LootContext::player_kill(3)),
+            ("entities/wither_skeleton", "bone", LootContext::player_kill(1)),
+            ("chests/desert_pyramid", "rotten_flesh", LootContext::default()),
+            ("gameplay/fishing", "cod", LootContext::fishing(2.0, true)),
+        ];
+
+        for (name, item, context) in cases {
+            let exact: Vec<f64> = tables.count_distribution(name, item, &context);
+            let mut counts: Vec<f64> = vec![0.0; exact.len()];
+
+            for _ in 0..rolls {
+                let count: u64 = tables.roll(name, &context, &mut rng).iter().filter(|stack| stack.item == full_name(item)).map(|stack| stack.count).sum();
+                counts[count as usize] += 1.0 / rolls as f64;
+            }
+
+            assert!((exact.iter().sum::<f64>() - 1.0).abs() < 1e-9, "{} {}", name, item);
+            for (count, (exact, rolled)) in exact.iter().zip(&counts).enumerate() {
+                let tolerance: f64 = 5.0 * (exact * (1.0 - exact) / rolls as f64).sqrt() + 1e-3;
+                assert!((exact - rolled).abs() <= tolerance, "{} {} x{}: {} vs rolled {}", name, item, count, exact, rolled);
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_tables_are_rejected() {
+        assert!(invalid(r#"{"pools": [{"rolls": 1, "entries": [{"type": "minecraft:item", "name": "stone", "functions": [{"function": "minecraft:apply_bonus"}]}]}]}"#).contains("apply_bonus"));
+        assert!(invalid(r#"{"pools": [{"rolls": 1, "entries": [{"type": "minecraft:loot_table", "name": "nowhere"}]}]}"#).contains("minecraft:nowhere"));
+        assert!(invalid(r#"{"pools": [{"rolls": 1, "entries": [{"type": "minecraft:loot_table", "name": "test"}]}]}"#).contains("itself"));
+        assert!(invalid(r#"{"pools": [{"rolls": 100000, "entries": []}]}"#).contains("rolls"));
+    }
+
+    #[test]
+    fn loads_a_data_folder() {
+        let folder = std::env::temp_dir().join(format!("trident_bot_loot_{}", std::process::id()));
+        fs::create_dir_all(folder.join("custom/loot_tables/mobs")).unwrap();
+        fs::write(
+            folder.join("custom/loot_tables/mobs/pig.json"),
+            r#"{"pools": [{"rolls": 1, "entries": [{"type": "item", "name": "porkchop", "functions": [{"function": "set_count", "count": {"min": 1, "max": 3}}]}]}]}"#,
+        ).unwrap();
+
+        let mut tables: LootTables = LootTables::bundled().unwrap();
+        let loaded = tables.load_data_folder(&folder);
+        fs::remove_dir_all(&folder).unwrap();
+
+        loaded.unwrap();
+        assert_close(&tables.count_distribution("custom:mobs/pig", "porkchop", &LootContext::default()), &[0.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+    }
+}
@@ -1,78 +1,100 @@
-use commands::commands;
 use twitch_irc::login::StaticLoginCredentials;
 use twitch_irc::TwitchIRCClient;
 use twitch_irc::message::{ServerMessage, UserNoticeEvent};
 use twitch_irc::{ClientConfig, SecureTCPTransport};
-use twitch_data::{LOGIN, OAUTH_TOKEN, CHANNEL};
 use rspotify::{prelude::*, scopes, AuthCodeSpotify, Credentials, OAuth, Config};
 use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
-use twitch_api::ban;
-
-mod twitch_data;
+use registry::{CommandContext, CommandError, CommandRegistry};
+use permissions::PermissionLevel;
+use cooldowns::{CooldownFeedback, Cooldowns, SystemClock};
+use jobs::JobPool;
+use rng::EntropySeeds;
+use sqlite::ConnectionWithFullMutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use whispers::Whispers;
+
+mod aassg;
+mod args;
+mod bartering;
+mod biomes;
 mod commands;
+mod config;
+mod cooldowns;
+mod database;
+mod eyes;
+mod fishing;
+mod jobs;
+mod leaderboards;
+mod loot;
 mod thunder;
+mod thunder_stats;
+mod weather;
 mod math;
+mod migrations;
+mod mob_drops;
+mod permissions;
 mod phantoms;
-mod twitch_api;
+mod registry;
+mod rng;
+mod streams;
+mod whispers;
 
-const RAID_FILE_PATH: &str = "./raid.txt";
 const RAID_FILE_DEFAULT_VALUE: &str = "No raids.";
-const MAX_MESSAGE_LENGTH: usize = 450;
 
-fn split_message(message: String, slices: &mut Vec<String>) {
+fn split_message(message: String, max_message_length: usize, slices: &mut Vec<String>) {
     let mut offset: usize = 0;
 
-    while !message.is_char_boundary(MAX_MESSAGE_LENGTH - offset) {
+    while !message.is_char_boundary(max_message_length - offset) {
         offset += 1;
 
-        if offset >= MAX_MESSAGE_LENGTH {
+        if offset >= max_message_length {
             return;
         }
     }
 
-    let (first, last) = message.split_at(MAX_MESSAGE_LENGTH - offset);
+    let (first, last) = message.split_at(max_message_length - offset);
     slices.push(first.to_owned());
     
-    if last.len() > MAX_MESSAGE_LENGTH {
-        split_message(last.to_owned(), slices) 
+    if last.len() > max_message_length {
+        split_message(last.to_owned(), max_message_length, slices) 
     } else {
         slices.push(last.to_owned());
     }
 }
 
-fn check_raid_file() {
-    match fs::metadata(RAID_FILE_PATH) {
-        Err(_) => {
-            match File::create(RAID_FILE_PATH) {
-                Ok(mut file) => {
-                    if let Err(err) = file.write(RAID_FILE_DEFAULT_VALUE.as_bytes()) {
-                        println!("Couldn't write to raid file: {}", err);
-                    }
-                },
-                Err(err) => {
-                    println!("Couldn't create raid file: {}", err);
+pub fn check_raid_file(file_path: &str) {
+    if fs::metadata(file_path).is_err() {
+        match File::create(file_path) {
+            Ok(mut file) => {
+                if let Err(err) = file.write_all(RAID_FILE_DEFAULT_VALUE.as_bytes()) {
+                    println!("Couldn't write to raid file: {}", err);
                 }
+            },
+            Err(err) => {
+                println!("Couldn't create raid file: {}", err);
             }
-        },
-        Ok(_) => {},
+        }
     }
 }
 
-async fn send_message(message: String, client: &TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>) {
+async fn send_message(message: String, channel: &str, bot_config: &config::Config, client: &TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>) {
     let message: String = message.trim().to_owned();
     let messages_split: Vec<&str> = message.split('$').collect();
 
     for message in messages_split {
         let mut messages: Vec<String> = Vec::new();
-        if message.len() > MAX_MESSAGE_LENGTH {
-            split_message(message.to_owned(), &mut messages);
+        if message.len() > bot_config.max_message_length {
+            split_message(message.to_owned(), bot_config.max_message_length, &mut messages);
         } else {
             messages.push(message.to_owned());
         }
 
         for message in messages {
-            let result = client.say(CHANNEL.to_owned(), message).await;
+            let result = client.say(channel.to_owned(), message).await;
             
             match result {
                 Ok(_) => {},
@@ -84,17 +106,167 @@ async fn send_message(message: String, client: &TwitchIRCClient::<SecureTCPTrans
     }
 }
 
+// Everything the message tasks share.
+struct Bot {
+    config: config::Config,
+    client: TwitchIRCClient<SecureTCPTransport, StaticLoginCredentials>,
+    sqlite_connection: Arc<ConnectionWithFullMutex>,
+    spotify: AuthCodeSpotify,
+    command_registry: CommandRegistry,
+    jobs: JobPool,
+    seeds: EntropySeeds,
+    // only set up when cooldown notices are whispered
+    whispers: Option<Whispers>,
+}
+
+enum Step {
+    // run the command with the message words starting at this index as its args
+    Run(&'static str, usize),
+    // commands like !combo only count as a use
+    CountUse(&'static str),
+    // a message decided on in the receive loop, like a permission error
+    Reply(String),
+}
+
+// A chat message after the receive loop has checked permissions and cooldowns for it.
+struct PlannedMessage {
+    channel: String,
+    user_id: String,
+    login: String,
+    display_name: String,
+    message_id: String,
+    text: String,
+    steps: Vec<Step>,
+    cooldown_messages: Vec<String>,
+}
+
+async fn handle_message(bot: Arc<Bot>, planned_message: PlannedMessage, previous_reply: Option<JoinHandle<()>>) {
+    let channel: &str = &planned_message.channel;
+    let user_id: &str = &planned_message.user_id;
+    let raid_file_path: String = bot.config.raid_file_path(channel);
+    let message_parts: Vec<&str> = planned_message.text.split(" ").collect();
+
+    let mut message: String = String::new();
+    let mut used_commands: Vec<String> = Vec::new();
+    for step in &planned_message.steps {
+        let command_name: &str = match step {
+            Step::Reply(reply) => {
+                message += &format!("{} ", reply);
+                continue;
+            },
+            Step::CountUse(command_name) => command_name,
+            Step::Run(command_name, i) => {
+                if let Some(registered_command) = bot.command_registry.find(command_name) {
+                    let context = CommandContext {
+                        args: message_parts[*i..message_parts.len()].into(),
+                        channel,
+                        user_id,
+                        sqlite_connection: &bot.sqlite_connection,
+                        spotify: &bot.spotify,
+                        raid_file_path: &raid_file_path,
+                        registry: &bot.command_registry,
+                        jobs: &bot.jobs,
+                        seeds: &bot.seeds,
+                    };
+
+                    match bot.command_registry.run(registered_command, &context).await {
+                        Ok(msg) | Err(msg) => {
+                            message += &format!("{} ", msg);
+                        }
+                    }
+                }
+
+                command_name
+            }
+        };
+
+        used_commands.push(command_name.replace("!", "emark_"));
+    }
+
+    // update commands and users data
+    let unix_time: i64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+    let sqlite_connection: Arc<ConnectionWithFullMutex> = bot.sqlite_connection.clone();
+    let (channel_name, user, login, display_name): (String, String, String, String) =
+        (channel.to_owned(), user_id.to_owned(), planned_message.login.to_owned(), planned_message.display_name.to_owned());
+    let update = bot.jobs.run_query(move || {
+        let mut result: Result<(), CommandError> = Ok(());
+
+        for command_name in used_commands {
+            if let Err(err) = database::add_command_use(&sqlite_connection, &channel_name, &command_name, &user, unix_time) {
+                println!("Command update query error: {}", err);
+                result = Err(CommandError::Message("Error: Database error.".to_owned()));
+            }
+        }
+
+        if let Err(err) = database::add_user_message(&sqlite_connection, &channel_name, &user, &login, &display_name, unix_time) {
+            println!("User update query error: {}", err);
+            result = Err(CommandError::Message("Error: Database error.".to_owned()));
+        }
+
+        result
+    });
+
+    let database_failed: bool = update.await.is_err();
+
+    // a chatter's replies go out in the order they sent their messages, database errors included
+    if let Some(previous_reply) = previous_reply {
+        if let Err(err) = previous_reply.await {
+            println!("Error in a previous reply: {}", err);
+        }
+    }
+
+    if database_failed {
+        message += "Error: Database error. ";
+    }
+
+    // send message
+    send_message(message, channel, &bot.config, &bot.client).await;
+
+    if planned_message.cooldown_messages.is_empty() {
+        return;
+    }
+
+    let cooldown_message: String = planned_message.cooldown_messages.join(" ");
+    match (bot.config.cooldown_feedback, &bot.whispers) {
+        (CooldownFeedback::Whisper, Some(whispers)) => {
+            if let Err(err) = whispers.send(user_id, &cooldown_message).await {
+                println!("Error when whispering a cooldown notice: {}", err);
+            }
+        },
+        (CooldownFeedback::Reply, _) => {
+            let reply_to: (String, String) = (channel.to_owned(), planned_message.message_id.to_owned());
+
+            if let Err(err) = bot.client.say_in_reply_to(&reply_to, cooldown_message).await {
+                println!("Error when sending a response message: {:?}", err);
+            }
+        },
+        _ => {}
+    }
+}
+
 #[tokio::main]
 pub async fn main() {
-    check_raid_file();
+    let mut command_registry: CommandRegistry = CommandRegistry::new();
+    commands::register_commands(&mut command_registry);
+
+    let bot_config: config::Config = match config::Config::load(&command_registry) {
+        Ok(bot_config) => bot_config,
+        Err(err) => {
+            println!("Couldn't start the bot: {}", err);
+            println!("Copy config.example.toml to {} and fill it in, or point TRIDENT_BOT_CONFIG at your config file.", config::DEFAULT_CONFIG_PATH);
+            return;
+        }
+    };
 
-    // default configuration is to join chat as anonymous.
-    let config = ClientConfig::new_simple(
-        StaticLoginCredentials::new(LOGIN.to_owned(), Some(OAUTH_TOKEN.to_owned()))
+    for channel in &bot_config.channels {
+        check_raid_file(&bot_config.raid_file_path(channel));
+    }
+
+    let client_config = ClientConfig::new_simple(
+        StaticLoginCredentials::new(bot_config.login.to_owned(), Some(bot_config.oauth_token.to_owned()))
     );
     let (mut incoming_messages, client) =
-        TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(config);
-    let send_client = client.clone();
+        TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(client_config);
 
     // spotify
     let rspotify_config = Config {
@@ -110,19 +282,68 @@ pub async fn main() {
     spotify.prompt_for_token(&url).await.unwrap();
 
     // sqlite
-    let sqlite_connection = sqlite::open("chat_data.sqlite").unwrap();
+    let sqlite_connection = match sqlite::Connection::open_with_full_mutex(&bot_config.database_path) {
+        Ok(sqlite_connection) => sqlite_connection,
+        Err(err) => {
+            println!("Couldn't open {}: {}", bot_config.database_path, err);
+            return;
+        }
+    };
     // spotify.add_item_to_queue("https://open.spotify.com/track/3ZEno9fORwMA1HPecdLi0R", None);
 
-    let create_commands_table_query: &str = "CREATE TABLE IF NOT EXISTS commands (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, uses INTEGER, user_id INTEGER);";
-    let create_users_table_query: &str = "CREATE TABLE IF NOT EXISTS users (user_id INTEGER PRIMARY KEY, display_name TEXT, messages INTEGER)";
-    let create_trident_rolls_table_query: &str = "CREATE TABLE IF NOT EXISTS trident_rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, durability INTEGER, unix_time INTEGER, user_id INTEGER)";
-    let create_gunpowder_rolls_table_query: &str = "CREATE TABLE IF NOT EXISTS gunpowder_rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, gunpowder INTEGER, unix_time INTEGER, user_id INTEGER)";
+    if let Err(err) = migrations::migrate(&sqlite_connection) {
+        println!("Couldn't migrate {}: {}", bot_config.database_path, err);
+        return;
+    }
+
+    if let Err(err) = database::claim_unowned_rows(&sqlite_connection, &bot_config.channels[0]) {
+        println!("Couldn't assign old chat data to {}: {}", bot_config.channels[0], err);
+    }
+
+    if let Err(err) = loot::init_loot_tables(bot_config.loot_tables_path.as_deref()) {
+        println!("Couldn't load the loot tables: {}", err);
+        return;
+    }
+
+    if let Err(err) = biomes::init_biome_tables() {
+        println!("Couldn't load the biome tables: {}", err);
+        return;
+    }
+
+    let whispers: Option<Whispers> = match (bot_config.cooldown_feedback, &bot_config.client_id) {
+        (CooldownFeedback::Whisper, Some(client_id)) => match Whispers::connect(client_id, &bot_config.oauth_token).await {
+            Ok(whispers) => Some(whispers),
+            Err(err) => {
+                println!("Couldn't set up whispers: {}", err);
+                return;
+            }
+        },
+        _ => None,
+    };
+
+    if let Some(client_id) = &bot_config.client_id {
+        streams::watch(client_id.to_owned(), bot_config.oauth_token.to_owned(), bot_config.channels.to_owned());
+    }
+
+    thunder::init_thunder_odds_tables();
+    thunder_stats::init_first_thunder_durations();
+
+    let channels: Vec<String> = bot_config.channels.to_owned();
+    let mut cooldowns: Cooldowns<SystemClock> = Cooldowns::new(SystemClock, bot_config.cooldowns.to_owned());
+    // (channel, user id) -> the task sending that chatter's latest reply
+    let mut pending_replies: HashMap<(String, String), JoinHandle<()>> = HashMap::new();
+    let jobs: JobPool = JobPool::new(bot_config.max_simulations, Duration::from_secs(bot_config.simulation_timeout));
+    let bot: Arc<Bot> = Arc::new(Bot {
+        config: bot_config,
+        client: client.clone(),
+        sqlite_connection: Arc::new(sqlite_connection),
+        spotify,
+        command_registry,
+        jobs,
+        seeds: EntropySeeds,
+        whispers,
+    });
 
-    sqlite_connection.execute(create_commands_table_query).unwrap();
-    sqlite_connection.execute(create_users_table_query).unwrap();
-    sqlite_connection.execute(create_trident_rolls_table_query).unwrap();
-    sqlite_connection.execute(create_gunpowder_rolls_table_query).unwrap();
- 
     // first thing you should do: start consuming incoming messages,
     // otherwise they will back up.
     let join_handle = tokio::spawn(async move {
@@ -130,346 +351,138 @@ pub async fn main() {
             // println!("{:#?}", server_message);
             match server_message {
                 ServerMessage::UserNotice(notice) => {
-                    match notice.event {
-                        UserNoticeEvent::Raid { viewer_count: _, profile_image_url: _ } => { 
-                            let user: String = notice.sender.name;
-                            let error_message: String = "Error: Couldn't automatically update the !raid command.".to_owned();
-                            let mut value: String = String::new();
-
-                            match OpenOptions::new().write(true).read(true).open(RAID_FILE_PATH) {
-                                Ok(mut file) => {
-                                    match file.read_to_string(&mut value) {
-                                        Ok(_) => {
-                                            let raid_message: String;
-
-                                            if value == RAID_FILE_DEFAULT_VALUE {
-                                                raid_message = format!("{}. PagBounce", user);
-                                            } else {
-                                                let new_value = value.strip_suffix(". PagBounce");
-
-                                                match new_value {
-                                                    Some(v) => {
-                                                        raid_message = format!("{}, {}. PagBounce", v, user)
-                                                    },
-                                                    None => {
-                                                        raid_message = format!("{}, {}. PagBounce", value, user)
-                                                    }
+                    if let UserNoticeEvent::Raid { viewer_count: _, profile_image_url: _ } = notice.event {
+                        let user: String = notice.sender.name;
+                        let channel: String = notice.channel_login;
+                        let error_message: String = "Error: Couldn't automatically update the !raid command.".to_owned();
+                        let mut value: String = String::new();
+
+                        match OpenOptions::new().write(true).read(true).open(bot.config.raid_file_path(&channel)) {
+                            Ok(mut file) => {
+                                match file.read_to_string(&mut value) {
+                                    Ok(_) => {
+                                        let raid_message: String;
+
+                                        if value == RAID_FILE_DEFAULT_VALUE {
+                                            raid_message = format!("{}. PagBounce", user);
+                                        } else {
+                                            let new_value = value.strip_suffix(". PagBounce");
+
+                                            match new_value {
+                                                Some(v) => {
+                                                    raid_message = format!("{}, {}. PagBounce", v, user)
+                                                },
+                                                None => {
+                                                    raid_message = format!("{}, {}. PagBounce", value, user)
                                                 }
                                             }
+                                        }
 
-                                            match file.set_len(0) {
-                                                Ok(_) => {
-                                                    match file.write(raid_message.as_bytes()) {
-                                                        Err(err) => {
-                                                            println!("{} {}", error_message, err);
-                                                            send_message(error_message, &send_client).await;
-                                                        }
-                                                        Ok(_) => {
-                                                            let message: String = format!("Automatically updated the !raid command to: {}", raid_message);
-                                                            send_message(message, &send_client).await;
-                                                        }
+                                        match file.set_len(0) {
+                                            Ok(_) => {
+                                                match file.write_all(raid_message.as_bytes()) {
+                                                    Err(err) => {
+                                                        println!("{} {}", error_message, err);
+                                                        send_message(error_message, &channel, &bot.config, &bot.client).await;
+                                                    }
+                                                    Ok(_) => {
+                                                        let message: String = format!("Automatically updated the !raid command to: {}", raid_message);
+                                                        send_message(message, &channel, &bot.config, &bot.client).await;
                                                     }
                                                 }
-                                                Err(err) => {
-                                                    println!("{} {}", error_message, err);
-                                                    send_message(error_message, &send_client).await;
-                                                }
                                             }
-                                        },
-                                        Err(err) => {
-                                            println!("{} {}", error_message, err);
-                                            send_message(error_message, &send_client).await;
+                                            Err(err) => {
+                                                println!("{} {}", error_message, err);
+                                                send_message(error_message, &channel, &bot.config, &bot.client).await;
+                                            }
                                         }
+                                    },
+                                    Err(err) => {
+                                        println!("{} {}", error_message, err);
+                                        send_message(error_message, &channel, &bot.config, &bot.client).await;
                                     }
-                                },
-                                Err(err) => {
-                                    println!("{} {}", error_message, err);
-                                    send_message(error_message, &send_client).await;
                                 }
+                            },
+                            Err(err) => {
+                                println!("{} {}", error_message, err);
+                                send_message(error_message, &channel, &bot.config, &bot.client).await;
                             }
-                         },
-                         _ => {}
+                        }
                     }
                 },
                 ServerMessage::Privmsg(msg) => {
-                    let user_id = msg.sender.id;
-                    let user_display_name = msg.sender.name;
+                    let user_id: String = msg.sender.id;
+                    let channel: String = msg.channel_login;
+                    let permission: PermissionLevel = PermissionLevel::from_badges(&msg.badges);
                     let message_parts: Vec<&str> = msg.message_text.split(" ").collect();
 
-                    // let mut banan: bool = false;
-                    // let mut ban_duration: u32 = 0;
-                    
+                    // Permissions and cooldowns are decided here, in the order messages arrive.
+                    // Running the commands happens in a separate task.
+                    let mut steps: Vec<Step> = Vec::new();
+                    let mut cooldown_messages: Vec<String> = Vec::new();
                     let mut call_all_commands: bool = false;
-                    let mut message: String = String::new();
                     for (i, command) in message_parts.iter().enumerate() {
-                        let args: Vec<&str> = message_parts[i..message_parts.len()].into();
-
-                        let result: Option<Result<String, String>> = if call_all_commands || i == 0 {
-                            let cmd: &str = &command.to_lowercase();
-                            match cmd {
-                                "!combo" => {
-                                    call_all_commands = true;
-                                    None
-                                },
-                                "!nomic" => {
-                                    Some(commands::nomic())
-                                },
-                                "!rolltrident" => {
-                                    // let conn = sqlite_connection;
-                                    if let Ok((resp, dur)) = commands::rolltrident(&sqlite_connection, &user_id) {
-                                        if dur == 0 || dur == 1 {
-                                            // ban_duration = if dur == 1 { 300 } else { 600 }; 
-                                            // banan = true;
-                                        }
-
-                                        Some(Ok(resp))
-                                    } else {
-                                        Some(Err("Error message".to_owned()))
-                                    }
-                                },
-                                "!age" => {
-                                    Some(commands::age())
-                                },
-                                "!rollseed" => {
-                                    Some(commands::rollseed())
-                                },
-                                "!findseed" => {
-                                    Some(commands::findseed())
-                                },
-                                "!weather" => {
-                                    Some(commands::weather())
-                                },
-                                "!thunderodds" => {
-                                    Some(commands::thunderodds(args))
-                                },
-                                "!skullodds" => {
-                                    Some(commands::skullodds(args))
-                                },
-                                "!tridentodds" => {
-                                    Some(commands::tridentodds(args))
-                                },
-                                "!rolldrowned" => {
-                                    Some(commands::rolldrowned(args))
-                                },
-                                "fishinge" => {
-                                    Some(commands::fishinge())
-                                },
-                                "!song" => {
-                                    Some(commands::song(spotify.clone()).await)
-                                },
-                                "!wr" => {
-                                    Some(commands::wr())
-                                },
-                                "!pb" => {
-                                    Some(commands::pb())
-                                },
-                                "!topcommands" => {
-                                    Some(commands::topcommands(&sqlite_connection))
-                                },
-                                "!topchatters" => {
-                                    Some(commands::topchatters(&sqlite_connection))
-                                },
-                                "!topspammers" => {
-                                    Some(commands::topspammers(&sqlite_connection))
-                                },
-                                "!rollgp" => {
-                                    Some(commands::rollgp(&sqlite_connection, &user_id))
-                                },
-                                "!rollbiome" => {
-                                    Some(commands::rollbiome())
-                                },  
-                                "!commands" => {
-                                    Some(commands::commands())
-                                },
-                                "!rollcats" => {
-                                    Some(commands::rollcats(args))
-                                },
-                                "!rollblazerods" => {
-                                    Some(commands::rollblazerods(args))
-                                },
-                                "!tridentjuicers" => {
-                                    Some(commands::tridentjuicers(&sqlite_connection))
-                                },
-                                "!gpjuicers" => {
-                                    Some(commands::gpjuicers(&sqlite_connection))
-                                },
-                                "!dailytridentjuicers" => {
-                                    Some(commands::dailytridentjuicers(&sqlite_connection))
-                                },
-                                "!tridentnoobs" => {
-                                    Some(commands::tridentnoobs(&sqlite_connection))
-                                },
-                                "!rollskulls" => { 
-                                    Some(commands::rollskulls(args)) 
-                                },
-                                "!commandstats" => {
-                                    Some(commands::commandstats(&sqlite_connection, args))
-                                },
-                                "!raid" => {
-                                    check_raid_file();
-                                    Some(commands::raid(RAID_FILE_PATH))
-                                },
-                                "!rollphantoms" => {
-                                    Some(commands::rollphantoms())
-                                },
-                                "!rollaassg" => {
-                                    Some(commands::rollaassg())
-                                },
-                                "!route" => {
-                                    Some(commands::route())
-                                },
-                                "!rollsilence" => {
-                                    Some(commands::rollsilence())
-                                },
-                                "!hdwghfix" => {
-                                    Some(commands::hdwghfix())
-                                },
-                                "!caamel" => {
-                                    Some(commands::caamel())
-                                },
-                                "!rollheavycore" => {
-                                    Some(commands::rollheavycore())
-                                },
-                                "!seed" => {
-                                    Some(commands::seed())
-                                }
-                                "!lootingseedroute" => {
-                                    Some(commands::lootingseedroute())
-                                }
-                                _ => { None }
-                            }
+                        let registered_command = if call_all_commands || i == 0 {
+                            bot.command_registry
+                                .find(command)
+                                .filter(|registered_command| bot.config.is_command_enabled(&channel, registered_command.name()))
                         } else {
                             None
                         };
 
-                        // let result: Option<Result<String, String>> = None;
-
-                        // ban
-                        // if banan {
-                        //     ban(&user_id, "Your trident roll sucks.", ban_duration).await;
-                        // }
-
-                        // update commands
-                        if result != None || command == &"!combo" {
-                            let fixed_command_name: &str = &command.replace("!", "emark_");
-                            let command_update_query: &str = &format!("UPDATE commands SET uses = uses + 1 WHERE name = '{}' AND user_id = {};", fixed_command_name, user_id);
-                            let command_set_query: &str = &format!("INSERT INTO commands (name, uses, user_id) VALUES ('{}', 1, {});", fixed_command_name, user_id);
-                            
-                            let query_result = sqlite_connection.execute(command_update_query);
-                            let mut is_error: bool = false;
-                            let mut error_message = String::new();
-
-                            match query_result {
-                                Err(err) => {
-                                    is_error = true;
-
-                                    println!("command update query error: {}", err);
-
-                                    if let Err(msg_send_error) = send_client.say(CHANNEL.to_owned(), "Error: Database error.".to_owned()).await {
-                                        println!("Error when sending a response message: {:?}", msg_send_error);
-                                    }
-                                },
-                                Ok(_) => {
-                                    if sqlite_connection.change_count() == 0 {
-                                        let query_result = sqlite_connection.execute(command_set_query);
-                                            
-                                        if let Err(query_error) = query_result {
-                                            println!("Command set query error: {}", query_error);
-                                            
-                                            if let Err(msg_send_error) = send_client.say(CHANNEL.to_owned(), "Error: Database error.".to_owned()).await {
-                                                println!("Error when sending a response message: {:?}", msg_send_error);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                        let registered_command = match registered_command {
+                            Some(registered_command) => registered_command,
+                            None => continue
+                        };
 
-                            
-                            // fix error handling eventually
-                            // if is_error {
-                            //     if let Some(error_message) = query_error.message {
-                            //         if error_message == format!("no such column: {}", fixed_command_name) {
-                            //             let query_result = sqlite_connection.execute(command_set_query);
-                                    
-                            //             if let Err(query_error) = query_result {
-                            //                 println!("Command set query error: {}", query_error);
-                                            
-                            //                 if let Err(msg_send_error) = send_client.say(CHANNEL.to_owned(), "Error: Database error.".to_owned()).await {
-                            //                     println!("Error when sending a response message: {:?}", msg_send_error);
-                            //                 }
-                            //             }
-                            //         } else {
-                            //             if let Err(msg_send_error) = send_client.say(CHANNEL.to_owned(), "Error: Database error.".to_owned()).await {
-                            //                 println!("Error when sending a response message: {:?}", msg_send_error);
-                            //             }
-                            //         }
-                            //     }
-                            // }
+                        // commands above the chatter's role are answered with an error instead of running
+                        if let Err(error) = registry::check_permission(registered_command, permission) {
+                            steps.push(Step::Reply(error));
+                            continue;
                         }
 
-                        match result {
-                            Some(value) => {
-                                match value {
-                                    Ok(msg) | Err(msg) => {
-                                        message += &format!("{} ", msg);
-                                    }
-                                }
-                            },  
-                            None => {}
+                        // commands on cooldown are skipped like any other word
+                        if let Err(wait) = cooldowns.try_use(&channel, registered_command.name(), &user_id, permission >= PermissionLevel::Moderator) {
+                            cooldown_messages.push(format!("{} is on cooldown for {}s.", registered_command.name(), wait.as_secs_f64().ceil()));
+                            continue;
                         }
 
-                    }
-
-                    // update users data
-                    let user_update_query: &str = &format!("UPDATE users SET messages = messages + 1 WHERE user_id = {};", user_id);
-                    let user_set_query: &str = &format!("INSERT INTO users (user_id, display_name, messages) VALUES ({}, '{}', 1);", user_id, user_display_name);
-
-                    let query_result = sqlite_connection.execute(user_update_query);
-
-                    match query_result {
-                        Err(err) => {
-                            println!("User update query error: {}", err);
-
-                            if let Err(msg_send_error) = send_client.say(CHANNEL.to_owned(), "Error: Database error.".to_owned()).await {
-                                println!("Error when sending a response message: {:?}", msg_send_error);
-                            }
-                        },
-                        Ok(_) => {
-                            if sqlite_connection.change_count() == 0 {
-                                let query_result = sqlite_connection.execute(user_set_query);
-                                    
-                                if let Err(query_error) = query_result {
-                                    println!("Command set query error: {}", query_error);
-                                    
-                                    if let Err(msg_send_error) = send_client.say(CHANNEL.to_owned(), "Error: Database error.".to_owned()).await {
-                                        println!("Error when sending a response message: {:?}", msg_send_error);
-                                    }
-                                }
-                            }
+                        if registered_command.chains() {
+                            call_all_commands = true;
+                            steps.push(Step::CountUse(registered_command.name()));
+                        } else {
+                            steps.push(Step::Run(registered_command.name(), i));
                         }
                     }
 
-                    // send message
-                    send_message(message, &send_client).await;
-                    // let result = send_client.say(CHANNEL.to_owned(), message).await;
-
-                    // match result {
-                    //     Ok(_) => {},
-                    //     Err(err) => {
-                    //         println!("Error when sending a response message: {:?}", err);
-                    //     }
-                    // }
+                    let reply_key: (String, String) = (channel.to_owned(), user_id.to_owned());
+                    let previous_reply: Option<JoinHandle<()>> = pending_replies.remove(&reply_key);
+                    pending_replies.retain(|_, reply| !reply.is_finished());
+
+                    let planned_message = PlannedMessage {
+                        channel,
+                        user_id,
+                        login: msg.sender.login,
+                        display_name: msg.sender.name,
+                        message_id: msg.message_id,
+                        text: msg.message_text.to_owned(),
+                        steps,
+                        cooldown_messages,
+                    };
+                    let reply = tokio::spawn(handle_message(bot.clone(), planned_message, previous_reply));
+                    pending_replies.insert(reply_key, reply);
                 },
                 _ => {}
             }
         }   
     });
     
-    // join a channel
+    // join the channels
     // This function only returns an error if the passed channel login name is malformed,
-    // so in this simple case where the channel name is hardcoded we can ignore the potential
-    // error with `unwrap`.
-    client.join(CHANNEL.to_owned()).unwrap();
+    // and the config loader already rejects malformed channel names, so we can `unwrap`.
+    for channel in channels {
+        client.join(channel).unwrap();
+    }
     println!("Bot is now running!");
     
     // keep the tokio executor alive.
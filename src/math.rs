@@ -1,15 +1,139 @@
-pub fn binomial_coefficient(n: u128, k: u128) -> u128 {  
-    if n < k { return 0; }
-    if n == k { return 1; }
-    if k == 0 { return 1; }
-    if k == 1 { return n; }
-    if k > n / 2 {
-        binomial_coefficient(n, n - k - 1) * (n - k + 1) / k
-    } else {
-        binomial_coefficient(n, k - 1) * (n - k + 1) / k
+use rand::distributions::Distribution;
+use rand::Rng;
+use rand_distr::Binomial;
+
+// Returns None when the coefficient doesn't fit in a u128.
+pub fn binomial_coefficient(n: u128, k: u128) -> Option<u128> {
+    if n < k { return Some(0); }
+    let k: u128 = k.min(n - k);
+
+    // C(n, i + 1) = C(n, i) * (n - i) / (i + 1). Dividing by the common factor first keeps every
+    // step exact and only overflows when the next coefficient itself doesn't fit.
+    let mut coefficient: u128 = 1;
+    for i in 0..k {
+        let divisor: u128 = i + 1;
+        let common: u128 = gcd(coefficient, divisor);
+        coefficient = (coefficient / common).checked_mul((n - i) / (divisor / common))?;
+    }
+
+    Some(coefficient)
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
     }
+
+    a
+}
+
+// ln(C(n, k)), for coefficients too large for binomial_coefficient. Takes min(k, n - k) steps.
+fn ln_binomial_coefficient(n: u128, k: u128) -> f64 {
+    let k: u128 = k.min(n - k);
+
+    (0..k).map(|i| ((n - i) as f64).ln() - ((i + 1) as f64).ln()).sum()
 }
 
+// Probability of exactly k successes in n trials with success probability p.
 pub fn bernoullis_scheme(n: u128, k: u128, p: f64) -> f64 {
-    binomial_coefficient(n, k) as f64 * p.powi(k as i32) * (1.0 - p).powi((n - k) as i32)
-}
\ No newline at end of file
+    if k > n { return 0.0; }
+    if p <= 0.0 { return if k == 0 { 1.0 } else { 0.0 }; }
+    if p >= 1.0 { return if k == n { 1.0 } else { 0.0 }; }
+
+    match binomial_coefficient(n, k) {
+        Some(coefficient) if n <= i32::MAX as u128 => {
+            coefficient as f64 * p.powi(k as i32) * (1.0 - p).powi((n - k) as i32)
+        },
+        _ => (ln_binomial_coefficient(n, k) + k as f64 * p.ln() + (n - k) as f64 * (1.0 - p).ln()).exp(),
+    }
+}
+
+// Probability of k or more successes in n trials with success probability p.
+pub fn binomial_tail(n: u128, k: u128, p: f64) -> f64 {
+    if k > n { return 0.0; }
+    if k == 0 { return 1.0; }
+    if p <= 0.0 { return 0.0; }
+    if p >= 1.0 { return 1.0; }
+
+    // P(X = i + 1) = P(X = i) * (n - i) / (i + 1) * p / (1 - p)
+    let mut term: f64 = bernoullis_scheme(n, k, p);
+    let mut sum: f64 = 0.0;
+    for i in k..=n {
+        sum += term;
+        term *= (n - i) as f64 / (i + 1) as f64 * p / (1.0 - p);
+
+        if term == 0.0 { break; }
+    }
+
+    sum.min(1.0)
+}
+
+pub fn binomial<R: Rng>(trials: u64, p: f64, rng: &mut R) -> u64 {
+    Binomial::new(trials, p.clamp(0.0, 1.0)).unwrap().sample(rng)
+}
+
+// How many of `trials` land in each outcome, drawn as a chain of binomials. The last outcome gets
+// whatever is left, so the odds should add up to 1.
+pub fn multinomial<R: Rng>(trials: u64, probabilities: &[f64], rng: &mut R) -> Vec<u64> {
+    let mut counts: Vec<u64> = Vec::with_capacity(probabilities.len());
+    let mut trials_left: u64 = trials;
+    let mut probability_left: f64 = 1.0;
+
+    for (i, probability) in probabilities.iter().enumerate() {
+        let count: u64 = if i == probabilities.len() - 1 {
+            trials_left
+        } else if probability_left <= 0.0 {
+            0
+        } else {
+            binomial(trials_left, probability / probability_left, rng)
+        };
+
+        counts.push(count);
+        trials_left -= count;
+        probability_left -= probability;
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binomial_coefficient_is_exact() {
+        assert_eq!(binomial_coefficient(5, 2), Some(10));
+        assert_eq!(binomial_coefficient(5, 0), Some(1));
+        assert_eq!(binomial_coefficient(5, 5), Some(1));
+        assert_eq!(binomial_coefficient(3, 5), Some(0));
+        assert_eq!(binomial_coefficient(60, 30), Some(118_264_581_564_861_424));
+        // one of the largest coefficients that fit in a u128
+        assert_eq!(binomial_coefficient(131, 65), Some(188_694_833_082_770_476_622_296_176_145_946_360_850));
+    }
+
+    #[test]
+    fn binomial_coefficient_reports_overflow() {
+        assert_eq!(binomial_coefficient(132, 66), None);
+        assert_eq!(binomial_coefficient(10_000, 5_000), None);
+    }
+
+    #[test]
+    fn bernoullis_scheme_works_past_u128() {
+        let p: f64 = 0.055;
+        let total: f64 = (0..=10_000).map(|k| bernoullis_scheme(10_000, k, p)).sum();
+
+        assert!((total - 1.0).abs() < 1e-9, "probabilities sum to {}", total);
+        assert!(bernoullis_scheme(10_000, 550, p) > 0.0);
+        assert_eq!(bernoullis_scheme(10, 11, p), 0.0);
+    }
+
+    #[test]
+    fn binomial_tail_matches_the_sum_of_its_terms() {
+        let p: f64 = 0.035;
+        let expected: f64 = (40..=1_000).map(|k| bernoullis_scheme(1_000, k, p)).sum();
+
+        assert!((binomial_tail(1_000, 40, p) - expected).abs() < 1e-12);
+        assert_eq!(binomial_tail(1_000, 0, p), 1.0);
+        assert_eq!(binomial_tail(1_000, 1_001, p), 0.0);
+    }
+}
@@ -0,0 +1,209 @@
+use sqlite::{Connection, State, Statement};
+use std::fmt;
+
+// Ordered schema migrations for chat_data.sqlite. The version stored in the database (PRAGMA user_version)
+// is the number of migrations that have already been applied, so new migrations must only ever be appended.
+const MIGRATIONS: &[&str] = &[
+    // 1: the tables that used to be created in main()
+    "CREATE TABLE IF NOT EXISTS commands (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, uses INTEGER, user_id INTEGER);
+     CREATE TABLE IF NOT EXISTS users (user_id INTEGER PRIMARY KEY, display_name TEXT, messages INTEGER);
+     CREATE TABLE IF NOT EXISTS trident_rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, durability INTEGER, unix_time INTEGER, user_id INTEGER);
+     CREATE TABLE IF NOT EXISTS gunpowder_rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, gunpowder INTEGER, unix_time INTEGER, user_id INTEGER);",
+    // 2: indexes for the per-user and per-command lookups
+    "CREATE INDEX IF NOT EXISTS commands_name_user_id ON commands (name, user_id);
+     CREATE INDEX IF NOT EXISTS trident_rolls_user_id ON trident_rolls (user_id);
+     CREATE INDEX IF NOT EXISTS gunpowder_rolls_user_id ON gunpowder_rolls (user_id);",
+    // 3: per-channel data. Rows from before this get an empty channel until database::claim_unowned_rows assigns them.
+    "ALTER TABLE commands ADD COLUMN channel TEXT NOT NULL DEFAULT '';
+     ALTER TABLE trident_rolls ADD COLUMN channel TEXT NOT NULL DEFAULT '';
+     ALTER TABLE gunpowder_rolls ADD COLUMN channel TEXT NOT NULL DEFAULT '';
+     CREATE TABLE users_per_channel (channel TEXT NOT NULL DEFAULT '', user_id INTEGER NOT NULL, display_name TEXT, messages INTEGER, PRIMARY KEY (channel, user_id));
+     INSERT INTO users_per_channel (user_id, display_name, messages) SELECT user_id, display_name, messages FROM users;
+     DROP TABLE users;
+     ALTER TABLE users_per_channel RENAME TO users;
+     DROP INDEX IF EXISTS commands_name_user_id;
+     CREATE INDEX commands_channel_name_user_id ON commands (channel, name, user_id);
+     CREATE INDEX trident_rolls_channel ON trident_rolls (channel, durability);
+     CREATE INDEX gunpowder_rolls_channel ON gunpowder_rolls (channel, gunpowder);",
+    // 4: what everyone caught with !fish, one row per item and command
+    "CREATE TABLE catches (id INTEGER PRIMARY KEY AUTOINCREMENT, channel TEXT NOT NULL, user_id INTEGER, item TEXT, category TEXT, count INTEGER, seed INTEGER, unix_time INTEGER);
+     CREATE INDEX catches_channel_category ON catches (channel, category, user_id);",
+    // 5: every roll command in one table, scored, with its result as JSON and the seed that replays it.
+    // The trident and gunpowder rolls saved before this are moved over without a seed.
+    "CREATE TABLE rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, channel TEXT NOT NULL, user_id INTEGER, command TEXT NOT NULL, score REAL, result TEXT, seed INTEGER, unix_time INTEGER);
+     CREATE INDEX rolls_channel_command_user_id ON rolls (channel, command, user_id, unix_time);
+     INSERT INTO rolls (channel, user_id, command, score, result, unix_time)
+         SELECT channel, user_id, '!rolltrident', durability, '{\"durability\":' || durability || '}', unix_time FROM trident_rolls ORDER BY id;
+     INSERT INTO rolls (channel, user_id, command, score, result, unix_time)
+         SELECT channel, user_id, '!rollgp', gunpowder, '{\"gunpowder\":' || gunpowder || '}', unix_time FROM gunpowder_rolls ORDER BY id;
+     DROP TABLE trident_rolls;
+     DROP TABLE gunpowder_rolls;",
+    // 6: when every chatter was first seen. Chatters from before this get their first roll's time,
+    // or none if they never rolled.
+    "ALTER TABLE users ADD COLUMN first_seen INTEGER;
+     UPDATE users SET first_seen = (SELECT MIN(unix_time) FROM rolls WHERE rolls.channel = users.channel AND rolls.user_id = users.user_id);",
+    // 7: everyone's login, and every login and display name they had. Chatters from before this
+    // get theirs with their next message.
+    "ALTER TABLE users ADD COLUMN login TEXT;
+     CREATE INDEX users_channel_login ON users (channel, login);
+     CREATE TABLE user_names (id INTEGER PRIMARY KEY AUTOINCREMENT, user_id INTEGER NOT NULL, login TEXT NOT NULL, display_name TEXT NOT NULL, unix_time INTEGER);
+     CREATE INDEX user_names_user_id ON user_names (user_id);
+     CREATE INDEX user_names_login ON user_names (login);",
+    // 8: every message and command use with its time, for leaderboards over a time window. The
+    // counters in users and commands keep the all time totals, which go back further than this.
+    "CREATE TABLE messages (id INTEGER PRIMARY KEY AUTOINCREMENT, channel TEXT NOT NULL, user_id INTEGER, unix_time INTEGER);
+     CREATE INDEX messages_channel_unix_time ON messages (channel, unix_time);
+     CREATE TABLE command_uses (id INTEGER PRIMARY KEY AUTOINCREMENT, channel TEXT NOT NULL, user_id INTEGER, name TEXT NOT NULL, unix_time INTEGER);
+     CREATE INDEX command_uses_channel_unix_time ON command_uses (channel, unix_time, name);",
+    // 9: one commands row per chatter and command, so a use can be counted with a single upsert.
+    // Rows that were added twice are merged into the first one.
+    "UPDATE commands SET uses = (SELECT SUM(uses) FROM commands AS same WHERE same.channel = commands.channel AND same.name = commands.name AND same.user_id IS commands.user_id)
+         WHERE id = (SELECT MIN(id) FROM commands AS same WHERE same.channel = commands.channel AND same.name = commands.name AND same.user_id IS commands.user_id);
+     DELETE FROM commands WHERE id > (SELECT MIN(id) FROM commands AS same WHERE same.channel = commands.channel AND same.name = commands.name AND same.user_id IS commands.user_id);
+     DROP INDEX commands_channel_name_user_id;
+     CREATE UNIQUE INDEX commands_channel_name_user_id ON commands (channel, name, user_id);",
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Database(sqlite::Error),
+    NewerSchema { found: i64, supported: i64 },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::Database(err) => write!(f, "database error: {}", err),
+            MigrationError::NewerSchema { found, supported } => write!(
+                f,
+                "the database schema is version {}, but this build only knows up to version {}; refusing to start",
+                found,
+                supported
+            ),
+        }
+    }
+}
+
+impl From<sqlite::Error> for MigrationError {
+    fn from(err: sqlite::Error) -> Self {
+        MigrationError::Database(err)
+    }
+}
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.len() as i64
+}
+
+pub fn schema_version(sqlite_connection: &Connection) -> Result<i64, sqlite::Error> {
+    let mut statement: Statement = sqlite_connection.prepare("PRAGMA user_version;")?;
+
+    match statement.next()? {
+        State::Row => statement.read::<i64, _>(0),
+        State::Done => Ok(0),
+    }
+}
+
+// Brings the database up to the latest schema version. All pending migrations run in one transaction,
+// so a failing migration leaves the database exactly as it was.
+pub fn migrate(sqlite_connection: &Connection) -> Result<i64, MigrationError> {
+    let version: i64 = schema_version(sqlite_connection)?;
+
+    if version > latest_version() {
+        return Err(MigrationError::NewerSchema { found: version, supported: latest_version() });
+    }
+
+    if version == latest_version() {
+        return Ok(version);
+    }
+
+    sqlite_connection.execute("BEGIN;")?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let result = sqlite_connection
+            .execute(migration)
+            .and_then(|_| sqlite_connection.execute(format!("PRAGMA user_version = {};", i + 1)));
+
+        if let Err(err) = result {
+            if let Err(rollback_err) = sqlite_connection.execute("ROLLBACK;") {
+                println!("Migration rollback error: {}", rollback_err);
+            }
+
+            return Err(MigrationError::Database(err));
+        }
+    }
+
+    sqlite_connection.execute("COMMIT;")?;
+
+    Ok(latest_version())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // chat_data.sqlite as created by the bot before it had migrations
+    const UNVERSIONED_FIXTURE: &str = "
+        CREATE TABLE commands (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, uses INTEGER, user_id INTEGER);
+        CREATE TABLE users (user_id INTEGER PRIMARY KEY, display_name TEXT, messages INTEGER);
+        CREATE TABLE trident_rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, durability INTEGER, unix_time INTEGER, user_id INTEGER);
+        CREATE TABLE gunpowder_rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, gunpowder INTEGER, unix_time INTEGER, user_id INTEGER);
+        INSERT INTO users (user_id, display_name, messages) VALUES (1, 'chatter', 10);
+        INSERT INTO commands (name, uses, user_id) VALUES ('emark_rolltrident', 4, 1);
+        INSERT INTO commands (name, uses, user_id) VALUES ('emark_rolltrident', 2, 1);
+        INSERT INTO trident_rolls (durability, unix_time, user_id) VALUES (250, 1700000000000, 1);
+        INSERT INTO gunpowder_rolls (gunpowder, unix_time, user_id) VALUES (30, 1700000000000, 1);
+    ";
+
+    fn count(sqlite_connection: &Connection, query: &str) -> i64 {
+        let mut statement: Statement = sqlite_connection.prepare(query).unwrap();
+        statement.next().unwrap();
+        statement.read::<i64, _>(0).unwrap()
+    }
+
+    #[test]
+    fn migrates_fresh_database() {
+        let sqlite_connection: Connection = sqlite::open(":memory:").unwrap();
+
+        assert_eq!(migrate(&sqlite_connection).unwrap(), latest_version());
+        assert_eq!(schema_version(&sqlite_connection).unwrap(), latest_version());
+        assert_eq!(count(&sqlite_connection, "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'rolls';"), 1);
+        assert_eq!(count(&sqlite_connection, "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('trident_rolls', 'gunpowder_rolls', 'findseed_rolls');"), 0);
+    }
+
+    #[test]
+    fn migrates_unversioned_database_forward() {
+        let sqlite_connection: Connection = sqlite::open(":memory:").unwrap();
+        sqlite_connection.execute(UNVERSIONED_FIXTURE).unwrap();
+
+        assert_eq!(migrate(&sqlite_connection).unwrap(), latest_version());
+        assert_eq!(count(&sqlite_connection, "SELECT messages FROM users WHERE user_id = 1;"), 10);
+        assert_eq!(count(&sqlite_connection, "SELECT uses FROM commands WHERE name = 'emark_rolltrident';"), 6);
+        assert_eq!(count(&sqlite_connection, "SELECT COUNT(*) FROM rolls WHERE command = '!rolltrident' AND result LIKE '{\"durability\":%}';"), 1);
+        assert_eq!(count(&sqlite_connection, "SELECT COUNT(*) FROM rolls WHERE command = '!rollgp' AND score = 30;"), 1);
+        assert_eq!(count(&sqlite_connection, "SELECT COUNT(*) FROM users WHERE channel = '';"), 1);
+        assert_eq!(count(&sqlite_connection, "SELECT first_seen FROM users WHERE user_id = 1;"), 1700000000000);
+        assert_eq!(count(&sqlite_connection, "SELECT COUNT(*) FROM users WHERE login IS NULL;"), 1);
+        assert_eq!(count(&sqlite_connection, "SELECT COUNT(*) FROM rolls WHERE channel = '';"), 2);
+    }
+
+    #[test]
+    fn migrating_twice_is_a_no_op() {
+        let sqlite_connection: sqlite::ConnectionWithFullMutex = crate::database::test_connection();
+
+        assert_eq!(migrate(&sqlite_connection).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        let sqlite_connection: Connection = sqlite::open(":memory:").unwrap();
+        sqlite_connection.execute(format!("PRAGMA user_version = {};", latest_version() + 1)).unwrap();
+
+        match migrate(&sqlite_connection) {
+            Err(MigrationError::NewerSchema { found, supported }) => {
+                assert_eq!(found, latest_version() + 1);
+                assert_eq!(supported, latest_version());
+            },
+            other => panic!("expected a newer schema error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use rand::distributions::Distribution;
+use rand::Rng;
+use rand_distr::{Gamma, Poisson};
+
+use crate::loot::{loot_tables, LootContext, LootTables};
+use crate::math::multinomial;
+
+// Counts up to this are simulated one kill at a time. Above it the totals are drawn straight from
+// the binomial, multinomial and negative binomial distributions those kills add up to, which gives
+// the same odds but takes the same time for a million kills as for ten thousand.
+pub const SAMPLING_THRESHOLD: u64 = 10_000;
+
+const DROWNED: &str = "trident_bot:entities/drowned";
+const BLAZE: &str = "minecraft:entities/blaze";
+const WITHER_SKELETON: &str = "minecraft:entities/wither_skeleton";
+
+#[derive(Debug, PartialEq, Default)]
+pub struct DrownedDrops {
+    pub rotten_flesh: u64,
+    pub copper_ingots: u64,
+    pub nautilus_shells: u64,
+    pub tridents: u64,
+    pub fishing_rods: u64,
+}
+
+pub fn roll_drowned<R: Rng>(kills: u64, looting_level: u32, rng: &mut R) -> DrownedDrops {
+    let totals: Vec<(String, u64)> = roll_totals(loot_tables(), DROWNED, kills, &LootContext::player_kill(looting_level), rng);
+    let total = |item: &str| totals.iter().find(|(name, _)| name == item).map_or(0, |(_, count)| *count);
+
+    DrownedDrops {
+        rotten_flesh: total("minecraft:rotten_flesh"),
+        copper_ingots: total("minecraft:copper_ingot"),
+        nautilus_shells: total("minecraft:nautilus_shell"),
+        tridents: total("minecraft:trident"),
+        fishing_rods: total("minecraft:fishing_rod"),
+    }
+}
+
+// number of cats of each of the 10 variants
+pub fn roll_cats<R: Rng>(cats: u64, rng: &mut R) -> [u64; 10] {
+    if cats <= SAMPLING_THRESHOLD {
+        roll_cats_per_cat(cats, rng)
+    } else {
+        sample_cats(cats, rng)
+    }
+}
+
+// number of blazes killed to get at least `rods` blaze rods
+pub fn roll_blaze_kills<R: Rng>(rods: u64, looting_level: u32, rng: &mut R) -> u64 {
+    roll_kills_for(loot_tables(), BLAZE, "minecraft:blaze_rod", rods, &LootContext::player_kill(looting_level), rng)
+        .expect("blazes killed by a player drop rods")
+}
+
+// number of wither skeletons killed to get `skulls` skulls
+pub fn roll_wither_skeleton_kills<R: Rng>(skulls: u64, looting_level: u32, rng: &mut R) -> u64 {
+    roll_kills_for(loot_tables(), WITHER_SKELETON, "minecraft:wither_skeleton_skull", skulls, &LootContext::player_kill(looting_level), rng)
+        .expect("wither skeletons killed by a player drop skulls")
+}
+
+// How many of every item the table can drop `rolls` rolls of it give, in the table's order.
+pub fn roll_totals<R: Rng>(tables: &LootTables, name: &str, rolls: u64, context: &LootContext, rng: &mut R) -> Vec<(String, u64)> {
+    if rolls <= SAMPLING_THRESHOLD {
+        roll_totals_per_roll(tables, name, rolls, context, rng)
+    } else {
+        tables.sample_totals(name, rolls, context, rng)
+    }
+}
+
+// Kills (or rolls) until the table has dropped at least `target` of `item`, or None if it can't drop it.
+pub fn roll_kills_for<R: Rng>(tables: &LootTables, name: &str, item: &str, target: u64, context: &LootContext, rng: &mut R) -> Option<u64> {
+    let counts: Vec<f64> = tables.count_distribution(name, item, context);
+    if target > 0 && counts.iter().skip(1).all(|odds| *odds <= 0.0) {
+        return None;
+    }
+
+    Some(if target <= SAMPLING_THRESHOLD {
+        roll_kills_for_per_kill(tables, name, item, target, context, rng)
+    } else {
+        sample_kills_for(&counts, target, rng)
+    })
+}
+
+fn roll_totals_per_roll<R: Rng>(tables: &LootTables, name: &str, rolls: u64, context: &LootContext, rng: &mut R) -> Vec<(String, u64)> {
+    let mut totals: Vec<(String, u64)> = tables.items(name).into_iter().map(|item| (item, 0)).collect();
+
+    for _ in 0..rolls {
+        for stack in tables.roll(name, context, rng) {
+            if let Some((_, total)) = totals.iter_mut().find(|(item, _)| *item == stack.item) {
+                *total += stack.count;
+            }
+        }
+    }
+
+    totals
+}
+
+fn roll_cats_per_cat<R: Rng>(cats: u64, rng: &mut R) -> [u64; 10] {
+    let mut variants: [u64; 10] = [0; 10];
+
+    for _ in 0..cats {
+        variants[rng.gen_range(0..10)] += 1;
+    }
+
+    variants
+}
+
+fn sample_cats<R: Rng>(cats: u64, rng: &mut R) -> [u64; 10] {
+    let mut variants: [u64; 10] = [0; 10];
+    variants.copy_from_slice(&multinomial(cats, &[0.1; 10], rng));
+
+    variants
+}
+
+fn roll_kills_for_per_kill<R: Rng>(tables: &LootTables, name: &str, item: &str, target: u64, context: &LootContext, rng: &mut R) -> u64 {
+    let mut dropped: u64 = 0;
+    let mut kills: u64 = 0;
+
+    while dropped < target {
+        dropped += tables.roll(name, context, rng).iter().filter(|stack| stack.item == item).map(|stack| stack.count).sum::<u64>();
+        kills += 1;
+    }
+
+    kills
+}
+
+// `counts[n]` is the odds of a kill dropping n of the item.
+fn sample_kills_for<R: Rng>(counts: &[f64], target: u64, rng: &mut R) -> u64 {
+    let drops: f64 = 1.0 - counts[0];
+    let amounts: Vec<f64> = counts[1..].iter().map(|odds| odds / drops).collect();
+    let most: u64 = amounts.len() as u64;
+
+    // Kills that drop at least one. As long as a whole batch can't reach the target even if every
+    // kill drops the most, the hunt can't end inside the batch, so it's drawn at once.
+    let mut dropped: u64 = 0;
+    let mut dropping_kills: u64 = 0;
+    while dropped < target {
+        let batch: u64 = ((target - dropped) / most).max(1);
+
+        dropped += multinomial(batch, &amounts, rng)
+            .iter()
+            .enumerate()
+            .map(|(amount, count)| (amount as u64 + 1) * count)
+            .sum::<u64>();
+        dropping_kills += batch;
+    }
+
+    // plus the kills that dropped nothing along the way
+    dropping_kills + negative_binomial(dropping_kills, drops, rng)
+}
+
+// Failures before the `successes`-th success, as a Poisson draw with a gamma distributed mean.
+fn negative_binomial<R: Rng>(successes: u64, p: f64, rng: &mut R) -> u64 {
+    if successes == 0 || p >= 1.0 {
+        return 0;
+    }
+
+    let mean: f64 = Gamma::new(successes as f64, (1.0 - p) / p).unwrap().sample(rng);
+    if mean <= 0.0 {
+        return 0;
+    }
+
+    Poisson::new(mean).unwrap().sample(rng) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    const RUNS: usize = 1_000;
+
+    fn rng() -> Xoroshiro128PlusPlus {
+        Xoroshiro128PlusPlus::seed_from_u64(1234)
+    }
+
+    fn mean_and_variance(values: &[f64]) -> (f64, f64) {
+        let mean: f64 = values.iter().sum::<f64>() / values.len() as f64;
+        let variance: f64 = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+
+        (mean, variance)
+    }
+
+    // Both paths must agree on the mean (within 5 standard errors) and on the spread.
+    fn assert_same_distribution(name: &str, per_kill: &[f64], sampled: &[f64]) {
+        let (per_kill_mean, per_kill_variance) = mean_and_variance(per_kill);
+        let (sampled_mean, sampled_variance) = mean_and_variance(sampled);
+        let standard_error: f64 = ((per_kill_variance + sampled_variance) / RUNS as f64).sqrt();
+
+        assert!(
+            (per_kill_mean - sampled_mean).abs() <= 5.0 * standard_error,
+            "{}: mean {} per kill vs {} sampled",
+            name,
+            per_kill_mean,
+            sampled_mean
+        );
+        assert!(
+            (0.75..=1.33).contains(&(sampled_variance / per_kill_variance)),
+            "{}: variance {} per kill vs {} sampled",
+            name,
+            per_kill_variance,
+            sampled_variance
+        );
+    }
+
+    #[test]
+    fn drowned_paths_agree() {
+        let mut rng = rng();
+
+        for looting_level in [0, 3] {
+            let context: LootContext = LootContext::player_kill(looting_level);
+            let per_kill: Vec<Vec<(String, u64)>> = (0..RUNS).map(|_| roll_totals_per_roll(loot_tables(), DROWNED, 300, &context, &mut rng)).collect();
+            let sampled: Vec<Vec<(String, u64)>> = (0..RUNS).map(|_| loot_tables().sample_totals(DROWNED, 300, &context, &mut rng)).collect();
+
+            for (i, (item, _)) in per_kill[0].iter().enumerate() {
+                let per_kill: Vec<f64> = per_kill.iter().map(|totals| totals[i].1 as f64).collect();
+                let sampled: Vec<f64> = sampled.iter().map(|totals| totals[i].1 as f64).collect();
+
+                assert_same_distribution(item, &per_kill, &sampled);
+            }
+        }
+    }
+
+    fn covariance(a: &[f64], b: &[f64]) -> f64 {
+        let (a_mean, _) = mean_and_variance(a);
+        let (b_mean, _) = mean_and_variance(b);
+
+        a.iter().zip(b).map(|(a, b)| (a - a_mean) * (b - b_mean)).sum::<f64>() / (a.len() - 1) as f64
+    }
+
+    // A drowned drops a trident or a fishing rod from the same pool, never both, so the sampled totals
+    // can't add up to more picks than kills and have to keep the per-kill covariance between them.
+    #[test]
+    fn entries_of_a_pool_exclude_each_other() {
+        const KILLS: u64 = 300;
+        let mut rng = rng();
+        let mut tables: LootTables = LootTables::bundled().unwrap();
+        tables.add(
+            "test:equipment",
+            r#"{"pools": [{"rolls": 1, "conditions": [{"condition": "random_chance", "chance": 0.5}], "entries": [{"type": "item", "name": "trident", "weight": 10}, {"type": "item", "name": "fishing_rod", "weight": 6}]}]}"#,
+        ).unwrap();
+        tables.add("test:drowned", r#"{"pools": [{"rolls": 1, "entries": [{"type": "loot_table", "name": "test:equipment"}]}]}"#).unwrap();
+
+        let context: LootContext = LootContext::default();
+        let per_kill: Vec<Vec<(String, u64)>> = (0..RUNS).map(|_| roll_totals_per_roll(&tables, "test:drowned", KILLS, &context, &mut rng)).collect();
+        let sampled: Vec<Vec<(String, u64)>> = (0..RUNS).map(|_| tables.sample_totals("test:drowned", KILLS, &context, &mut rng)).collect();
+        assert!(sampled.iter().all(|totals| totals[0].1 + totals[1].1 <= KILLS));
+
+        let column = |runs: &[Vec<(String, u64)>], i: usize| -> Vec<f64> { runs.iter().map(|totals| totals[i].1 as f64).collect() };
+        let (per_kill_tridents, per_kill_rods) = (column(&per_kill, 0), column(&per_kill, 1));
+        let (sampled_tridents, sampled_rods) = (column(&sampled, 0), column(&sampled, 1));
+        let per_kill_covariance: f64 = covariance(&per_kill_tridents, &per_kill_rods);
+        let sampled_covariance: f64 = covariance(&sampled_tridents, &sampled_rods);
+
+        // a covariance estimate has a standard error of about sqrt(var(a) * var(b) / runs), and both paths are estimates
+        let standard_error: f64 = (2.0 * mean_and_variance(&per_kill_tridents).1 * mean_and_variance(&per_kill_rods).1 / RUNS as f64).sqrt();
+        assert!(sampled_covariance < 0.0, "sampled covariance {}", sampled_covariance);
+        assert!(
+            (per_kill_covariance - sampled_covariance).abs() <= 5.0 * standard_error,
+            "covariance {} per kill vs {} sampled",
+            per_kill_covariance,
+            sampled_covariance
+        );
+        assert_same_distribution("tridents", &per_kill_tridents, &sampled_tridents);
+        assert_same_distribution("fishing rods", &per_kill_rods, &sampled_rods);
+    }
+
+    #[test]
+    fn cat_paths_agree() {
+        let mut rng = rng();
+        let per_cat: Vec<[u64; 10]> = (0..RUNS).map(|_| roll_cats_per_cat(1_000, &mut rng)).collect();
+        let sampled: Vec<[u64; 10]> = (0..RUNS).map(|_| sample_cats(1_000, &mut rng)).collect();
+
+        for variant in 0..10 {
+            let per_cat: Vec<f64> = per_cat.iter().map(|variants| variants[variant] as f64).collect();
+            let sampled: Vec<f64> = sampled.iter().map(|variants| variants[variant] as f64).collect();
+
+            assert_same_distribution("cats", &per_cat, &sampled);
+        }
+
+        assert!(sampled.iter().all(|variants| variants.iter().sum::<u64>() == 1_000));
+    }
+
+    fn assert_kill_paths_agree(name: &str, item: &str, target: u64) {
+        let mut rng = rng();
+
+        for looting_level in [0, 1, 3] {
+            let context: LootContext = LootContext::player_kill(looting_level);
+            let counts: Vec<f64> = loot_tables().count_distribution(name, item, &context);
+            let per_kill: Vec<f64> = (0..RUNS).map(|_| roll_kills_for_per_kill(loot_tables(), name, item, target, &context, &mut rng) as f64).collect();
+            let sampled: Vec<f64> = (0..RUNS).map(|_| sample_kills_for(&counts, target, &mut rng) as f64).collect();
+
+            assert_same_distribution(name, &per_kill, &sampled);
+        }
+    }
+
+    #[test]
+    fn blaze_paths_agree() {
+        assert_kill_paths_agree(BLAZE, "minecraft:blaze_rod", 100);
+    }
+
+    #[test]
+    fn wither_skeleton_paths_agree() {
+        assert_kill_paths_agree(WITHER_SKELETON, "minecraft:wither_skeleton_skull", 20);
+    }
+
+    #[test]
+    fn huge_counts_are_sampled() {
+        let mut rng = rng();
+
+        let drops: DrownedDrops = roll_drowned(1_000_000_000, 3, &mut rng);
+        assert!(drops.rotten_flesh > 1_000_000_000);
+        assert!(drops.tridents > drops.fishing_rods);
+
+        assert!(roll_blaze_kills(1_000_000_000, 3, &mut rng) > 400_000_000);
+        assert!(roll_wither_skeleton_kills(1_000_000_000, 0, &mut rng) > 30_000_000_000);
+        assert_eq!(roll_cats(1_000_000_000, &mut rng).iter().sum::<u64>(), 1_000_000_000);
+    }
+}
@@ -0,0 +1,60 @@
+use twitch_irc::message::Badge;
+
+// Who can use a command. Every level can also use the commands of the levels below it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum PermissionLevel {
+    Everyone,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+impl PermissionLevel {
+    pub fn from_badges(badges: &[Badge]) -> PermissionLevel {
+        badges
+            .iter()
+            .map(|badge| match badge.name.as_str() {
+                "broadcaster" => PermissionLevel::Broadcaster,
+                "moderator" => PermissionLevel::Moderator,
+                "vip" => PermissionLevel::Vip,
+                _ => PermissionLevel::Everyone,
+            })
+            .max()
+            .unwrap_or(PermissionLevel::Everyone)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            PermissionLevel::Everyone => "everyone",
+            PermissionLevel::Vip => "VIPs and moderators",
+            PermissionLevel::Moderator => "moderators",
+            PermissionLevel::Broadcaster => "the broadcaster",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn badges(names: &[&str]) -> Vec<Badge> {
+        names.iter().map(|name| Badge { name: name.to_string(), version: "1".to_owned() }).collect()
+    }
+
+    #[test]
+    fn highest_badge_wins() {
+        assert_eq!(PermissionLevel::from_badges(&badges(&[])), PermissionLevel::Everyone);
+        assert_eq!(PermissionLevel::from_badges(&badges(&["subscriber", "premium"])), PermissionLevel::Everyone);
+        assert_eq!(PermissionLevel::from_badges(&badges(&["vip", "subscriber"])), PermissionLevel::Vip);
+        assert_eq!(PermissionLevel::from_badges(&badges(&["subscriber", "moderator"])), PermissionLevel::Moderator);
+        assert_eq!(PermissionLevel::from_badges(&badges(&["broadcaster", "subscriber"])), PermissionLevel::Broadcaster);
+    }
+
+    #[test]
+    fn higher_levels_include_lower_ones() {
+        assert!(PermissionLevel::Broadcaster >= PermissionLevel::Moderator);
+        assert!(PermissionLevel::Moderator >= PermissionLevel::Vip);
+        assert!(PermissionLevel::Vip >= PermissionLevel::Everyone);
+        assert!(PermissionLevel::Vip < PermissionLevel::Moderator);
+    }
+}
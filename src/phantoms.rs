@@ -1,37 +1,234 @@
-use rand::{Rng, SeedableRng, rngs::StdRng}; 
+use rand::Rng;
 
-// 1:10:34.65-1:19:25.35
-const ONE_MINUTE_IN_TICKS: u64 = 1200;
-const FIRST_POSSIBLE_SPAWN_TIME_IN_NIGHT: u64 = 693;
-const LAST_POSSIBLE_SPAWN_TIME_IN_NIGHT: u64 = 11307;
+// Phantom spawning from 1.16's PhantomSpawner. Every 1200 to 2380 ticks (a multiple of 20) the game
+// tries to spawn phantoms around each player. An attempt only goes ahead at night (a sky darkness of 5
+// or more, 12693 to 23307 ticks into each day in clear weather), when the player can see the sky, if
+// the local difficulty beats a random number from 0 to 3 and if a random number below the time since
+// the player last rested is at least 72000. It then spawns 1 to difficulty + 1 phantoms.
+const FIRST_ATTEMPT: u64 = 1;
+const ATTEMPT_STEP: u64 = 20;
+// attempts are 60 to 119 steps apart
+const ATTEMPT_STEPS: std::ops::RangeInclusive<u64> = 60..=119;
+const DAY_LENGTH: u64 = 24_000;
+const NIGHT: std::ops::RangeInclusive<u64> = 12_693..=23_307;
+const INSOMNIA_TICKS: u64 = 72_000;
+// Past this (10000 minutes) the simulation gives up on a spawn.
+pub const LATEST_SPAWN: u64 = 12_000_000;
+// how full the moon is in each of its 8 phases, starting from a full moon
+const MOON_SIZES: [f64; 8] = [1.0, 0.75, 0.5, 0.25, 0.0, 0.25, 0.5, 0.75];
 
-pub fn get_phantoms_spawn_time() -> (u64, u64) {
-    // time for 2 or more phantoms spawn with 2,25 local difficulty
-    let mut night: u64 = 4;
-    let mut time: u64 = (night * 20 - 10) * ONE_MINUTE_IN_TICKS + FIRST_POSSIBLE_SPAWN_TIME_IN_NIGHT;
-    let mut night_end: u64 = (night * 20 - 10) * ONE_MINUTE_IN_TICKS + LAST_POSSIBLE_SPAWN_TIME_IN_NIGHT;
-    let local_difficulty: f64 = 2.25;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy = 1,
+    Normal = 2,
+    Hard = 3,
+}
 
-    let mut rng: StdRng = SeedableRng::from_entropy();
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn largest_group(self) -> u64 {
+        self as u64 + 1
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhantomModel {
+    pub difficulty: Difficulty,
+    // A fixed local difficulty. Without one it's worked out from the world time and the moon like in
+    // game, for a chunk nobody has spent time in yet.
+    pub local_difficulty: Option<f64>,
+    // moon phase on the first night, 0 being a full moon like in a new world
+    pub moon_phase: u64,
+    // ticks since the player last slept when the world starts
+    pub time_since_rest: u64,
+    // the spawn only counts with at least this many phantoms
+    pub min_group: u64,
+    // odds that the player is under open sky when the game tries to spawn phantoms
+    pub sky_exposure: f64,
+}
+
+impl Default for PhantomModel {
+    // a new hard world where the player never sleeps and stays outside, waiting for 2 or more phantoms
+    fn default() -> Self {
+        PhantomModel {
+            difficulty: Difficulty::Hard,
+            local_difficulty: None,
+            moon_phase: 0,
+            time_since_rest: 0,
+            min_group: 2,
+            sky_exposure: 1.0,
+        }
+    }
+}
+
+impl PhantomModel {
+    // 1.16's LocalDifficulty with no inhabited time: it grows over the first 21 days after day 3,
+    // plus a bit on fuller moons, but the moon can't add more than the days have.
+    pub fn local_difficulty(&self, time: u64) -> f64 {
+        if let Some(local_difficulty) = self.local_difficulty {
+            return local_difficulty;
+        }
+
+        let days: f64 = ((time as f64 - INSOMNIA_TICKS as f64) / 1_440_000.0).clamp(0.0, 1.0) * 0.25;
+        let moon_size: f64 = MOON_SIZES[((time / DAY_LENGTH + self.moon_phase) % 8) as usize];
+        let mut moon: f64 = (moon_size * 0.25).clamp(0.0, days);
+        if self.difficulty == Difficulty::Easy {
+            moon *= 0.5;
+        }
+
+        self.difficulty as u64 as f64 * (0.75 + days + moon)
+    }
+
+    // Odds that an attempt at `time` spawns a big enough group.
+    pub fn spawn_odds(&self, time: u64) -> f64 {
+        if !NIGHT.contains(&(time % DAY_LENGTH)) {
+            return 0.0;
+        }
+
+        let harder: f64 = (self.local_difficulty(time) / 3.0).clamp(0.0, 1.0);
+        let time_since_rest: u64 = (self.time_since_rest + time).max(1);
+        let insomnia: f64 = time_since_rest.saturating_sub(INSOMNIA_TICKS) as f64 / time_since_rest as f64;
+
+        self.sky_exposure * harder * insomnia * self.group_odds()
+    }
+
+    fn group_odds(&self) -> f64 {
+        let largest: u64 = self.difficulty.largest_group();
+        (largest + 1).saturating_sub(self.min_group.max(1)) as f64 / largest as f64
+    }
+
+    // A group big enough is possible at all.
+    pub fn can_spawn(&self) -> bool {
+        self.sky_exposure > 0.0 && self.group_odds() > 0.0 && self.local_difficulty.is_none_or(|local_difficulty| local_difficulty > 0.0)
+    }
+}
+
+// Time and size of the first big enough group, or None if there isn't one by LATEST_SPAWN.
+pub fn get_phantoms_spawn_time<R: Rng>(model: &PhantomModel, rng: &mut R) -> Option<(u64, u64)> {
+    if !model.can_spawn() {
+        return None;
+    }
+
+    let mut time: u64 = FIRST_ATTEMPT;
 
-    loop {
-        time += rng.gen_range(ONE_MINUTE_IN_TICKS..=(ONE_MINUTE_IN_TICKS * 2));
+    while time <= LATEST_SPAWN {
+        if NIGHT.contains(&(time % DAY_LENGTH))
+            && rng.gen::<f64>() < model.sky_exposure
+            && rng.gen_range(0.0..3.0) < model.local_difficulty(time)
+            && rng.gen_range(0..(model.time_since_rest + time).max(1)) >= INSOMNIA_TICKS
+        {
+            let phantoms: u64 = rng.gen_range(1..=model.difficulty.largest_group());
+            if phantoms >= model.min_group {
+                return Some((time, phantoms));
+            }
+        }
+
+        time += rng.gen_range(ATTEMPT_STEPS) * ATTEMPT_STEP;
+    }
+
+    None
+}
+
+// Odds that a big enough group has spawned by `time`. [s] holds the odds that there's an attempt
+// at step s and nothing spawned before it; the attempts after it spread evenly over the next steps.
+pub fn phantom_odds_by(model: &PhantomModel, time: u64) -> f64 {
+    if time < FIRST_ATTEMPT || !model.can_spawn() {
+        return 0.0;
+    }
 
-        if time > night_end {
-            night += 1;
-            time = (night * 20 - 10) * ONE_MINUTE_IN_TICKS + FIRST_POSSIBLE_SPAWN_TIME_IN_NIGHT;
-            night_end = (night * 20 - 10) * ONE_MINUTE_IN_TICKS + LAST_POSSIBLE_SPAWN_TIME_IN_NIGHT;
+    let steps: usize = ((time - FIRST_ATTEMPT) / ATTEMPT_STEP) as usize;
+    let (nearest, farthest): (usize, usize) = (*ATTEMPT_STEPS.start() as usize, *ATTEMPT_STEPS.end() as usize);
+    let spread: f64 = (farthest - nearest + 1) as f64;
 
-            continue;
+    // changes to the running odds, so spreading one step over the next 60 is two additions
+    let mut changes: Vec<f64> = vec![0.0; steps + farthest + 2];
+    let mut attempt: f64 = 1.0;
+    let mut spawned: f64 = 0.0;
+
+    for step in 0..=steps {
+        if step > 0 {
+            attempt += changes[step];
+        }
+
+        let odds: f64 = model.spawn_odds(FIRST_ATTEMPT + step as u64 * ATTEMPT_STEP);
+        spawned += attempt * odds;
+
+        let next: f64 = attempt * (1.0 - odds) / spread;
+        changes[step + nearest] += next;
+        changes[step + farthest + 1] -= next;
+        // the first attempt has nothing before it
+        if step == 0 {
+            attempt = 0.0;
         }
+    }
+
+    spawned.min(1.0)
+}
 
-        if rng.gen_range(0.0..3.0) < local_difficulty {
-            if rng.gen_range(0.0..1.0) <= (time - 72000) as f64 / time as f64 {
-                let spawns: u64 = rng.gen_range(1..=4);
-                if spawns >= 2 {
-                    return (time, spawns);
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    #[test]
+    fn local_difficulty_matches_the_game() {
+        let hard: PhantomModel = PhantomModel::default();
+        let easy: PhantomModel = PhantomModel { difficulty: Difficulty::Easy, ..hard };
+
+        assert_eq!(hard.local_difficulty(0), 2.25);
+        assert_eq!(easy.local_difficulty(0), 0.75);
+        // after 63 days the days add their full 0.25, and a full moon adds as much again
+        assert_eq!(hard.local_difficulty(64 * DAY_LENGTH), 3.0 * 1.25);
+        assert_eq!(hard.local_difficulty(68 * DAY_LENGTH), 3.0 * 1.0);
+        assert_eq!(PhantomModel { local_difficulty: Some(1.5), ..hard }.local_difficulty(0), 1.5);
+    }
+
+    #[test]
+    fn odds_match_the_rolls() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let models: [PhantomModel; 3] = [
+            PhantomModel::default(),
+            PhantomModel { difficulty: Difficulty::Normal, min_group: 3, sky_exposure: 0.5, ..Default::default() },
+            PhantomModel { time_since_rest: 100_000, moon_phase: 4, local_difficulty: Some(1.0), ..Default::default() },
+        ];
+        let worlds: u64 = 5_000;
+
+        for model in models {
+            let spawns: Vec<u64> = (0..worlds).filter_map(|_| get_phantoms_spawn_time(&model, &mut rng).map(|(time, _)| time)).collect();
+
+            for minutes in [80, 120, 200] {
+                let time: u64 = minutes * 1200;
+                let rolled: f64 = spawns.iter().filter(|spawn| **spawn <= time).count() as f64 / worlds as f64;
+                let exact: f64 = phantom_odds_by(&model, time);
+                let tolerance: f64 = 5.0 * (exact * (1.0 - exact) / worlds as f64).sqrt() + 1e-3;
+
+                assert!((exact - rolled).abs() <= tolerance, "{:?} at {} minutes: {} vs rolled {}", model, minutes, exact, rolled);
             }
-        };
+        }
+    }
+
+    #[test]
+    fn impossible_groups_never_spawn() {
+        let mut rng: Xoroshiro128PlusPlus = Xoroshiro128PlusPlus::seed_from_u64(1234);
+        let indoors: PhantomModel = PhantomModel { sky_exposure: 0.0, ..Default::default() };
+        let too_many: PhantomModel = PhantomModel { difficulty: Difficulty::Easy, min_group: 3, ..Default::default() };
+
+        for model in [indoors, too_many] {
+            assert_eq!(get_phantoms_spawn_time(&model, &mut rng), None);
+            assert_eq!(phantom_odds_by(&model, LATEST_SPAWN), 0.0);
+        }
+
+        // nobody has insomnia before the third night
+        assert_eq!(phantom_odds_by(&PhantomModel::default(), 3 * DAY_LENGTH), 0.0);
+        assert!(phantom_odds_by(&PhantomModel { time_since_rest: INSOMNIA_TICKS, ..Default::default() }, DAY_LENGTH) > 0.0);
     }
-}
\ No newline at end of file
+}
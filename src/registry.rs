@@ -0,0 +1,361 @@
+use rspotify::AuthCodeSpotify;
+use sqlite::{Connection, ConnectionWithFullMutex};
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::args::take_seed;
+use crate::database;
+use crate::jobs::JobPool;
+use crate::permissions::PermissionLevel;
+use crate::rng::{Roll, SeedSource};
+
+pub type CommandResult = Result<String, CommandError>;
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = CommandResult> + Send + 'a>>;
+
+#[derive(Debug, PartialEq)]
+pub enum CommandError {
+    // the dispatcher turns this into "Error: Invalid syntax; {usage}"
+    InvalidSyntax,
+    Message(String),
+}
+
+// Everything the dispatcher can hand to a command.
+pub struct CommandContext<'a> {
+    pub args: Vec<&'a str>,
+    pub channel: &'a str,
+    pub user_id: &'a str,
+    pub sqlite_connection: &'a Arc<ConnectionWithFullMutex>,
+    pub spotify: &'a AuthCodeSpotify,
+    pub raid_file_path: &'a str,
+    pub registry: &'a CommandRegistry,
+    pub jobs: &'a JobPool,
+    pub seeds: &'a dyn SeedSource,
+}
+
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn usage(&self) -> &'static str {
+        self.name()
+    }
+
+    // The lowest role that can use the command, checked by the dispatcher before it runs.
+    fn permission(&self) -> PermissionLevel {
+        PermissionLevel::Everyone
+    }
+
+    // Commands that return true make every following word of the message run as a command too.
+    fn chains(&self) -> bool {
+        false
+    }
+
+    fn execute<'a>(&'a self, context: &'a CommandContext<'a>) -> CommandFuture<'a>;
+}
+
+// The context a command needs, together with the function that handles it.
+// Database handlers get the channel the command was used in right after the connection, and run on
+// the job pool like simulations so a query never blocks the chat loop.
+// Roll handlers get a seeded Roll; a "seed=1234" argument replays the roll with that seed.
+pub enum Handler {
+    Static(fn() -> CommandResult),
+    Args(fn(Vec<&str>) -> CommandResult),
+    Roll(fn(&mut Roll, Vec<&str>) -> CommandResult),
+    // CPU-heavy handlers, run on the job pool instead of the chat loop
+    Simulation(fn(Vec<&str>) -> CommandResult),
+    RollSimulation(fn(&mut Roll, Vec<&str>) -> CommandResult),
+    // connection, channel, user id, args
+    DatabaseUserArgs(fn(&Connection, &str, &str, Vec<&str>) -> CommandResult),
+    // connection, channel, user id, roll, args; run on the job pool with the simulations, like !fish
+    DatabaseUserRoll(fn(&Connection, &str, &str, &mut Roll, Vec<&str>) -> CommandResult),
+    RaidFile(fn(&str) -> CommandResult),
+    RaidFileArgs(fn(&str, Vec<&str>) -> CommandResult),
+    Registry(fn(&CommandRegistry) -> CommandResult),
+    Spotify(fn(AuthCodeSpotify) -> CommandFuture<'static>),
+}
+
+pub struct SimpleCommand {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    usage: Option<&'static str>,
+    permission: PermissionLevel,
+    handler: Handler,
+}
+
+impl SimpleCommand {
+    pub fn new(name: &'static str, handler: Handler) -> Self {
+        SimpleCommand { name, aliases: &[], usage: None, permission: PermissionLevel::Everyone, handler }
+    }
+
+    pub fn aliases(mut self, aliases: &'static [&'static str]) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn usage(mut self, usage: &'static str) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    pub fn permission(mut self, permission: PermissionLevel) -> Self {
+        self.permission = permission;
+        self
+    }
+}
+
+impl Command for SimpleCommand {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        self.aliases
+    }
+
+    fn usage(&self) -> &'static str {
+        self.usage.unwrap_or(self.name)
+    }
+
+    fn permission(&self) -> PermissionLevel {
+        self.permission
+    }
+
+    fn execute<'a>(&'a self, context: &'a CommandContext<'a>) -> CommandFuture<'a> {
+        let name: &'static str = self.name;
+
+        let result: CommandResult = match self.handler {
+            Handler::Static(handler) => handler(),
+            Handler::Args(handler) => handler(context.args.clone()),
+            Handler::Roll(handler) => {
+                let (mut roll, args): (Roll, Vec<&str>) = match roll(context) {
+                    Ok(roll) => roll,
+                    Err(err) => return Box::pin(future::ready(Err(err))),
+                };
+                let result: CommandResult = handler(&mut roll, args);
+
+                let (sqlite_connection, channel, user_id) = owned_context(context);
+                return context.jobs.run_query(move || finish_roll(&sqlite_connection, &channel, &user_id, name, &roll, result));
+            },
+            Handler::RollSimulation(handler) => {
+                let (mut roll, args): (Roll, Vec<&str>) = match roll(context) {
+                    Ok(roll) => roll,
+                    Err(err) => return Box::pin(future::ready(Err(err))),
+                };
+                let args: Vec<String> = owned_args(&args);
+
+                let (sqlite_connection, channel, user_id) = owned_context(context);
+                return context.jobs.run_job(move || {
+                    let result: CommandResult = handler(&mut roll, borrowed_args(&args));
+                    finish_roll(&sqlite_connection, &channel, &user_id, name, &roll, result)
+                });
+            },
+            Handler::DatabaseUserArgs(handler) => {
+                let args: Vec<String> = owned_args(&context.args);
+
+                let (sqlite_connection, channel, user_id) = owned_context(context);
+                return context.jobs.run_query(move || handler(&sqlite_connection, &channel, &user_id, borrowed_args(&args)));
+            },
+            Handler::DatabaseUserRoll(handler) => {
+                let (mut roll, args): (Roll, Vec<&str>) = match roll(context) {
+                    Ok(roll) => roll,
+                    Err(err) => return Box::pin(future::ready(Err(err))),
+                };
+                let args: Vec<String> = owned_args(&args);
+
+                let (sqlite_connection, channel, user_id) = owned_context(context);
+                return context.jobs.run_job(move || {
+                    let result: CommandResult = handler(&sqlite_connection, &channel, &user_id, &mut roll, borrowed_args(&args));
+                    finish_roll(&sqlite_connection, &channel, &user_id, name, &roll, result)
+                });
+            },
+            Handler::RaidFile(handler) => handler(context.raid_file_path),
+            Handler::RaidFileArgs(handler) => handler(context.raid_file_path, context.args.clone()),
+            Handler::Registry(handler) => handler(context.registry),
+            Handler::Simulation(handler) => {
+                return context.jobs.run(handler, owned_args(&context.args));
+            },
+            Handler::Spotify(handler) => {
+                return handler(context.spotify.clone());
+            }
+        };
+
+        Box::pin(future::ready(result))
+    }
+}
+
+// Jobs outlive the message they came from, so they get their own copies of it.
+pub fn owned_context(context: &CommandContext) -> (Arc<ConnectionWithFullMutex>, String, String) {
+    (context.sqlite_connection.clone(), context.channel.to_owned(), context.user_id.to_owned())
+}
+
+pub fn owned_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+pub fn borrowed_args(args: &[String]) -> Vec<&str> {
+    args.iter().map(|arg| arg.as_str()).collect()
+}
+
+// The chatter's seed if they gave one, otherwise a new one from the seed source.
+fn roll<'a>(context: &CommandContext<'a>) -> Result<(Roll, Vec<&'a str>), CommandError> {
+    let mut args: Vec<&str> = context.args.clone();
+
+    let roll: Roll = match take_seed(&mut args)? {
+        Some(seed) => Roll::new(seed, true),
+        None => Roll::new(context.seeds.next_seed(), false),
+    };
+
+    Ok((roll, args))
+}
+
+// Saves what the roll came out as, unless it's a replay or the command failed, and marks replays.
+fn finish_roll(sqlite_connection: &Connection, channel: &str, user_id: &str, command: &str, roll: &Roll, result: CommandResult) -> CommandResult {
+    if let (Ok(_), Some(record), false) = (&result, &roll.record, roll.replay) {
+        let unix_time: i64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+
+        if let Err(err) = database::add_roll(sqlite_connection, channel, user_id, command, record, roll.seed, unix_time) {
+            println!("Roll database error: {}", err);
+        }
+    }
+
+    replay_note(roll, result)
+}
+
+// Replays say which seed they used, so they can't be passed off as real rolls.
+fn replay_note(roll: &Roll, result: CommandResult) -> CommandResult {
+    match result {
+        Ok(message) if roll.replay => Ok(format!("Replay of seed {}: {}", roll.seed, message)),
+        result => result,
+    }
+}
+
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        CommandRegistry::default()
+    }
+
+    pub fn register<T: Command + 'static>(&mut self, command: T) {
+        self.commands.push(Box::new(command));
+    }
+
+    // Finds a command by its name or one of its aliases, ignoring case.
+    pub fn find(&self, name: &str) -> Option<&dyn Command> {
+        let name: String = name.to_lowercase();
+
+        self.commands
+            .iter()
+            .find(|command| command.name() == name || command.aliases().contains(&name.as_str()))
+            .map(|command| command.as_ref())
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &dyn Command> {
+        self.commands.iter().map(|command| command.as_ref())
+    }
+
+    pub async fn run(&self, command: &dyn Command, context: &CommandContext<'_>) -> Result<String, String> {
+        match command.execute(context).await {
+            Ok(message) => Ok(message),
+            Err(CommandError::InvalidSyntax) => Err(format!("Error: Invalid syntax; {}", command.usage())),
+            Err(CommandError::Message(message)) => Err(message),
+        }
+    }
+}
+
+// Returns the message for chatters whose role is too low to use the command.
+pub fn check_permission(command: &dyn Command, permission: PermissionLevel) -> Result<(), String> {
+    if permission >= command.permission() {
+        return Ok(());
+    }
+
+    Err(format!("Error: {} can only be used by {}.", command.name(), command.permission().description()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok() -> CommandResult {
+        Ok(String::new())
+    }
+
+    #[test]
+    fn permission_is_checked_against_the_chatters_role() {
+        let public: SimpleCommand = SimpleCommand::new("!nomic", Handler::Static(ok));
+        let mod_only: SimpleCommand = SimpleCommand::new("!resetraid", Handler::Static(ok)).permission(PermissionLevel::Moderator);
+
+        assert_eq!(check_permission(&public, PermissionLevel::Everyone), Ok(()));
+        assert_eq!(check_permission(&mod_only, PermissionLevel::Moderator), Ok(()));
+        assert_eq!(check_permission(&mod_only, PermissionLevel::Broadcaster), Ok(()));
+        assert_eq!(
+            check_permission(&mod_only, PermissionLevel::Vip),
+            Err("Error: !resetraid can only be used by moderators.".to_owned())
+        );
+    }
+
+    struct FixedSeed;
+
+    impl SeedSource for FixedSeed {
+        fn next_seed(&self) -> u64 {
+            1234
+        }
+    }
+
+    fn scored(roll: &mut Roll, _message_parts: Vec<&str>) -> CommandResult {
+        roll.record(Some(7.0), serde_json::json!({ "score": 7 }));
+        Ok("rolled".to_owned())
+    }
+
+    fn scored_with_database(_sqlite_connection: &Connection, _channel: &str, _user_id: &str, roll: &mut Roll, message_parts: Vec<&str>) -> CommandResult {
+        scored(roll, message_parts)
+    }
+
+    fn saved_rolls(sqlite_connection: &Connection) -> Vec<(String, i64)> {
+        let mut statement = sqlite_connection.prepare("SELECT command, seed FROM rolls ORDER BY id;").unwrap();
+        let mut rows: Vec<(String, i64)> = Vec::new();
+        while let Ok(sqlite::State::Row) = statement.next() {
+            rows.push((statement.read::<String, _>("command").unwrap(), statement.read::<i64, _>("seed").unwrap()));
+        }
+        rows
+    }
+
+    #[tokio::test]
+    async fn rolls_are_saved_unless_replayed() {
+        let sqlite_connection: Arc<ConnectionWithFullMutex> = Arc::new(crate::database::test_connection());
+        let spotify: AuthCodeSpotify = AuthCodeSpotify::default();
+        let registry: CommandRegistry = CommandRegistry::new();
+        let jobs: JobPool = JobPool::new(1, std::time::Duration::from_secs(5));
+        let commands: [SimpleCommand; 3] = [
+            SimpleCommand::new("!rollscore", Handler::Roll(scored)),
+            SimpleCommand::new("!simulatescore", Handler::RollSimulation(scored)),
+            SimpleCommand::new("!fishscore", Handler::DatabaseUserRoll(scored_with_database)),
+        ];
+
+        for args in [vec!["!rollscore"], vec!["!rollscore", "seed=99"], vec!["!simulatescore"], vec!["!fishscore"], vec!["!fishscore", "seed=5"]] {
+            let command: &SimpleCommand = commands.iter().find(|command| command.name == args[0]).unwrap();
+            let context: CommandContext = CommandContext {
+                args,
+                channel: "channel",
+                user_id: "1",
+                sqlite_connection: &sqlite_connection,
+                spotify: &spotify,
+                raid_file_path: "",
+                registry: &registry,
+                jobs: &jobs,
+                seeds: &FixedSeed,
+            };
+            assert!(command.execute(&context).await.unwrap().ends_with("rolled"));
+        }
+
+        assert_eq!(saved_rolls(&sqlite_connection), vec![("!rollscore".to_owned(), 1234), ("!simulatescore".to_owned(), 1234), ("!fishscore".to_owned(), 1234)]);
+    }
+}
@@ -0,0 +1,73 @@
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoroshiro128PlusPlus;
+use serde_json::Value;
+
+// Seeds are stored in SQLite INTEGER columns, so they stay within an i64.
+pub const MAX_SEED: u64 = i64::MAX as u64;
+
+// Where rolls get their seed from when the chatter doesn't pick one.
+pub trait SeedSource: Send + Sync {
+    fn next_seed(&self) -> u64;
+}
+
+pub struct EntropySeeds;
+
+impl SeedSource for EntropySeeds {
+    fn next_seed(&self) -> u64 {
+        rand::thread_rng().gen_range(0..=MAX_SEED)
+    }
+}
+
+// The random numbers for one roll and the seed they come from. Xoroshiro128++ always turns a seed into
+// the same numbers, unlike StdRng which may change between rand versions, so a stored seed keeps
+// replaying the same roll.
+pub struct Roll {
+    pub seed: u64,
+    // The chatter picked the seed to replay a roll. Replays don't count for the leaderboards.
+    pub replay: bool,
+    pub rng: Xoroshiro128PlusPlus,
+    pub record: Option<RollRecord>,
+}
+
+// What a roll came out as. The dispatcher saves it to the rolls table once the command has replied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollRecord {
+    // the number the roll is ranked by, like a trident's durability
+    pub score: Option<f64>,
+    pub result: Value,
+}
+
+impl Roll {
+    pub fn new(seed: u64, replay: bool) -> Self {
+        Roll { seed, replay, rng: Xoroshiro128PlusPlus::seed_from_u64(seed), record: None }
+    }
+
+    pub fn record(&mut self, score: Option<f64>, result: Value) {
+        self.record = Some(RollRecord { score, result });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_seed_replays_the_same_numbers() {
+        let first: Vec<u32> = (0..10).map(|_| Roll::new(1234, false).rng.gen_range(0..=250)).collect();
+        let mut roll: Roll = Roll::new(1234, true);
+        let mut replay: Roll = Roll::new(1234, true);
+
+        assert!(first.iter().all(|n| *n == first[0]));
+        assert_eq!(
+            (0..100).map(|_| roll.rng.gen::<u64>()).collect::<Vec<u64>>(),
+            (0..100).map(|_| replay.rng.gen::<u64>()).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn entropy_seeds_fit_in_the_database() {
+        for _ in 0..1000 {
+            assert!(EntropySeeds.next_seed() <= MAX_SEED);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::leaderboards;
+
+const STREAMS_URL: &str = "https://api.twitch.tv/helix/streams";
+// Twitch caches the streams endpoint for about a minute anyway.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct Streams {
+    data: Vec<Stream>,
+}
+
+#[derive(Deserialize)]
+struct Stream {
+    user_login: String,
+    // like "2021-03-10T15:04:21Z"
+    started_at: String,
+}
+
+// Chat doesn't say when a stream goes live, so the Helix API is asked every minute which channels
+// are live and since when. Channels that went offline lose their stream start.
+pub fn watch(client_id: String, oauth_token: String, channels: Vec<String>) {
+    tokio::spawn(async move {
+        let client: Client = Client::new();
+
+        loop {
+            match live_streams(&client, &client_id, &oauth_token, &channels).await {
+                Ok(streams) => for channel in &channels {
+                    let started_at: Option<i64> = streams.iter()
+                        .find(|stream| stream.user_login.eq_ignore_ascii_case(channel))
+                        .and_then(|stream| parse_time(&stream.started_at));
+                    leaderboards::set_stream_start(channel, started_at);
+                },
+                Err(err) => println!("Couldn't look up which streams are live: {}", err),
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn live_streams(client: &Client, client_id: &str, oauth_token: &str, channels: &[String]) -> Result<Vec<Stream>, String> {
+    let logins: Vec<(&str, &str)> = channels.iter().map(|channel| ("user_login", channel.as_str())).collect();
+    let response = client
+        .get(STREAMS_URL)
+        .query(&logins)
+        .bearer_auth(oauth_token)
+        .header("Client-Id", client_id)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("Twitch answered {}", response.status()));
+    }
+
+    let streams: Streams = response.json().await.map_err(|err| err.to_string())?;
+    Ok(streams.data)
+}
+
+// Reads a UTC time like "2021-03-10T15:04:21Z" as unix time in ms.
+fn parse_time(time: &str) -> Option<i64> {
+    let (date, time) = time.strip_suffix('Z')?.split_once('T')?;
+    let date: Vec<i64> = date.split('-').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let time: Vec<i64> = time.split(':').map(|part| part.split('.').next()?.parse().ok()).collect::<Option<_>>()?;
+    let (&[year, month, day], &[hours, minutes, seconds]) = (date.as_slice(), time.as_slice()) else {
+        return None;
+    };
+
+    // a civil date to days, from Howard Hinnant's date algorithms
+    let year: i64 = if month <= 2 { year - 1 } else { year };
+    let era: i64 = year.div_euclid(400);
+    let year_of_era: i64 = year - era * 400;
+    let day_of_year: i64 = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era: i64 = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days: i64 = era * 146_097 + day_of_era - 719_468;
+
+    Some(((days * 24 + hours) * 60 + minutes) * 60_000 + seconds * 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_helix_times() {
+        assert_eq!(parse_time("2023-11-14T22:13:20Z"), Some(1_700_000_000_000));
+        assert_eq!(parse_time("2000-02-29T00:00:00Z"), Some(951_782_400_000));
+        assert_eq!(parse_time("2000-02-29T00:00:00.5Z"), Some(951_782_400_000));
+        assert_eq!(parse_time("2000-02-29 00:00:00"), None);
+        assert_eq!(parse_time("2000-02T00:00:00Z"), None);
+    }
+}
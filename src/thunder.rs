@@ -1,14 +1,10 @@
-use rand::{Rng, SeedableRng, rngs::StdRng}; 
+use rand::Rng;
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
 
-const ONE_MINUTE_IN_TICKS: u64 = 1200;
+use crate::weather::WeatherModel;
 
-const MIN_TIME_BETWEEN_CYCLES: u64 = ONE_MINUTE_IN_TICKS * 10;
-const MAX_TIME_BETWEEN_CYCLES: u64 = ONE_MINUTE_IN_TICKS * 150;
-
-const MIN_RAIN_CYCLE_DURATION: u64 = ONE_MINUTE_IN_TICKS * 10;
-const MAX_RAIN_CYCLE_DURATION: u64 = ONE_MINUTE_IN_TICKS * 20;
-const MIN_THUNDER_CYCLE_DURATION: u64 = ONE_MINUTE_IN_TICKS * 3;
-const MAX_THUNDER_CYCLE_DURATION: u64 = ONE_MINUTE_IN_TICKS * 13;
+pub const ONE_MINUTE_IN_TICKS: u64 = 1200;
 
 pub fn get_thunder_duration(
     rain_cycle_start: u64,
@@ -37,14 +33,10 @@ pub fn get_thunder_duration(
         return thunder_cycle_duration;
     } else if thunder_cycle_start < rain_cycle_start && rain_cycle_end < thunder_cycle_end {
         return rain_cycle_duration;
-    } else if thunder_cycle_start > rain_cycle_start && thunder_cycle_start < rain_cycle_end {
+    } else if thunder_cycle_start < rain_cycle_end && (thunder_cycle_start > rain_cycle_start || rain_cycle_end < thunder_cycle_end) {
         return rain_cycle_end - thunder_cycle_start;
-    } else if rain_cycle_start > thunder_cycle_start && rain_cycle_start < thunder_cycle_end {
-        return thunder_cycle_end - rain_cycle_start;
-    } else if thunder_cycle_end > rain_cycle_start && thunder_cycle_end < rain_cycle_end {
+    } else if rain_cycle_start < thunder_cycle_end && (rain_cycle_start > thunder_cycle_start || thunder_cycle_end < rain_cycle_end) {
         return thunder_cycle_end - rain_cycle_start;
-    } else if rain_cycle_end > thunder_cycle_start && rain_cycle_end < thunder_cycle_end {
-        return rain_cycle_end - thunder_cycle_start;
     }
 
     // println!("{}, {}, {}, {}, {}, {}", 
@@ -70,82 +62,357 @@ pub fn get_thunder_start_time(
     }
 }
 
-pub fn get_thunder_odds(time: u64) -> f64 {
-    let mut rng: StdRng = SeedableRng::from_entropy();
+// Odds that the first thunder of a world starts before `time` (in ticks).
+pub fn get_thunder_odds(model: WeatherModel, time: u64) -> f64 {
+    thunder_odds_table(model).odds_before(time)
+}
 
-    let loops: u64 = 1_000_000;
-    let mut succes: u64 = 0;
+// Ticks per step of the thunder odds table. Finer steps only change the odds past the 4th decimal.
+pub const TABLE_STEP: u64 = 600;
+// The table stops once the odds of no thunder yet drop below this.
+const TABLE_PRECISION: f64 = 1e-13;
 
-    for _ in 1..=loops {
-        let mut rain_cycle_start: u64 = rng.gen_range((MIN_TIME_BETWEEN_CYCLES + 1)..MAX_TIME_BETWEEN_CYCLES);
-        let mut rain_cycle_duration: u64 = rng.gen_range(MIN_RAIN_CYCLE_DURATION..MAX_RAIN_CYCLE_DURATION);
-        let mut thunder_cycle_start: u64 = rng.gen_range((MIN_TIME_BETWEEN_CYCLES + 1)..MAX_TIME_BETWEEN_CYCLES);
-        let mut thunder_cycle_duration: u64 = rng.gen_range(MIN_THUNDER_CYCLE_DURATION..MAX_THUNDER_CYCLE_DURATION);
-        let mut start_time: u64;
+static THUNDER_ODDS_TABLES: [OnceLock<ThunderOddsTable>; WeatherModel::ALL.len()] =
+    [const { OnceLock::new() }; WeatherModel::ALL.len()];
 
-        'thunder_loop: loop {
-            let duration: u64 = get_thunder_duration(
-                rain_cycle_start,
-                rain_cycle_duration,
-                thunder_cycle_start,
-                thunder_cycle_duration
-            );
+// Builds the tables ahead of time so the first !thunderodds doesn't have to wait for them.
+pub fn init_thunder_odds_tables() {
+    for model in WeatherModel::ALL {
+        thunder_odds_table(model);
+    }
+}
 
-            start_time = get_thunder_start_time(rain_cycle_start, thunder_cycle_start);
-            if duration > 0 && start_time < time {
-                succes += 1;
-                break 'thunder_loop;
-            } else if thunder_cycle_start >= time || rain_cycle_start >= time {
-                break 'thunder_loop;
-            }
+fn thunder_odds_table(model: WeatherModel) -> &'static ThunderOddsTable {
+    THUNDER_ODDS_TABLES[model.index()].get_or_init(|| ThunderOddsTable::new(model, TABLE_STEP))
+}
 
-            if rain_cycle_start <= thunder_cycle_start {
-                let rain_cycle_end: u64 = rain_cycle_start + rain_cycle_duration;
-                
-                rain_cycle_start = rain_cycle_end + rng.gen_range((MIN_TIME_BETWEEN_CYCLES + 1)..MAX_TIME_BETWEEN_CYCLES);
-                rain_cycle_duration = rng.gen_range(MIN_RAIN_CYCLE_DURATION..MAX_RAIN_CYCLE_DURATION);
-            } else {
-                let thunder_cycle_end: u64 = thunder_cycle_start + thunder_cycle_duration;
-                
-                thunder_cycle_start = thunder_cycle_end + rng.gen_range((MIN_TIME_BETWEEN_CYCLES + 1)..MAX_TIME_BETWEEN_CYCLES);
-                thunder_cycle_duration = rng.gen_range(MIN_THUNDER_CYCLE_DURATION..MAX_THUNDER_CYCLE_DURATION);
-            }
+// Odds of the first thunder starting in each step of `step` ticks, worked out from the model's cycle
+// ranges instead of simulated.
+pub struct ThunderOddsTable {
+    step: u64,
+    // cumulative[i]: odds that the first thunder started in step i or earlier
+    cumulative: Vec<f64>,
+}
+
+impl ThunderOddsTable {
+    pub fn new(model: WeatherModel, step: u64) -> Self {
+        let cumulative: Vec<f64> = ThunderChain::new(model, step)
+            .scan(0.0, |thundered: &mut f64, start: ThunderStart| {
+                *thundered += start.odds;
+                Some(thundered.min(1.0))
+            })
+            .collect();
+
+        ThunderOddsTable { step, cumulative }
+    }
+
+    // A step holds the thunder that starts within half a step of it, and the odds grow evenly across it.
+    pub fn odds_before(&self, time: u64) -> f64 {
+        let position: f64 = time as f64 / self.step as f64 + 0.5;
+        let i: usize = position.floor() as usize;
+
+        if i >= self.cumulative.len() {
+            return *self.cumulative.last().unwrap_or(&1.0);
         }
 
-        // while  == 0 {
-            // if rain_cycle_start <= thunder_cycle_start {
-            //     let rain_cycle_end: u64 = rain_cycle_start + rain_cycle_duration;
-                
-            //     rain_cycle_start = rng.gen_range((rain_cycle_end + MIN_TIME_BETWEEN_CYCLES + 1)..=(rain_cycle_end + MAX_TIME_BETWEEN_CYCLES));
-            //     rain_cycle_duration = rng.gen_range(MIN_RAIN_CYCLE_DURATION..=MAX_RAIN_CYCLE_DURATION);
-            // } else {
-            //     let thunder_cycle_end: u64 = thunder_cycle_start + thunder_cycle_duration;
-                
-            //     thunder_cycle_start = rng.gen_range((thunder_cycle_end + MIN_TIME_BETWEEN_CYCLES + 1)..=(thunder_cycle_end + MAX_TIME_BETWEEN_CYCLES));
-            //     thunder_cycle_duration = rng.gen_range(MIN_THUNDER_CYCLE_DURATION..=MAX_THUNDER_CYCLE_DURATION);
-            // }
+        let before: f64 = if i == 0 { 0.0 } else { self.cumulative[i - 1] };
+        before + (self.cumulative[i] - before) * position.fract()
+    }
+}
+
+// Enough for the odds to reach TABLE_PRECISION with any step size down to a second.
+const MAX_TABLE_STEPS: usize = 30_000_000 / 20;
+
+// How the worlds that got their first thunder in one step got it.
+pub struct ThunderStart {
+    // odds of the first thunder starting this step
+    pub odds: f64,
+    // [i]: thunder started while it rained, and the rain stops i steps later
+    pub during_rain: Vec<f64>,
+    // [i]: rain started while it thundered, and the thunder stops i steps later
+    pub during_thunder: Vec<f64>,
+    // both started this step
+    pub together: f64,
+}
+
+// Every world's weather at once, one step of `step` ticks at a time, up to its first thunder.
+//
+// Rain and thunder are two independent cycles that switch between off and on. The chain follows the
+// probability of every combination of (rain phase, step rain switches next, thunder phase, step thunder
+// switches next) that hasn't had thunder yet. Both being on at once is thunder, so that probability is
+// taken out and returned for the step it happened in. Stops once less than TABLE_PRECISION is left.
+pub struct ThunderChain {
+    t: usize,
+    untouched: f64,
+    gap_changes: Vec<(usize, f64)>,
+    rain_changes: Vec<(usize, f64)>,
+    thunder_changes: Vec<(usize, f64)>,
+    both_off: JointPhase,
+    raining: JointPhase,
+    thundering: JointPhase,
+}
+
+impl ThunderChain {
+    // A new world, with both cycles off.
+    pub fn new(model: WeatherModel, step: u64) -> Self {
+        ThunderChain::starting_at(model, step, 0).0
+    }
+
+    // Worlds that have already been running for `start` steps, whatever their weather was before.
+    // Also returns the odds that it's thundering right at that step.
+    pub fn starting_at(model: WeatherModel, step: u64, start: usize) -> (Self, f64) {
+        let gap: Vec<f64> = step_distribution(model.time_between_cycles(), step);
+        let rain: Vec<f64> = step_distribution(model.rain_duration(), step);
+        let thunder: Vec<f64> = step_distribution(model.thunder_duration(), step);
+        let window: usize = gap.len().max(rain.len()).max(thunder.len()) + 1;
+
+        let mut chain: ThunderChain = ThunderChain {
+            t: start,
+            untouched: 1.0,
+            gap_changes: changes(&gap),
+            rain_changes: changes(&rain),
+            thunder_changes: changes(&thunder),
+            both_off: JointPhase::new(window),
+            raining: JointPhase::new(window),
+            thundering: JointPhase::new(window),
+        };
+
+        // Until `start` the two cycles don't affect each other, so each one is followed on its own
+        // and the joint odds are the products of theirs.
+        let (rain_off, rain_on): (Vec<f64>, Vec<f64>) = cycle_at(start, &gap, &rain, window);
+        let (thunder_off, thunder_on): (Vec<f64>, Vec<f64>) = cycle_at(start, &gap, &thunder, window);
+
+        chain.both_off.by_rain.spread(start, &changes(&rain_off), &thunder_off);
+        chain.raining.by_rain.spread(start, &changes(&rain_on), &thunder_off);
+        chain.thundering.by_rain.spread(start, &changes(&rain_off), &thunder_on);
+
+        let thundering_now: f64 = rain_on.iter().sum::<f64>() * thunder_on.iter().sum::<f64>();
+        chain.untouched -= thundering_now;
+
+        (chain, thundering_now)
+    }
+}
+
+impl Iterator for ThunderChain {
+    type Item = ThunderStart;
+
+    fn next(&mut self) -> Option<ThunderStart> {
+        if self.untouched <= TABLE_PRECISION || self.t >= MAX_TABLE_STEPS {
+            return None;
+        }
+
+        let t: usize = self.t;
+        self.both_off.advance(t);
+        self.raining.advance(t);
+        self.thundering.advance(t);
+
+        // Cycles that end this step switch off first. When one cycle ends in the same step the other
+        // one starts, either could have come first, so half of those worlds count as thunder.
+        let mut rain_ending: Vec<f64> = self.raining.rain_ending(t);
+        let mut thunder_ending: Vec<f64> = self.thundering.thunder_ending(t);
+        rain_ending[0] /= 2.0;
+        thunder_ending[0] /= 2.0;
+        self.both_off.by_rain.spread(t, &self.gap_changes, &rain_ending);
+        self.both_off.by_thunder.spread(t, &self.gap_changes, &thunder_ending);
+
+        // then the ones that start this step switch on
+        let mut rain_starting: Vec<f64> = self.both_off.rain_ending(t);
+        let mut thunder_starting: Vec<f64> = self.both_off.thunder_ending(t);
+        let together: f64 = rain_starting[0];
+        rain_starting[0] = 0.0;
+        thunder_starting[0] = 0.0;
+        self.raining.by_rain.spread(t, &self.rain_changes, &rain_starting);
+        self.thundering.by_thunder.spread(t, &self.thunder_changes, &thunder_starting);
+
+        // thunder starting while it rains, or rain starting while it thunders
+        let mut during_rain: Vec<f64> = self.raining.thunder_ending(t);
+        let mut during_thunder: Vec<f64> = self.thundering.rain_ending(t);
+        during_rain[0] = rain_ending[0];
+        during_thunder[0] = thunder_ending[0];
+
+        let odds: f64 = together + during_rain.iter().sum::<f64>() + during_thunder.iter().sum::<f64>();
+        self.untouched -= odds;
+
+        self.both_off.clear(t);
+        self.raining.clear(t);
+        self.thundering.clear(t);
+        self.t += 1;
+
+        Some(ThunderStart { odds, during_rain, during_thunder, together })
+    }
+}
+
+// Odds of one cycle being off or on at step `start`, by how many steps later it switches next.
+fn cycle_at(start: usize, gap: &[f64], duration: &[f64], window: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut off: Vec<f64> = vec![0.0; start + window];
+    let mut on: Vec<f64> = vec![0.0; start + window];
+    off[..gap.len()].copy_from_slice(gap);
+
+    for t in 0..start {
+        let (switching_on, switching_off): (f64, f64) = (off[t], on[t]);
+
+        for (i, odds) in duration.iter().enumerate() {
+            on[t + i] += switching_on * odds;
+        }
+        for (i, odds) in gap.iter().enumerate() {
+            off[t + i] += switching_off * odds;
+        }
+    }
+
+    // the last slot of the window has to stay free for the end of the spreads
+    let (mut off, mut on): (Vec<f64>, Vec<f64>) = (off.split_off(start), on.split_off(start));
+    off.truncate(window - 1);
+    on.truncate(window - 1);
+
+    (off, on)
+}
+
+// Odds of a duration drawn uniformly from `range` ticks rounding to each number of steps.
+fn step_distribution(range: RangeInclusive<u64>, step: u64) -> Vec<f64> {
+    let (min, max): (u64, u64) = range.into_inner();
+    let total: f64 = (max - min + 1) as f64;
+    let last: u64 = (max + step / 2) / step;
+
+    (0..=last)
+        .map(|i| {
+            let low: u64 = (i * step).saturating_sub(step / 2).max(min);
+            let high: u64 = (i * step + (step - 1) / 2).min(max);
+
+            if high < low { 0.0 } else { (high - low + 1) as f64 / total }
+        })
+        .collect()
+}
+
+// The (offset, change) pairs where a distribution differs from the value before it.
+fn changes(distribution: &[f64]) -> Vec<(usize, f64)> {
+    (0..=distribution.len())
+        .filter_map(|i| {
+            let value: f64 = distribution.get(i).copied().unwrap_or(0.0);
+            let previous: f64 = if i == 0 { 0.0 } else { distribution[i - 1] };
+
+            if value != previous { Some((i, value - previous)) } else { None }
+        })
+        .collect()
+}
 
-            // start_time = get_thunder_start_time(rain_cycle_start, thunder_cycle_start);
-            // if start_time > time {
-            //     continue;
-            // }
-        // }
+// Probability of each (step rain switches next, step thunder switches next) pair for one combination
+// of rain and thunder phases. Mass only ever arrives by one cycle switching, which spreads it over a
+// range of steps for that cycle, so it's stored as the differences between neighbouring steps.
+struct JointPhase {
+    by_rain: Spreads,
+    by_thunder: Spreads,
+}
 
-        // start_time = get_thunder_start_time(rain_cycle_start, thunder_cycle_start);
-        // if start_time <= time {
-        //     succes += 1;
-        // }
+impl JointPhase {
+    fn new(window: usize) -> Self {
+        JointPhase { by_rain: Spreads::new(window), by_thunder: Spreads::new(window) }
     }
-    
-    succes as f64 / loops as f64
+
+    fn advance(&mut self, t: usize) {
+        self.by_rain.advance(t);
+        self.by_thunder.advance(t);
+    }
+
+    // [i]: probability that rain switches at step t and thunder at step t + i
+    fn rain_ending(&self, t: usize) -> Vec<f64> {
+        let mut line: Vec<f64> = self.by_thunder.ahead(t, t);
+        for (i, value) in line.iter_mut().enumerate() {
+            *value += self.by_rain.now(t + i);
+        }
+
+        line
+    }
+
+    // [i]: probability that thunder switches at step t and rain at step t + i
+    fn thunder_ending(&self, t: usize) -> Vec<f64> {
+        let mut line: Vec<f64> = self.by_rain.ahead(t, t);
+        for (i, value) in line.iter_mut().enumerate() {
+            *value += self.by_thunder.now(t + i);
+        }
+
+        line
+    }
+
+    fn clear(&mut self, t: usize) {
+        self.by_rain.clear(t);
+        self.by_thunder.clear(t);
+    }
+}
+
+// Mass spread along one cycle's switch step (the "main" step) for each step of the other cycle.
+// Steps are absolute, stored in a ring buffer of `window` steps.
+struct Spreads {
+    window: usize,
+    // differences[main][other]
+    differences: Vec<f64>,
+    // running sum of the differences up to the current step, for each other step
+    current: Vec<f64>,
 }
 
-pub fn get_first_thunder() -> (u64, u64) {
-        let mut rng: StdRng = SeedableRng::from_entropy();
-        let mut rain_cycle_start: u64 = rng.gen_range((MIN_TIME_BETWEEN_CYCLES + 1)..MAX_TIME_BETWEEN_CYCLES);
-        let mut rain_cycle_duration: u64 = rng.gen_range(MIN_RAIN_CYCLE_DURATION..MAX_RAIN_CYCLE_DURATION);
-        let mut thunder_cycle_start: u64 = rng.gen_range((MIN_TIME_BETWEEN_CYCLES + 1)..MAX_TIME_BETWEEN_CYCLES);
-        let mut thunder_cycle_duration: u64 = rng.gen_range(MIN_THUNDER_CYCLE_DURATION..MAX_THUNDER_CYCLE_DURATION);
+impl Spreads {
+    fn new(window: usize) -> Self {
+        Spreads { window, differences: vec![0.0; window * window], current: vec![0.0; window] }
+    }
+
+    fn index(&self, main: usize, other: usize) -> usize {
+        (main % self.window) * self.window + other % self.window
+    }
+
+    fn advance(&mut self, t: usize) {
+        for other in 0..self.window {
+            self.current[other] += self.differences[(t % self.window) * self.window + other];
+        }
+    }
+
+    // mass with the main cycle switching now and the other at `other`
+    fn now(&self, other: usize) -> f64 {
+        self.current[other % self.window]
+    }
+
+    // [i]: mass with the main cycle switching at step t + i and the other at `other`
+    fn ahead(&self, t: usize, other: usize) -> Vec<f64> {
+        let mut line: Vec<f64> = Vec::with_capacity(self.window);
+        let mut value: f64 = self.now(other);
+        line.push(value);
+
+        for i in 1..self.window {
+            value += self.differences[self.index(t + i, other)];
+            line.push(value);
+        }
+
+        line
+    }
+
+    // Spreads source[i] (mass with the other cycle switching at t + i) over the main cycle's next
+    // switch, t + a duration with the given changes.
+    fn spread(&mut self, t: usize, changes: &[(usize, f64)], source: &[f64]) {
+        for (offset, change) in changes {
+            for (i, mass) in source.iter().enumerate() {
+                if *mass != 0.0 {
+                    let index: usize = self.index(t + offset, t + i);
+                    self.differences[index] += mass * change;
+                }
+            }
+        }
+    }
+
+    // Frees step t's row and column of the ring buffer for step t + window.
+    fn clear(&mut self, t: usize) {
+        let slot: usize = t % self.window;
+
+        for other in 0..self.window {
+            self.differences[slot * self.window + other] = 0.0;
+            self.differences[other * self.window + slot] = 0.0;
+        }
+        self.current[slot] = 0.0;
+    }
+}
+
+pub fn get_first_thunder<R: Rng>(model: WeatherModel, rng: &mut R) -> (u64, u64) {
+        let mut rain_cycle_start: u64 = rng.gen_range(model.time_between_cycles());
+        let mut rain_cycle_duration: u64 = rng.gen_range(model.rain_duration());
+        let mut thunder_cycle_start: u64 = rng.gen_range(model.time_between_cycles());
+        let mut thunder_cycle_duration: u64 = rng.gen_range(model.thunder_duration());
 
         while get_thunder_duration(
             rain_cycle_start,
@@ -156,13 +423,13 @@ pub fn get_first_thunder() -> (u64, u64) {
             if rain_cycle_start <= thunder_cycle_start {
                 let rain_cycle_end: u64 = rain_cycle_start + rain_cycle_duration;
                 
-                rain_cycle_start = rng.gen_range((rain_cycle_end + MIN_TIME_BETWEEN_CYCLES)..(rain_cycle_end + MAX_TIME_BETWEEN_CYCLES - 1));
-                rain_cycle_duration = rng.gen_range(MIN_RAIN_CYCLE_DURATION..MAX_RAIN_CYCLE_DURATION);
+                rain_cycle_start = rain_cycle_end + rng.gen_range(model.time_between_cycles());
+                rain_cycle_duration = rng.gen_range(model.rain_duration());
             } else {
                 let thunder_cycle_end: u64 = thunder_cycle_start + thunder_cycle_duration;
                 
-                thunder_cycle_start = rng.gen_range((thunder_cycle_end + MIN_TIME_BETWEEN_CYCLES)..(thunder_cycle_end + MAX_TIME_BETWEEN_CYCLES - 1));
-                thunder_cycle_duration = rng.gen_range(MIN_THUNDER_CYCLE_DURATION..MAX_THUNDER_CYCLE_DURATION);
+                thunder_cycle_start = thunder_cycle_end + rng.gen_range(model.time_between_cycles());
+                thunder_cycle_duration = rng.gen_range(model.thunder_duration());
             }
         }
 
@@ -203,19 +470,114 @@ pub fn format_duration(time: u64) -> String {
 
     let minutes_string: String = match minutes {
         0 => { "".to_owned() }
-        1 => { format!("1 minute") }
+        1 => { "1 minute".to_owned() }
         _ => { format!("{} minutes", minutes) }
     };
     let seconds_string: String = match seconds {
         0 => { "".to_owned() }
-        1 => { format!("1 seconds") }
+        1 => { "1 seconds".to_owned() }
         _ => { format!("{} seconds", seconds) }
     };
     let ticks_string: String = match ticks {
         0 => { "".to_owned() }
-        1 => { format!("1 tick") }
+        1 => { "1 tick".to_owned() }
         _ => { format!("{} ticks", ticks) }
     };
 
     format!("{} {} {}", minutes_string, seconds_string, ticks_string)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    // The Monte Carlo estimate !thunderodds used before the exact table.
+    fn simulate_thunder_odds(model: WeatherModel, time: u64, worlds: u64, rng: &mut StdRng) -> f64 {
+        let mut succes: u64 = 0;
+
+        for _ in 1..=worlds {
+            let mut rain_cycle_start: u64 = rng.gen_range(model.time_between_cycles());
+            let mut rain_cycle_duration: u64 = rng.gen_range(model.rain_duration());
+            let mut thunder_cycle_start: u64 = rng.gen_range(model.time_between_cycles());
+            let mut thunder_cycle_duration: u64 = rng.gen_range(model.thunder_duration());
+
+            loop {
+                let duration: u64 = get_thunder_duration(
+                    rain_cycle_start,
+                    rain_cycle_duration,
+                    thunder_cycle_start,
+                    thunder_cycle_duration
+                );
+
+                let start_time: u64 = get_thunder_start_time(rain_cycle_start, thunder_cycle_start);
+                if duration > 0 && start_time < time {
+                    succes += 1;
+                    break;
+                } else if thunder_cycle_start >= time || rain_cycle_start >= time {
+                    break;
+                }
+
+                if rain_cycle_start <= thunder_cycle_start {
+                    let rain_cycle_end: u64 = rain_cycle_start + rain_cycle_duration;
+
+                    rain_cycle_start = rain_cycle_end + rng.gen_range(model.time_between_cycles());
+                    rain_cycle_duration = rng.gen_range(model.rain_duration());
+                } else {
+                    let thunder_cycle_end: u64 = thunder_cycle_start + thunder_cycle_duration;
+
+                    thunder_cycle_start = thunder_cycle_end + rng.gen_range(model.time_between_cycles());
+                    thunder_cycle_duration = rng.gen_range(model.thunder_duration());
+                }
+            }
+        }
+
+        succes as f64 / worlds as f64
+    }
+
+    #[test]
+    fn table_matches_the_simulator() {
+        let model: WeatherModel = WeatherModel::default();
+        let mut rng: StdRng = StdRng::seed_from_u64(1234);
+        let worlds: u64 = 100_000;
+
+        for minutes in [0, 15, 30, 60, 120, 240, 480, 960] {
+            let time: u64 = minutes * ONE_MINUTE_IN_TICKS;
+            let simulated: f64 = simulate_thunder_odds(model, time, worlds, &mut rng);
+            let exact: f64 = get_thunder_odds(model, time);
+
+            // 5 standard errors of the simulation
+            let tolerance: f64 = 5.0 * (simulated * (1.0 - simulated) / worlds as f64).sqrt() + 1e-4;
+            assert!((exact - simulated).abs() <= tolerance, "{} minutes: table {} vs simulated {}", minutes, exact, simulated);
+        }
+    }
+
+    #[test]
+    fn odds_grow_from_zero_to_one() {
+        let model: WeatherModel = WeatherModel::default();
+        assert_eq!(get_thunder_odds(model, 0), 0.0);
+        // the first rain can't start before 10 minutes
+        assert!(get_thunder_odds(model, 10 * ONE_MINUTE_IN_TICKS) < 1e-5);
+
+        let mut previous: f64 = 0.0;
+        for minutes in 0..=10_000 {
+            let odds: f64 = get_thunder_odds(model, minutes * ONE_MINUTE_IN_TICKS);
+            assert!(odds >= previous, "odds drop at {} minutes", minutes);
+            previous = odds;
+        }
+
+        assert!(previous > 1.0 - 1e-9);
+        assert!(previous <= 1.0);
+    }
+
+    #[test]
+    fn table_converges_with_smaller_steps() {
+        let model: WeatherModel = WeatherModel::default();
+        let coarse: ThunderOddsTable = ThunderOddsTable::new(model, TABLE_STEP * 2);
+
+        for minutes in [20, 60, 240, 1_000] {
+            let time: u64 = minutes * ONE_MINUTE_IN_TICKS;
+            assert!((coarse.odds_before(time) - get_thunder_odds(model, time)).abs() < 1e-3, "{} minutes", minutes);
+        }
+    }
+}
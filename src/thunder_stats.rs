@@ -0,0 +1,279 @@
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
+use crate::thunder::{self, ThunderChain, ThunderStart, TABLE_STEP};
+use crate::weather::WeatherModel;
+
+// Latest time the percentile search looks at. The odds of no thunder by then are far below what
+// fits in an f64 next to 1.
+const LATEST_THUNDER: u64 = 30_000_000;
+
+static FIRST_THUNDER_DURATIONS: [OnceLock<FirstThunderDurations>; WeatherModel::ALL.len()] =
+    [const { OnceLock::new() }; WeatherModel::ALL.len()];
+
+// Builds the durations ahead of time so the first !thunderduration doesn't have to wait for them.
+pub fn init_first_thunder_durations() {
+    for model in WeatherModel::ALL {
+        first_thunder_durations(model);
+    }
+}
+
+fn first_thunder_durations(model: WeatherModel) -> &'static FirstThunderDurations {
+    FIRST_THUNDER_DURATIONS[model.index()].get_or_init(|| FirstThunderDurations::new(model, TABLE_STEP))
+}
+
+// Earliest time (in ticks) by which `percent`% of worlds have had their first thunder.
+pub fn first_thunder_percentile(model: WeatherModel, percent: f64) -> u64 {
+    let odds: f64 = percent / 100.0;
+    let (mut low, mut high): (u64, u64) = (0, LATEST_THUNDER);
+
+    while low < high {
+        let middle: u64 = low + (high - low) / 2;
+
+        if thunder::get_thunder_odds(model, middle) >= odds {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    low
+}
+
+// Average length of a world's first thunder, in ticks.
+pub fn expected_first_thunder_duration(model: WeatherModel) -> f64 {
+    first_thunder_durations(model).expected
+}
+
+// Odds that a world's first thunder lasts at least `time` ticks.
+pub fn first_thunder_lasts_at_least(model: WeatherModel, time: u64) -> f64 {
+    first_thunder_durations(model).odds_at_least(time)
+}
+
+// Odds of any thunder (not just the first one) between `from` and `to` ticks: either it's already
+// thundering at `from`, or it starts before `to`.
+pub fn thunder_odds_between(model: WeatherModel, from: u64, to: u64) -> f64 {
+    let first: usize = (from as f64 / TABLE_STEP as f64 + 0.5).floor() as usize;
+    let position: f64 = to as f64 / TABLE_STEP as f64 + 0.5;
+    let last: usize = (position.floor() as usize).max(first);
+
+    let (chain, thundering): (ThunderChain, f64) = ThunderChain::starting_at(model, TABLE_STEP, first);
+    let mut odds: f64 = thundering;
+
+    // like ThunderOddsTable::odds_before, the last step only counts up to `to`
+    for (i, start) in (first..=last).zip(chain) {
+        odds += if i == last { start.odds * position.fract() } else { start.odds };
+    }
+
+    odds.min(1.0)
+}
+
+// How long the first thunder lasts. It lasts until either cycle switches off: whichever cycle was
+// already on when the other one started has a known number of steps left, the one that just started
+// runs for its full random duration.
+pub struct FirstThunderDurations {
+    model: WeatherModel,
+    step: u64,
+    // [i]: odds that the first thunder started while it rained, with i steps of rain left
+    during_rain: Vec<f64>,
+    // [i]: odds that the first thunder started while it thundered, with i steps of thunder left
+    during_thunder: Vec<f64>,
+    // odds that both cycles started in the same step
+    together: f64,
+    // in ticks
+    expected: f64,
+}
+
+impl FirstThunderDurations {
+    pub fn new(model: WeatherModel, step: u64) -> Self {
+        let mut durations: FirstThunderDurations = FirstThunderDurations {
+            model,
+            step,
+            during_rain: Vec::new(),
+            during_thunder: Vec::new(),
+            together: 0.0,
+            expected: 0.0,
+        };
+
+        for start in ThunderChain::new(model, step) {
+            let ThunderStart { during_rain, during_thunder, together, .. } = start;
+
+            add_into(&mut durations.during_rain, &during_rain);
+            add_into(&mut durations.during_thunder, &during_thunder);
+            durations.together += together;
+        }
+
+        // Durations are whole ticks and never longer than a thunder cycle, so the average is the sum
+        // of the odds of lasting at least each tick.
+        durations.expected = (1..=*model.thunder_duration().end()).map(|time| durations.odds_at_least(time)).sum();
+        durations
+    }
+
+    pub fn odds_at_least(&self, time: u64) -> f64 {
+        let rain_lasts: f64 = lasts_at_least(self.model.rain_duration(), time);
+        let thunder_lasts: f64 = lasts_at_least(self.model.thunder_duration(), time);
+
+        let left_at_least = |odds: &[f64]| -> f64 {
+            odds.iter().enumerate().map(|(steps, odds)| odds * steps_left_at_least(steps, self.step, time)).sum()
+        };
+
+        let total: f64 = self.during_rain.iter().sum::<f64>() + self.during_thunder.iter().sum::<f64>() + self.together;
+        let lasting: f64 = left_at_least(&self.during_rain) * thunder_lasts
+            + left_at_least(&self.during_thunder) * rain_lasts
+            + self.together * rain_lasts * thunder_lasts;
+
+        lasting / total
+    }
+}
+
+fn add_into(sums: &mut Vec<f64>, values: &[f64]) {
+    if sums.len() < values.len() {
+        sums.resize(values.len(), 0.0);
+    }
+
+    for (sum, value) in sums.iter_mut().zip(values) {
+        *sum += value;
+    }
+}
+
+// Odds that `steps` steps left is at least `time` ticks. Both switches happen somewhere within half a
+// step of the step they're counted in, so the time left is anywhere from steps - 1 to steps + 1 steps,
+// most likely steps. With 0 steps left only the worlds where the other cycle switched on first count.
+fn steps_left_at_least(steps: usize, step: u64, time: u64) -> f64 {
+    let position: f64 = time as f64 / step as f64 - steps as f64 + 1.0;
+
+    if steps == 0 {
+        (2.0 - position).max(0.0).powi(2)
+    } else if position <= 0.0 {
+        1.0
+    } else if position <= 1.0 {
+        1.0 - position * position / 2.0
+    } else if position < 2.0 {
+        (2.0 - position).powi(2) / 2.0
+    } else {
+        0.0
+    }
+}
+
+// Odds that a cycle with a duration drawn from `range` ticks lasts at least `time` ticks.
+fn lasts_at_least(range: RangeInclusive<u64>, time: u64) -> f64 {
+    let (min, max): (u64, u64) = range.into_inner();
+
+    if time <= min {
+        1.0
+    } else if time > max {
+        0.0
+    } else {
+        (max - time + 1) as f64 / (max - min + 1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thunder::ONE_MINUTE_IN_TICKS;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    // Start and end ticks of one cycle's on phases until `until`.
+    fn simulate_cycle(rng: &mut StdRng, model: WeatherModel, duration: RangeInclusive<u64>, until: u64) -> Vec<(u64, u64)> {
+        let mut phases: Vec<(u64, u64)> = Vec::new();
+        let mut time: u64 = 0;
+
+        while time < until {
+            let start: u64 = time + rng.gen_range(model.time_between_cycles());
+            let end: u64 = start + rng.gen_range(duration.clone());
+            phases.push((start, end));
+            time = end;
+        }
+
+        phases
+    }
+
+    fn simulate_thunder_between(rng: &mut StdRng, model: WeatherModel, from: u64, to: u64) -> bool {
+        let rain: Vec<(u64, u64)> = simulate_cycle(rng, model, model.rain_duration(), to);
+        let thunder: Vec<(u64, u64)> = simulate_cycle(rng, model, model.thunder_duration(), to);
+
+        rain.iter().any(|(rain_start, rain_end)| {
+            thunder.iter().any(|(thunder_start, thunder_end)| {
+                let start: u64 = *rain_start.max(thunder_start);
+                let end: u64 = *rain_end.min(thunder_end);
+
+                start < end && start < to && end > from
+            })
+        })
+    }
+
+    #[test]
+    fn percentiles_invert_the_odds() {
+        let model: WeatherModel = WeatherModel::default();
+        let median: u64 = first_thunder_percentile(model, 50.0);
+        let ninetieth: u64 = first_thunder_percentile(model, 90.0);
+
+        // the first tick where the odds reach the percentile
+        assert!(thunder::get_thunder_odds(model, median - 1) < 0.5 && thunder::get_thunder_odds(model, median) >= 0.5);
+        assert!(thunder::get_thunder_odds(model, ninetieth - 1) < 0.9 && thunder::get_thunder_odds(model, ninetieth) >= 0.9);
+        assert!(median < ninetieth);
+        assert_eq!(first_thunder_percentile(model, 0.0), 0);
+    }
+
+    #[test]
+    fn durations_match_the_simulator() {
+        let model: WeatherModel = WeatherModel::default();
+        let samples: u64 = 20_000;
+        let mut rng: StdRng = StdRng::seed_from_u64(1234);
+        let durations: Vec<u64> = (0..samples).map(|_| thunder::get_first_thunder(model, &mut rng).1).collect();
+        let mean: f64 = durations.iter().sum::<u64>() as f64 / samples as f64;
+        let expected: f64 = expected_first_thunder_duration(model);
+
+        // within ~5 standard errors, the spread of durations is below 4 minutes
+        assert!((expected - mean).abs() < 5.0 * 4.0 * 1200.0 / (samples as f64).sqrt(), "expected {} vs simulated {}", expected, mean);
+
+        for minutes in [1, 3, 5, 8, 12] {
+            let time: u64 = minutes * ONE_MINUTE_IN_TICKS;
+            let simulated: f64 = durations.iter().filter(|duration| **duration >= time).count() as f64 / samples as f64;
+            let exact: f64 = first_thunder_lasts_at_least(model, time);
+            let tolerance: f64 = 5.0 * (simulated * (1.0 - simulated) / samples as f64).sqrt() + 2e-3;
+
+            assert!((exact - simulated).abs() <= tolerance, "{} minutes: {} vs simulated {}", minutes, exact, simulated);
+        }
+
+        assert_eq!(first_thunder_lasts_at_least(model, 0), 1.0);
+        assert!(first_thunder_lasts_at_least(model, *model.thunder_duration().end() + 1) < 1e-12);
+    }
+
+    #[test]
+    fn thunder_can_last_a_tick_longer_since_1_20() {
+        let longest: u64 = *WeatherModel::Java1_16.thunder_duration().end();
+
+        assert!(first_thunder_lasts_at_least(WeatherModel::Java1_16, longest + 1) < 1e-12);
+        assert!(first_thunder_lasts_at_least(WeatherModel::Java1_20, longest + 1) > 1e-12);
+        assert!(expected_first_thunder_duration(WeatherModel::Java1_20) > expected_first_thunder_duration(WeatherModel::Java1_16));
+        assert_ne!(thunder::get_thunder_odds(WeatherModel::Java1_20, 100_000), thunder::get_thunder_odds(WeatherModel::Java1_16, 100_000));
+    }
+
+    #[test]
+    fn windows_from_the_start_match_the_first_thunder_odds() {
+        let model: WeatherModel = WeatherModel::default();
+
+        for minutes in [0, 15, 60, 300] {
+            let time: u64 = minutes * ONE_MINUTE_IN_TICKS;
+            assert!((thunder_odds_between(model, 0, time) - thunder::get_thunder_odds(model, time)).abs() < 1e-9, "{} minutes", minutes);
+        }
+    }
+
+    #[test]
+    fn windows_match_the_simulator() {
+        let model: WeatherModel = WeatherModel::default();
+        let mut rng: StdRng = StdRng::seed_from_u64(1234);
+        let worlds: u64 = 20_000;
+
+        for (from, to) in [(60, 90), (200, 200), (300, 420)] {
+            let (from, to): (u64, u64) = (from * ONE_MINUTE_IN_TICKS, to * ONE_MINUTE_IN_TICKS);
+            let simulated: f64 = (0..worlds).filter(|_| simulate_thunder_between(&mut rng, model, from, to)).count() as f64 / worlds as f64;
+            let exact: f64 = thunder_odds_between(model, from, to);
+            let tolerance: f64 = 5.0 * (simulated * (1.0 - simulated) / worlds as f64).sqrt() + 2e-3;
+
+            assert!((exact - simulated).abs() <= tolerance, "{}..{}: {} vs simulated {}", from, to, exact, simulated);
+        }
+    }
+}
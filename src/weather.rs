@@ -0,0 +1,89 @@
+use std::ops::RangeInclusive;
+
+// Weather timing of the game versions runners use. Rain and thunder each keep a timer: when it runs
+// out the cycle switches on or off, and the next tick rolls how long the new phase lasts. Every range
+// below is the full length of a phase in ticks, so it includes that extra tick.
+// The weather rolls only changed once since 1.12, so every version between two changes shares a model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WeatherModel {
+    // 1.12 to 1.19: 12000 + nextInt(168000) between cycles, 12000 + nextInt(12000) of rain and
+    // 3600 + nextInt(12000) of thunder. 1.13 moved the rolls from World to ServerWorld unchanged.
+    #[default]
+    Java1_16,
+    // 1.20 and later: the rolls are UniformInt providers, whose upper bounds are inclusive, so every
+    // phase can last one tick longer. 1.20.5 and 1.21 roll the same way.
+    Java1_20,
+}
+
+impl WeatherModel {
+    pub const ALL: [WeatherModel; 2] = [WeatherModel::Java1_16, WeatherModel::Java1_20];
+
+    // Reads a version like "1.16" or "1.20.5". Versions before 1.12 aren't modeled.
+    pub fn from_version(version: &str) -> Option<Self> {
+        let mut parts = version.split('.');
+
+        if parts.next()? != "1" { return None; }
+        let minor: u32 = parts.next()?.parse().ok()?;
+        if let Some(patch) = parts.next() {
+            patch.parse::<u32>().ok()?;
+        }
+        if parts.next().is_some() { return None; }
+
+        match minor {
+            12..=19 => Some(WeatherModel::Java1_16),
+            20.. => Some(WeatherModel::Java1_20),
+            _ => None,
+        }
+    }
+
+    // Position in ALL, for per-model caches.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn time_between_cycles(self) -> RangeInclusive<u64> {
+        match self {
+            WeatherModel::Java1_16 => 12_001..=180_000,
+            WeatherModel::Java1_20 => 12_001..=180_001,
+        }
+    }
+
+    pub fn rain_duration(self) -> RangeInclusive<u64> {
+        match self {
+            WeatherModel::Java1_16 => 12_001..=24_000,
+            WeatherModel::Java1_20 => 12_001..=24_001,
+        }
+    }
+
+    pub fn thunder_duration(self) -> RangeInclusive<u64> {
+        match self {
+            WeatherModel::Java1_16 => 3_601..=15_600,
+            WeatherModel::Java1_20 => 3_601..=15_601,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_versions() {
+        assert_eq!(WeatherModel::from_version("1.12.2"), Some(WeatherModel::Java1_16));
+        assert_eq!(WeatherModel::from_version("1.16"), Some(WeatherModel::Java1_16));
+        assert_eq!(WeatherModel::from_version("1.16.1"), Some(WeatherModel::Java1_16));
+        assert_eq!(WeatherModel::from_version("1.20.5"), Some(WeatherModel::Java1_20));
+        assert_eq!(WeatherModel::from_version("1.21"), Some(WeatherModel::Java1_20));
+        assert_eq!(WeatherModel::from_version("1.8.9"), None);
+        assert_eq!(WeatherModel::from_version("1.16.x"), None);
+        assert_eq!(WeatherModel::from_version("1.16.1.1"), None);
+        assert_eq!(WeatherModel::from_version("16"), None);
+    }
+
+    #[test]
+    fn index_matches_all() {
+        for (i, model) in WeatherModel::ALL.iter().enumerate() {
+            assert_eq!(model.index(), i);
+        }
+    }
+}
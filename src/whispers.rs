@@ -0,0 +1,65 @@
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+
+const USERS_URL: &str = "https://api.twitch.tv/helix/users";
+const WHISPERS_URL: &str = "https://api.twitch.tv/helix/whispers";
+
+// Twitch stopped delivering whispers sent through chat, so they go through the Helix API instead.
+// The OAuth token needs the user:manage:whispers scope, and the bot account needs a verified phone number.
+pub struct Whispers {
+    client: Client,
+    client_id: String,
+    oauth_token: String,
+    // the bot's own user id, which whispers are sent from
+    user_id: String,
+}
+
+#[derive(Deserialize)]
+struct Users {
+    data: Vec<User>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+}
+
+impl Whispers {
+    // Looks up the user id of the account the token belongs to.
+    pub async fn connect(client_id: &str, oauth_token: &str) -> Result<Self, String> {
+        let mut whispers: Whispers = Whispers {
+            client: Client::new(),
+            client_id: client_id.to_owned(),
+            oauth_token: oauth_token.to_owned(),
+            user_id: String::new(),
+        };
+
+        let response = whispers.authorized(whispers.client.get(USERS_URL)).send().await.map_err(|err| err.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Twitch answered {} when looking up the bot's user id", response.status()));
+        }
+
+        let users: Users = response.json().await.map_err(|err| err.to_string())?;
+        whispers.user_id = users.data.into_iter().next().ok_or("the OAuth token doesn't belong to a user")?.id;
+
+        Ok(whispers)
+    }
+
+    pub async fn send(&self, to_user_id: &str, message: &str) -> Result<(), String> {
+        let request: RequestBuilder = self.client
+            .post(WHISPERS_URL)
+            .query(&[("from_user_id", self.user_id.as_str()), ("to_user_id", to_user_id)])
+            .json(&serde_json::json!({ "message": message }));
+
+        let response = self.authorized(request).send().await.map_err(|err| err.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Twitch answered {}", response.status()));
+        }
+
+        Ok(())
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        request.bearer_auth(&self.oauth_token).header("Client-Id", &self.client_id)
+    }
+}